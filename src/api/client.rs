@@ -1,25 +1,90 @@
-use reqwest::{Client, RequestBuilder};
-use std::time::Duration;
-use tracing::debug;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, SET_COOKIE};
+use reqwest::{Client, ClientBuilder, RequestBuilder, Response};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
-use crate::config::NetworkConfig;
+use super::cookies::{CookieJar, CookieRefresher};
+use super::rate_limit::RateLimiter;
+use super::resolver::FamilyResolver;
+use crate::config::{IpVersion, NetworkConfig};
 use crate::error::{Error, Result};
+use crate::fs::dump;
+
+/// Delay before the first connection retry; doubles on each further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
 
 const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
 
+/// Browser-like headers to avoid Cloudflare blocks; any can be turned off
+/// with `disabled_headers`
+const BUILTIN_HEADERS: &[(&str, &str)] = &[
+    ("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8"),
+    ("Accept-Language", "en-US,en;q=0.9"),
+    ("Accept-Encoding", "gzip, deflate, br"),
+    ("Sec-Ch-Ua", "\"Chromium\";v=\"120\", \"Not(A:Brand\";v=\"24\""),
+    ("Sec-Ch-Ua-Mobile", "?0"),
+    ("Sec-Ch-Ua-Platform", "\"Windows\""),
+    ("Sec-Fetch-Dest", "document"),
+    ("Sec-Fetch-Mode", "navigate"),
+    ("Sec-Fetch-Site", "none"),
+    ("Sec-Fetch-User", "?1"),
+    ("Upgrade-Insecure-Requests", "1"),
+    // Required header to bypass age verification
+    ("X-Requested-With", "XMLHttpRequest"),
+];
+
 pub struct ChaturbateClient {
     client: Client,
     domain: String,
+    fallback_domains: Vec<String>,
     user_agent: String,
-    cookies: Option<String>,
+    /// Configured cookies plus any the server sets, shared by all clones
+    cookie_jar: CookieJar,
+    cookie_refresher: Option<Arc<CookieRefresher>>,
+    transport: Transport,
+    /// Cookie-less client for webhook deliveries, on the same transport
+    webhook_client: Client,
+    /// [`BUILTIN_HEADERS`] minus the ones disabled in the config
+    builtin_headers: Vec<(&'static str, &'static str)>,
+    /// Configured extra headers, applied last
+    headers: HeaderMap,
+    cloudflare_markers: Vec<String>,
+    captcha_markers: Vec<String>,
+    age_markers: Vec<String>,
+    max_attempts: u32,
+    /// Request budget shared by all clones; `None` when unlimited
+    rate_limiter: Option<Arc<RateLimiter>>,
+    master_cache_ttl: Duration,
+    /// Master playlists by room, shared by all clones of this client
+    master_cache: Arc<Mutex<HashMap<String, CachedPlaylist>>>,
+}
+
+struct CachedPlaylist {
+    url: String,
+    content: String,
+    fetched_at: Instant,
 }
 
 impl ChaturbateClient {
     pub fn new(config: &NetworkConfig) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .connect_timeout(Duration::from_secs(10))
-            .build()?;
+        let domain = config.domain_with_trailing_slash();
+        let fallback_domains = config.fallback_domains_with_trailing_slash();
+        let jar_domains: Vec<String> = std::iter::once(&domain)
+            .chain(&fallback_domains)
+            .cloned()
+            .collect();
+        let cookie_jar = CookieJar::new(
+            config.cookie_jar_file.as_deref(),
+            config.cookies.as_deref(),
+            &jar_domains,
+        )?;
+
+        let transport = Transport::new(config)?;
+        let client = http_client(&cookie_jar, &transport)?;
+        let webhook_client = connection_builder(&transport).build()?;
 
         let user_agent = config
             .user_agent
@@ -28,56 +93,156 @@ impl ChaturbateClient {
 
         Ok(Self {
             client,
-            domain: config.domain_with_trailing_slash(),
+            domain,
+            fallback_domains,
             user_agent,
-            cookies: config.cookies.clone(),
+            cookie_jar,
+            cookie_refresher: config.cookie_refresh_command.as_ref().map(|command| {
+                Arc::new(CookieRefresher::new(
+                    command.clone(),
+                    Duration::from_secs(config.cookie_refresh_cooldown_seconds),
+                ))
+            }),
+            transport,
+            webhook_client,
+            builtin_headers: builtin_headers(&config.disabled_headers)?,
+            headers: extra_headers(config)?,
+            cloudflare_markers: config.cloudflare_markers.clone(),
+            captcha_markers: config.captcha_markers.clone(),
+            age_markers: config.age_markers.clone(),
+            max_attempts: config.max_request_attempts.max(1),
+            rate_limiter: RateLimiter::new(config.max_requests_per_second).map(Arc::new),
+            master_cache_ttl: Duration::from_secs(config.master_playlist_cache_seconds),
+            master_cache: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// A client that sends `cookies` instead of the configured ones, e.g. a
+    /// room's own session. It has its own connection pool and an in-memory
+    /// jar (never saved, never refreshed), but shares the rate limit.
+    pub fn with_cookies(&self, cookies: &str) -> Result<Self> {
+        let jar_domains: Vec<String> = std::iter::once(&self.domain)
+            .chain(&self.fallback_domains)
+            .cloned()
+            .collect();
+        let cookie_jar = CookieJar::new(None, Some(cookies), &jar_domains)?;
+
+        Ok(Self {
+            client: http_client(&cookie_jar, &self.transport)?,
+            cookie_jar,
+            cookie_refresher: None,
+            master_cache: Arc::new(Mutex::new(HashMap::new())),
+            ..self.clone()
+        })
+    }
+
+    /// A client for hosts other than the site, e.g. `--url-file` playlists.
+    /// Sends no cookies, no browser headers and no `[network.headers]`, and
+    /// has its own master cache; the transport and rate limit are shared.
+    pub fn plain(&self) -> Result<Self> {
+        let cookie_jar = CookieJar::new(None, None, &[])?;
+
+        Ok(Self {
+            client: http_client(&cookie_jar, &self.transport)?,
+            cookie_jar,
+            cookie_refresher: None,
+            builtin_headers: Vec::new(),
+            headers: HeaderMap::new(),
+            master_cache: Arc::new(Mutex::new(HashMap::new())),
+            ..self.clone()
         })
     }
 
     fn build_request(&self, url: &str) -> RequestBuilder {
         let mut req = self.client.get(url);
 
-        // Browser-like headers to avoid Cloudflare blocks
         req = req.header("User-Agent", &self.user_agent);
-        req = req.header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8");
-        req = req.header("Accept-Language", "en-US,en;q=0.9");
-        req = req.header("Accept-Encoding", "gzip, deflate, br");
-        req = req.header("Sec-Ch-Ua", "\"Chromium\";v=\"120\", \"Not(A:Brand\";v=\"24\"");
-        req = req.header("Sec-Ch-Ua-Mobile", "?0");
-        req = req.header("Sec-Ch-Ua-Platform", "\"Windows\"");
-        req = req.header("Sec-Fetch-Dest", "document");
-        req = req.header("Sec-Fetch-Mode", "navigate");
-        req = req.header("Sec-Fetch-Site", "none");
-        req = req.header("Sec-Fetch-User", "?1");
-        req = req.header("Upgrade-Insecure-Requests", "1");
-        // Required header to bypass age verification
-        req = req.header("X-Requested-With", "XMLHttpRequest");
-
-        if let Some(ref cookies) = self.cookies {
-            req = req.header("Cookie", cookies);
+        for (name, value) in &self.builtin_headers {
+            req = req.header(*name, *value);
         }
 
-        req
+        // Replaces any built-in header of the same name
+        req.headers(self.headers.clone())
+    }
+
+    /// Send a GET, retrying with exponential backoff when the connection
+    /// itself fails. Any HTTP response, including 4xx/5xx, is returned as-is
+    /// for the caller to interpret. Every attempt waits for the rate limiter.
+    async fn send(&self, url: &str) -> Result<Response> {
+        let mut delay = RETRY_BASE_DELAY;
+        let mut attempt = 1;
+
+        loop {
+            if let Some(limiter) = &self.rate_limiter {
+                limiter.acquire().await;
+            }
+
+            match self.build_request(url).send().await {
+                Ok(response) => return Ok(response),
+                Err(e) if attempt < self.max_attempts && is_connection_error(&e) => {
+                    debug!(
+                        "Request to {} failed ({}), retrying in {:?} (attempt {}/{})",
+                        url, e, delay, attempt, self.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
     }
 
     pub async fn get(&self, url: &str) -> Result<String> {
+        self.fetch(url, None).await
+    }
+
+    /// `get`, saving the response under `room` and `kind` for `--debug-dump`
+    /// whatever its status, so a page that fails the check can be inspected
+    async fn fetch(&self, url: &str, dump_as: Option<(&str, &str)>) -> Result<String> {
         debug!("GET {}", url);
         debug!("User-Agent: {}", self.user_agent);
-        if let Some(ref cookies) = self.cookies {
-            debug!("Cookies: {}...", &cookies.chars().take(50).collect::<String>());
+        if let Some(cookies) = self.cookie_jar.header_for(url) {
+            debug!(
+                "Cookies: {}...",
+                &cookies.chars().take(50).collect::<String>()
+            );
         }
-        let response = self.build_request(url).send().await?;
+        let response = self.send(url).await?;
 
         let status = response.status();
         debug!("Response status: {} for {}", status, url);
 
+        if response.headers().contains_key(SET_COOKIE) {
+            self.cookie_jar.save_soon();
+        }
+
         // Check for Cloudflare by looking for cf-ray header
         let is_cloudflare = response.headers().get("cf-ray").is_some();
 
+        // Error pages are read too: a challenge page says which kind it is,
+        // and a dump shows what came back
+        let text = self.read_text(response, url, dump_as).await;
+        if let (Some((room, kind)), Ok(text)) = (dump_as, &text) {
+            if dump::enabled() {
+                dump::save(room, kind, "html", text).await;
+                dump::save(
+                    room,
+                    &format!("{}-request", kind),
+                    "txt",
+                    &self.request_metadata(url),
+                )
+                .await;
+            }
+        }
+
         if status == reqwest::StatusCode::FORBIDDEN {
             if is_cloudflare {
                 debug!("Cloudflare 403 detected (cf-ray header present)");
-                return Err(Error::CloudflareBlocked);
+                let text = text.as_deref().unwrap_or_default();
+                return Err(self
+                    .challenge_error(text)
+                    .unwrap_or(Error::CloudflareBlocked));
             }
             return Err(Error::PrivateStream);
         }
@@ -89,47 +254,201 @@ impl ChaturbateClient {
         if status.is_server_error() {
             return Err(Error::ServerError(
                 status.as_u16(),
-                format!("{} for {}", status.canonical_reason().unwrap_or("Unknown"), url),
+                format!(
+                    "{} for {}",
+                    status.canonical_reason().unwrap_or("Unknown"),
+                    url
+                ),
             ));
         }
 
-        let text = response.text().await?;
+        let text = text?;
 
         // Check for Cloudflare challenge page
-        if text.contains("<title>Just a moment...</title>") || text.contains("cf-challenge") {
-            return Err(Error::CloudflareBlocked);
+        if let Some(e) = self.challenge_error(&text) {
+            return Err(e);
         }
 
         // Check for age verification
-        if text.contains("Verify your age") {
+        if contains_marker(&text, &self.age_markers) {
             return Err(Error::AgeVerification);
         }
 
         Ok(text)
     }
 
+    /// The body of `response` as text. Without decompression, a response
+    /// being dumped as `dump_as` also gets an exact copy as `<kind>-raw`.
+    async fn read_text(
+        &self,
+        response: Response,
+        url: &str,
+        dump_as: Option<(&str, &str)>,
+    ) -> Result<String> {
+        if self.transport.decompress {
+            return Ok(response.text().await?);
+        }
+
+        // Still encoded as the server sent it
+        let body = response.bytes().await?;
+        if let Some((room, kind)) = dump_as.filter(|_| dump::enabled()) {
+            debug!("Dumping raw response body of {}", url);
+            dump::save_bytes(room, &format!("{}-raw", kind), "bin", &body).await;
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// Run `cookie_refresh_command` (subject to its cooldown) and switch to
+    /// the cookies it prints. Returns whether cookies were replaced; `false`
+    /// when no command is configured or it ran too recently.
+    pub async fn refresh_cookies(&self) -> Result<bool> {
+        let Some(ref refresher) = self.cookie_refresher else {
+            return Ok(false);
+        };
+        let Some(cookies) = refresher.run().await? else {
+            return Ok(false);
+        };
+
+        self.cookie_jar.set_cookies(&cookies)?;
+        Ok(true)
+    }
+
+    /// What `get` sends to `url`, for debug dumps, with cookie values and
+    /// credential headers redacted. The fixed browser headers are left out.
+    fn request_metadata(&self, url: &str) -> String {
+        let mut lines = vec![
+            format!("GET {}", url),
+            format!("User-Agent: {}", self.user_agent),
+        ];
+
+        for (name, value) in &self.headers {
+            let value = if is_sensitive_header(name.as_str()) {
+                REDACTED
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            lines.push(format!("{}: {}", name, value));
+        }
+
+        if let Some(cookies) = self.cookie_jar.header_for(url) {
+            lines.push(format!("Cookie: {}", redact_cookies(&cookies)));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Classify a Cloudflare interstitial: a CAPTCHA needs a human, a plain
+    /// JS challenge may clear on its own
+    fn challenge_error(&self, text: &str) -> Option<Error> {
+        if contains_marker(text, &self.captcha_markers) {
+            Some(Error::CaptchaRequired)
+        } else if contains_marker(text, &self.cloudflare_markers) {
+            Some(Error::CloudflareBlocked)
+        } else {
+            None
+        }
+    }
+
     pub async fn get_bytes(&self, url: &str) -> Result<Vec<u8>> {
-        let response = self.build_request(url).send().await?;
+        let response = self.get_response(url).await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Send a GET and return the response without reading the body, so callers
+    /// can consume it incrementally via `bytes_stream()`.
+    pub async fn get_response(&self, url: &str) -> Result<Response> {
+        let response = self.send(url).await?;
 
         let status = response.status();
+        if status == reqwest::StatusCode::FORBIDDEN {
+            if response.headers().get("cf-ray").is_some() {
+                return Err(Error::CloudflareBlocked);
+            }
+            return Err(Error::PrivateStream);
+        }
+
         if !status.is_success() {
-            return Err(Error::Network(
-                response.error_for_status().unwrap_err()
-            ));
+            return Err(Error::Network(response.error_for_status().unwrap_err()));
         }
 
-        Ok(response.bytes().await?.to_vec())
+        Ok(response)
     }
 
+    /// Fetch a room page, falling back to each configured mirror in order
+    /// when the primary domain is Cloudflare-blocked or unreachable.
     pub async fn get_room_page(&self, room: &str) -> Result<String> {
-        let url = format!("{}{}/", self.domain, room);
-        debug!("Fetching room page: {}", url);
-        self.get(&url).await
+        let domains: Vec<&String> = std::iter::once(&self.domain)
+            .chain(&self.fallback_domains)
+            .collect();
+
+        for (attempt, domain) in domains.iter().enumerate() {
+            let is_last = attempt + 1 == domains.len();
+            let url = format!("{}{}/", domain, room);
+            debug!("Fetching room page: {}", url);
+
+            match self.fetch(&url, Some((room, "room-page"))).await {
+                Ok(html) => {
+                    if attempt > 0 {
+                        info!("Fetched {} via fallback domain {}", room, domain);
+                    } else {
+                        debug!("Fetched {} via {}", room, domain);
+                    }
+                    return Ok(html);
+                }
+                Err(e) if !is_last && should_try_fallback(&e) => {
+                    warn!("{} failed for {}: {}, trying next domain", domain, room, e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        unreachable!("primary domain is always attempted")
+    }
+
+    /// Fetch `room`'s master playlist, reusing the last copy while it is
+    /// younger than `master_playlist_cache_seconds` and the URL is unchanged.
+    /// Failed fetches are never cached.
+    pub async fn get_master_playlist(&self, room: &str, url: &str) -> Result<String> {
+        if !self.master_cache_ttl.is_zero() {
+            let cache = self.master_cache.lock().unwrap();
+            if let Some(cached) = cache.get(room) {
+                if cached.url == url && cached.fetched_at.elapsed() < self.master_cache_ttl {
+                    debug!("Using cached master playlist for {}", room);
+                    return Ok(cached.content.clone());
+                }
+            }
+        }
+
+        let content = self.get(url).await?;
+
+        if !self.master_cache_ttl.is_zero() {
+            self.master_cache.lock().unwrap().insert(
+                room.to_string(),
+                CachedPlaylist {
+                    url: url.to_string(),
+                    content: content.clone(),
+                    fetched_at: Instant::now(),
+                },
+            );
+        }
+
+        Ok(content)
+    }
+
+    /// Forget `room`'s cached master playlist so the next lookup fetches it
+    pub fn invalidate_master_playlist(&self, room: &str) {
+        self.master_cache.lock().unwrap().remove(room);
     }
 
     pub fn domain(&self) -> &str {
         &self.domain
     }
+
+    /// HTTP client for webhook endpoints: same local address and IP version
+    /// as site traffic, but no cookies, browser headers or TLS fingerprint
+    pub fn webhook_client(&self) -> Client {
+        self.webhook_client.clone()
+    }
 }
 
 impl Clone for ChaturbateClient {
@@ -137,8 +456,606 @@ impl Clone for ChaturbateClient {
         Self {
             client: self.client.clone(),
             domain: self.domain.clone(),
+            fallback_domains: self.fallback_domains.clone(),
             user_agent: self.user_agent.clone(),
-            cookies: self.cookies.clone(),
+            cookie_jar: self.cookie_jar.clone(),
+            cookie_refresher: self.cookie_refresher.clone(),
+            transport: self.transport.clone(),
+            webhook_client: self.webhook_client.clone(),
+            builtin_headers: self.builtin_headers.clone(),
+            headers: self.headers.clone(),
+            cloudflare_markers: self.cloudflare_markers.clone(),
+            captcha_markers: self.captcha_markers.clone(),
+            age_markers: self.age_markers.clone(),
+            max_attempts: self.max_attempts,
+            rate_limiter: self.rate_limiter.clone(),
+            master_cache_ttl: self.master_cache_ttl,
+            master_cache: Arc::clone(&self.master_cache),
         }
     }
 }
+
+/// How connections are made, from the network config
+#[derive(Debug, Clone)]
+struct Transport {
+    #[cfg_attr(not(feature = "tls-impersonate"), allow(dead_code))]
+    tls_impersonate: bool,
+    local_address: Option<IpAddr>,
+    ip_version: IpVersion,
+    decompress: bool,
+}
+
+impl Transport {
+    fn new(config: &NetworkConfig) -> Result<Self> {
+        let local_address = match config.bind_address {
+            Some(ref address) => Some(address.parse::<IpAddr>().map_err(|_| {
+                Error::Config(format!(
+                    "Invalid bind_address '{}': not an IP address",
+                    address
+                ))
+            })?),
+            None => None,
+        };
+
+        // A local address can only reach hosts of its own IP version
+        let ip_version = match (local_address, config.ip_version) {
+            (Some(IpAddr::V4(_)), IpVersion::V6) | (Some(IpAddr::V6(_)), IpVersion::V4) => {
+                return Err(Error::Config(format!(
+                    "bind_address {} doesn't match ip_version {}",
+                    config.bind_address.as_deref().unwrap_or_default(),
+                    config.ip_version
+                )));
+            }
+            (Some(IpAddr::V4(_)), IpVersion::Auto) => IpVersion::V4,
+            (Some(IpAddr::V6(_)), IpVersion::Auto) => IpVersion::V6,
+            (_, version) => version,
+        };
+
+        Ok(Self {
+            tls_impersonate: config.tls_impersonate,
+            local_address,
+            ip_version,
+            decompress: config.decompress,
+        })
+    }
+}
+
+/// Timeouts, local address and IP version, shared by every client we build
+fn connection_builder(transport: &Transport) -> ClientBuilder {
+    let builder = Client::builder()
+        .timeout(Duration::from_secs(30))
+        .connect_timeout(Duration::from_secs(10))
+        .local_address(transport.local_address);
+
+    if transport.ip_version != IpVersion::Auto {
+        builder.dns_resolver(Arc::new(FamilyResolver::new(transport.ip_version)))
+    } else {
+        builder
+    }
+}
+
+fn http_client(cookie_jar: &CookieJar, transport: &Transport) -> Result<Client> {
+    let mut builder = connection_builder(transport).cookie_provider(cookie_jar.provider());
+
+    if !transport.decompress {
+        builder = builder.no_gzip().no_deflate();
+    }
+
+    #[cfg(feature = "tls-impersonate")]
+    let builder = if transport.tls_impersonate {
+        builder.use_preconfigured_tls(super::tls::chrome_tls_config()?)
+    } else {
+        builder
+    };
+
+    Ok(builder.build()?)
+}
+
+const REDACTED: &str = "<redacted>";
+
+fn is_sensitive_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.contains("cookie") || name.contains("authorization") || name.contains("token")
+}
+
+/// `a=1; b=2` as `a=<redacted>; b=<redacted>`, keeping which cookies were sent
+fn redact_cookies(header: &str) -> String {
+    header
+        .split(';')
+        .map(|cookie| {
+            let name = cookie.split('=').next().unwrap_or("").trim();
+            format!("{}={}", name, REDACTED)
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn should_try_fallback(error: &Error) -> bool {
+    match error {
+        Error::CloudflareBlocked | Error::CaptchaRequired => true,
+        Error::Network(e) => e.is_connect() || e.is_timeout(),
+        _ => false,
+    }
+}
+
+/// Failures worth retrying: the request never got a response
+fn is_connection_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout() || (error.is_request() && !error.is_builder())
+}
+
+fn contains_marker(text: &str, markers: &[String]) -> bool {
+    markers
+        .iter()
+        .any(|marker| !marker.is_empty() && text.contains(marker.as_str()))
+}
+
+/// Built-in headers matching those of a desktop browser, sent after the
+/// User-Agent
+fn builtin_headers(disabled: &[String]) -> Result<Vec<(&'static str, &'static str)>> {
+    for name in disabled {
+        if !BUILTIN_HEADERS
+            .iter()
+            .any(|(builtin, _)| builtin.eq_ignore_ascii_case(name))
+        {
+            return Err(Error::Config(format!(
+                "'{}' in disabled_headers is not a built-in header",
+                name
+            )));
+        }
+    }
+
+    Ok(BUILTIN_HEADERS
+        .iter()
+        .filter(|(builtin, _)| {
+            !disabled
+                .iter()
+                .any(|name| builtin.eq_ignore_ascii_case(name))
+        })
+        .copied()
+        .collect())
+}
+
+/// Configured headers plus the `accept_encoding` override, which replaces the
+/// built-in value unless `headers` sets one itself
+fn extra_headers(config: &NetworkConfig) -> Result<HeaderMap> {
+    let mut headers = parse_headers(&config.headers)?;
+
+    if let Some(ref encoding) = config.accept_encoding {
+        let value = HeaderValue::from_str(encoding)
+            .map_err(|_| Error::Config(format!("Invalid accept_encoding '{}'", encoding)))?;
+        headers.entry(ACCEPT_ENCODING).or_insert(value);
+    }
+
+    Ok(headers)
+}
+
+/// Validate configured headers up front so a typo fails at startup rather
+/// than on every request
+fn parse_headers(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+
+    for (name, value) in headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::Config(format!("Invalid header name '{}'", name)))?;
+        let header_value = HeaderValue::from_str(value)
+            .map_err(|_| Error::Config(format!("Invalid value for header '{}'", name)))?;
+        map.insert(header_name, header_value);
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_headers_rejects_invalid() {
+        let bad_name = HashMap::from([("Bad Header".to_string(), "x".to_string())]);
+        assert!(parse_headers(&bad_name).is_err());
+
+        let bad_value = HashMap::from([("X-Test".to_string(), "a\nb".to_string())]);
+        assert!(parse_headers(&bad_value).is_err());
+    }
+
+    /// Minimal one-shot HTTP server that replies 200 with `body` and hands
+    /// back the raw (lowercased) request
+    async fn serve_once(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        serve_once_with("200 OK", "", body).await
+    }
+
+    /// Like `serve_once`, with a custom status and extra header lines
+    async fn serve_once_with(
+        status: &'static str,
+        headers: &'static str,
+        body: impl AsRef<[u8]> + Send + 'static,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = body.as_ref();
+            let head = format!(
+                "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                headers,
+                body.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(body).await.unwrap();
+            String::from_utf8_lossy(&request).to_lowercase()
+        });
+
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers_reach_server() {
+        let (url, server) = serve_once("ok").await;
+
+        let config = NetworkConfig {
+            headers: HashMap::from([
+                ("X-Test-Header".to_string(), "hello".to_string()),
+                ("Accept-Language".to_string(), "de-DE".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+
+        let body = client.get(&url).await.unwrap();
+        assert_eq!(body, "ok");
+
+        let request = server.await.unwrap();
+        assert!(request.contains("x-test-header: hello"));
+        assert!(request.contains("accept-language: de-de"));
+        assert!(!request.contains("en-us"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_builtin_header_omitted() {
+        let (url, server) = serve_once("ok").await;
+
+        let config = NetworkConfig {
+            disabled_headers: vec!["x-requested-with".to_string()],
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+        client.get(&url).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(!request.contains("x-requested-with"));
+        assert!(request.contains("sec-fetch-mode: navigate"));
+
+        let unknown = NetworkConfig {
+            disabled_headers: vec!["X-Made-Up".to_string()],
+            ..Default::default()
+        };
+        assert!(ChaturbateClient::new(&unknown).is_err());
+    }
+
+    /// `#EXTM3U\n#EXT-X-VERSION:3\n`, gzipped
+    const GZIP_PLAYLIST: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x53, 0x76, 0x8d, 0x08, 0xf1,
+        0x35, 0x0e, 0xe5, 0x52, 0x06, 0xd2, 0xba, 0x11, 0xba, 0x61, 0xae, 0x41, 0xc1, 0x9e, 0xfe,
+        0x7e, 0x56, 0xc6, 0x5c, 0x00, 0xc4, 0x30, 0xc4, 0xa6, 0x19, 0x00, 0x00, 0x00,
+    ];
+
+    #[tokio::test]
+    async fn test_decompress() {
+        let (url, _) = serve_once_with("200 OK", "Content-Encoding: gzip\r\n", GZIP_PLAYLIST).await;
+        let client = ChaturbateClient::new(&NetworkConfig::default()).unwrap();
+        assert_eq!(
+            client.get(&url).await.unwrap(),
+            "#EXTM3U\n#EXT-X-VERSION:3\n"
+        );
+
+        // Turned off, the body arrives exactly as sent
+        let (url, _) = serve_once_with("200 OK", "Content-Encoding: gzip\r\n", GZIP_PLAYLIST).await;
+        let config = NetworkConfig {
+            decompress: false,
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+        assert_eq!(
+            client.get(&url).await.unwrap(),
+            String::from_utf8_lossy(GZIP_PLAYLIST)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_accept_encoding_override() {
+        let (url, server) = serve_once("ok").await;
+
+        let config = NetworkConfig {
+            accept_encoding: Some("gzip".to_string()),
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+        assert_eq!(client.get(&url).await.unwrap(), "ok");
+
+        let request = server.await.unwrap();
+        assert!(request.contains("accept-encoding: gzip\r\n"));
+
+        // An explicit header still wins
+        let config = NetworkConfig {
+            accept_encoding: Some("gzip".to_string()),
+            headers: HashMap::from([("Accept-Encoding".to_string(), "identity".to_string())]),
+            ..Default::default()
+        };
+        let headers = extra_headers(&config).unwrap();
+        assert_eq!(headers[ACCEPT_ENCODING], "identity");
+    }
+
+    #[tokio::test]
+    async fn test_bind_address() {
+        let (url, server) = serve_once("ok").await;
+
+        let config = NetworkConfig {
+            bind_address: Some("127.0.0.1".to_string()),
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+        assert_eq!(client.get(&url).await.unwrap(), "ok");
+        server.await.unwrap();
+
+        let invalid = NetworkConfig {
+            bind_address: Some("eth0".to_string()),
+            ..Default::default()
+        };
+        assert!(ChaturbateClient::new(&invalid).is_err());
+
+        let mismatched = NetworkConfig {
+            bind_address: Some("127.0.0.1".to_string()),
+            ip_version: IpVersion::V6,
+            ..Default::default()
+        };
+        assert!(ChaturbateClient::new(&mismatched).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retries_dropped_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            // Hang up on the first connection without answering
+            let (socket, _) = listener.accept().await.unwrap();
+            drop(socket);
+
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+        });
+
+        let client = ChaturbateClient::new(&NetworkConfig::default()).unwrap();
+        assert_eq!(client.get(&url).await.unwrap(), "ok");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_errors_not_retried() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            // Dropping the listener here makes any retry fail to connect
+        });
+
+        let client = ChaturbateClient::new(&NetworkConfig::default()).unwrap();
+        assert!(matches!(
+            client.get(&url).await,
+            Err(Error::RoomNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_set_cookie_sent_on_next_request() {
+        let (first, _server) =
+            serve_once_with("200 OK", "Set-Cookie: cf_clearance=fresh; Path=/\r\n", "ok").await;
+        // Cookies are scoped by host, not port, so the second server sees them
+        let (second, server) = serve_once("ok").await;
+
+        let config = NetworkConfig {
+            cookies: Some("sessionid=abc".to_string()),
+            domain: first.clone(),
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+
+        client.get(&first).await.unwrap();
+        client.clone().get(&second).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.contains("cf_clearance=fresh"));
+        assert!(request.contains("sessionid=abc"));
+    }
+
+    #[tokio::test]
+    async fn test_with_cookies_replaces_configured_cookies() {
+        let (url, server) = serve_once("ok").await;
+
+        let config = NetworkConfig {
+            cookies: Some("sessionid=global".to_string()),
+            domain: url.clone(),
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+        let room_client = client.with_cookies("sessionid=room").unwrap();
+
+        room_client.get(&url).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.contains("sessionid=room"));
+        assert!(!request.contains("sessionid=global"));
+    }
+
+    #[tokio::test]
+    async fn test_plain_client_sends_no_site_headers() {
+        let (url, server) = serve_once("ok").await;
+
+        let config = NetworkConfig {
+            cookies: Some("sessionid=secret".to_string()),
+            domain: url.clone(),
+            headers: HashMap::from([("X-Test-Header".to_string(), "hello".to_string())]),
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap().plain().unwrap();
+
+        client.get(&url).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(!request.contains("sessionid"));
+        assert!(!request.contains("x-test-header"));
+        assert!(!request.contains("x-requested-with"));
+    }
+
+    #[test]
+    fn test_request_metadata_redacts_credentials() {
+        let config = NetworkConfig {
+            cookies: Some("sessionid=secret1; cf_clearance=secret2".to_string()),
+            headers: HashMap::from([
+                ("Authorization".to_string(), "Bearer secret3".to_string()),
+                ("Referer".to_string(), "https://example.com/".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+
+        let metadata = client.request_metadata(&format!("{}alice/", client.domain()));
+        assert!(!metadata.contains("secret"), "{}", metadata);
+        assert!(metadata.contains("sessionid=<redacted>"), "{}", metadata);
+        assert!(
+            metadata.contains("referer: https://example.com/"),
+            "{}",
+            metadata
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_cloudflare_marker() {
+        let (url, _server) = serve_once("<title>Un instant...</title>").await;
+
+        let config = NetworkConfig {
+            cloudflare_markers: vec!["Un instant...".to_string()],
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+
+        assert!(matches!(
+            client.get(&url).await,
+            Err(Error::CloudflareBlocked)
+        ));
+    }
+
+    #[test]
+    fn test_challenge_error_kinds() {
+        let client = ChaturbateClient::new(&NetworkConfig::default()).unwrap();
+
+        let js_challenge = r#"<html><head><title>Just a moment...</title></head>
+            <script>window._cf_chl_opt={cvId: '3', cType: 'non-interactive'};</script></html>"#;
+        assert!(matches!(
+            client.challenge_error(js_challenge),
+            Some(Error::CloudflareBlocked)
+        ));
+
+        let managed = r#"<html><head><title>Just a moment...</title></head>
+            <script>window._cf_chl_opt={cvId: '3', cType: 'managed'};</script></html>"#;
+        assert!(matches!(
+            client.challenge_error(managed),
+            Some(Error::CaptchaRequired)
+        ));
+
+        let turnstile = r#"<div class="cf-turnstile" data-sitekey="x"></div>
+            <script src="https://challenges.cloudflare.com/turnstile/v0/api.js"></script>"#;
+        assert!(matches!(
+            client.challenge_error(turnstile),
+            Some(Error::CaptchaRequired)
+        ));
+
+        assert!(client.challenge_error("<html>room page</html>").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cloudflare_403_body_is_classified() {
+        let (url, _server) = serve_once_with(
+            "403 Forbidden",
+            "cf-ray: 1234-AMS\r\n",
+            "<title>Just a moment...</title><div class=\"cf-turnstile\"></div>",
+        )
+        .await;
+        let client = ChaturbateClient::new(&NetworkConfig::default()).unwrap();
+
+        assert!(matches!(
+            client.get(&url).await,
+            Err(Error::CaptchaRequired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_room_page_uses_fallback_domain() {
+        // Grab a free port and close it so the primary refuses connections
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+
+        let (fallback, server) = serve_once("room page").await;
+
+        let config = NetworkConfig {
+            domain: primary,
+            fallback_domains: vec![fallback],
+            ..Default::default()
+        };
+        let client = ChaturbateClient::new(&config).unwrap();
+
+        assert_eq!(client.get_room_page("alice").await.unwrap(), "room page");
+        assert!(server.await.unwrap().starts_with("get /alice/ "));
+    }
+
+    #[tokio::test]
+    async fn test_master_playlist_cached_until_invalidated() {
+        let (url, _server) = serve_once("#EXTM3U").await;
+        let client = ChaturbateClient::new(&NetworkConfig::default()).unwrap();
+
+        assert_eq!(
+            client.get_master_playlist("alice", &url).await.unwrap(),
+            "#EXTM3U"
+        );
+        // The one-shot server is gone, so this can only come from the cache
+        assert_eq!(
+            client
+                .clone()
+                .get_master_playlist("alice", &url)
+                .await
+                .unwrap(),
+            "#EXTM3U"
+        );
+
+        client.invalidate_master_playlist("alice");
+        assert!(client.get_master_playlist("alice", &url).await.is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use cookie_store::CookieStore;
+use reqwest_cookie_store::CookieStoreMutex;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// Longest a `cookie_refresh_command` may run before it's killed
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long `save_soon` waits, so a burst of `Set-Cookie` responses is
+/// written once
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Makes each save's temporary file name unique within the process
+static SAVE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Cookie store shared by the HTTP client and all its clones. Picks up
+/// `Set-Cookie` responses and, with a jar file, keeps them across runs.
+pub struct CookieJar {
+    store: Arc<CookieStoreMutex>,
+    file: Option<PathBuf>,
+    /// Domains the configured cookies are sent to
+    domains: Vec<Url>,
+    /// Set while a `save_soon` write is scheduled, shared by all clones
+    save_pending: Arc<AtomicBool>,
+}
+
+impl CookieJar {
+    /// Load the jar from `file` if it exists, then apply the configured
+    /// `cookies` for each of `domains`. Configured cookies replace saved ones
+    /// of the same name. A jar file that can't be read starts an empty jar.
+    pub fn new(file: Option<&str>, cookies: Option<&str>, domains: &[String]) -> Result<Self> {
+        let file = file.map(PathBuf::from);
+        let mut store = file.as_deref().map(load_store).unwrap_or_default();
+        let domains = domains
+            .iter()
+            .map(|d| Url::parse(d))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        if let Some(cookies) = cookies {
+            for url in &domains {
+                seed_cookies(&mut store, cookies, url);
+            }
+        }
+
+        Ok(Self {
+            store: Arc::new(CookieStoreMutex::new(store)),
+            file,
+            domains,
+            save_pending: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Apply a new cookie string for every domain, replacing cookies of the
+    /// same name, and save the jar
+    pub fn set_cookies(&self, cookies: &str) -> Result<()> {
+        {
+            let mut store = self.store.lock().unwrap();
+            for url in &self.domains {
+                seed_cookies(&mut store, cookies, url);
+            }
+        }
+        self.save()
+    }
+
+    /// The `Cookie` header value that would be sent to `url`
+    pub fn header_for(&self, url: &str) -> Option<String> {
+        let url = Url::parse(url).ok()?;
+        let store = self.store.lock().unwrap();
+        let pairs: Vec<String> = store
+            .get_request_values(&url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+
+    /// The store to hand to `reqwest::ClientBuilder::cookie_provider`
+    pub fn provider(&self) -> Arc<CookieStoreMutex> {
+        Arc::clone(&self.store)
+    }
+
+    /// Write persistent, unexpired cookies to the jar file, if configured.
+    /// Session-only cookies (including configured `cookies`) aren't saved.
+    pub fn save(&self) -> Result<()> {
+        let Some(ref file) = self.file else {
+            return Ok(());
+        };
+
+        let mut data = Vec::new();
+        {
+            let store = self.store.lock().unwrap();
+            cookie_store::serde::json::save(&store, &mut data)
+                .map_err(|e| Error::Config(format!("Failed to serialize cookie jar: {}", e)))?;
+        }
+
+        // Write then rename so a crash never leaves a truncated jar; the
+        // temporary name is unique so concurrent saves can't interleave
+        let mut tmp = file.clone().into_os_string();
+        tmp.push(format!(
+            ".{}-{}.tmp",
+            std::process::id(),
+            SAVE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, file)?;
+        Ok(())
+    }
+
+    /// `save` in the background after [`SAVE_DELAY`], folding the cookies
+    /// set meanwhile into the same write. Failures are only logged.
+    pub fn save_soon(&self) {
+        if self.file.is_none() || self.save_pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let jar = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(SAVE_DELAY).await;
+            jar.save_pending.store(false, Ordering::Release);
+            match tokio::task::spawn_blocking(move || jar.save()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!("Failed to save cookie jar: {}", e),
+                Err(e) => tracing::warn!("Failed to save cookie jar: {}", e),
+            }
+        });
+    }
+}
+
+impl Clone for CookieJar {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            file: self.file.clone(),
+            domains: self.domains.clone(),
+            save_pending: Arc::clone(&self.save_pending),
+        }
+    }
+}
+
+/// Runs `cookie_refresh_command` at most once per cooldown, shared by all
+/// clones of the client
+pub struct CookieRefresher {
+    command: String,
+    cooldown: Duration,
+    last_run: Mutex<Option<Instant>>,
+}
+
+impl CookieRefresher {
+    pub fn new(command: String, cooldown: Duration) -> Self {
+        Self {
+            command,
+            cooldown,
+            last_run: Mutex::new(None),
+        }
+    }
+
+    /// Run the command unless it ran within the cooldown. Returns the
+    /// trimmed cookie string it printed, or `None` while cooling down.
+    pub async fn run(&self) -> Result<Option<String>> {
+        {
+            let mut last_run = self.last_run.lock().unwrap();
+            if last_run.is_some_and(|t| t.elapsed() < self.cooldown) {
+                return Ok(None);
+            }
+            *last_run = Some(Instant::now());
+        }
+
+        tracing::info!("Running cookie refresh command");
+        run_command(&self.command).await.map(Some)
+    }
+}
+
+async fn run_command(command: &str) -> Result<String> {
+    #[cfg(windows)]
+    let mut cmd = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    cmd.arg("/C");
+    #[cfg(not(windows))]
+    let mut cmd = tokio::process::Command::new("sh");
+    #[cfg(not(windows))]
+    cmd.arg("-c");
+
+    cmd.arg(command).kill_on_drop(true);
+
+    let output = tokio::time::timeout(REFRESH_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| Error::CookieRefresh(format!("timed out after {:?}", REFRESH_TIMEOUT)))?
+        .map_err(|e| Error::CookieRefresh(e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::CookieRefresh(format!(
+            "{}: {}",
+            output.status,
+            stderr.trim()
+        )));
+    }
+
+    let cookies = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if cookies.is_empty() {
+        return Err(Error::CookieRefresh("printed no cookies".to_string()));
+    }
+
+    Ok(cookies)
+}
+
+fn load_store(path: &Path) -> CookieStore {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return CookieStore::default(),
+        Err(e) => {
+            tracing::warn!(
+                "Cannot read cookie jar {}: {}; starting empty",
+                path.display(),
+                e
+            );
+            return CookieStore::default();
+        }
+    };
+
+    cookie_store::serde::json::load(BufReader::new(file)).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring malformed cookie jar {}: {}", path.display(), e);
+        CookieStore::default()
+    })
+}
+
+/// Add `name=value` pairs from a `Cookie` header string as cookies for `url`
+fn seed_cookies(store: &mut CookieStore, cookies: &str, url: &Url) {
+    // A Domain attribute also sends them to subdomains, e.g. www.; IP hosts
+    // can't have one
+    let attributes = match url.domain() {
+        Some(domain) => format!("Path=/; Domain={}", domain),
+        None => "Path=/".to_string(),
+    };
+    for pair in cookies.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        if let Err(e) = store.parse(&format!("{}; {}", pair, attributes), url) {
+            tracing::warn!("Ignoring cookie '{}': {}", pair, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie_header(jar: &CookieJar, url: &str) -> String {
+        let header = jar.header_for(url).unwrap_or_default();
+        let mut pairs: Vec<&str> = header.split("; ").filter(|p| !p.is_empty()).collect();
+        pairs.sort();
+        pairs.join("; ")
+    }
+
+    #[test]
+    fn test_configured_cookies_seed_each_domain() {
+        let domains = vec![
+            "https://a.example/".to_string(),
+            "https://b.example/".to_string(),
+        ];
+        let jar = CookieJar::new(None, Some("cf_clearance=abc; sessionid=xyz;"), &domains).unwrap();
+
+        assert_eq!(
+            cookie_header(&jar, "https://a.example/room/"),
+            "cf_clearance=abc; sessionid=xyz"
+        );
+        assert_eq!(
+            cookie_header(&jar, "https://b.example/"),
+            "cf_clearance=abc; sessionid=xyz"
+        );
+        assert_eq!(
+            cookie_header(&jar, "https://www.a.example/"),
+            "cf_clearance=abc; sessionid=xyz"
+        );
+        assert_eq!(cookie_header(&jar, "https://other.example/"), "");
+    }
+
+    #[test]
+    fn test_jar_round_trip_and_malformed_file() {
+        let path = std::env::temp_dir().join(format!("cbr-cookies-{}.json", std::process::id()));
+        let domains = vec!["https://a.example/".to_string()];
+
+        let jar = CookieJar::new(Some(path.to_str().unwrap()), None, &domains).unwrap();
+        {
+            let url = Url::parse("https://a.example/").unwrap();
+            let mut store = jar.store.lock().unwrap();
+            store
+                .parse("cf_clearance=fresh; Path=/; Max-Age=3600", &url)
+                .unwrap();
+            store.parse("session_only=1; Path=/", &url).unwrap();
+        }
+        jar.save().unwrap();
+
+        let reloaded = CookieJar::new(Some(path.to_str().unwrap()), None, &domains).unwrap();
+        assert_eq!(
+            cookie_header(&reloaded, "https://a.example/"),
+            "cf_clearance=fresh"
+        );
+
+        std::fs::write(&path, "not json").unwrap();
+        let empty = CookieJar::new(Some(path.to_str().unwrap()), None, &domains).unwrap();
+        assert_eq!(cookie_header(&empty, "https://a.example/"), "");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_soon_writes_once_after_a_burst() {
+        let dir = std::env::temp_dir().join(format!("cbr-cookies-soon-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cookies.json");
+        let domains = vec!["https://a.example/".to_string()];
+
+        let jar = CookieJar::new(Some(path.to_str().unwrap()), None, &domains).unwrap();
+        {
+            let url = Url::parse("https://a.example/").unwrap();
+            let mut store = jar.store.lock().unwrap();
+            store
+                .parse("cf_clearance=fresh; Path=/; Max-Age=3600", &url)
+                .unwrap();
+        }
+        jar.save_soon();
+        jar.clone().save_soon();
+        assert!(!path.exists());
+
+        tokio::time::sleep(SAVE_DELAY + Duration::from_millis(500)).await;
+        let reloaded = CookieJar::new(Some(path.to_str().unwrap()), None, &domains).unwrap();
+        assert_eq!(
+            cookie_header(&reloaded, "https://a.example/"),
+            "cf_clearance=fresh"
+        );
+        // Only the jar itself is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_refresher_runs_once_per_cooldown() {
+        let refresher = CookieRefresher::new(
+            "echo ' cf_clearance=new '".to_string(),
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            refresher.run().await.unwrap().as_deref(),
+            Some("cf_clearance=new")
+        );
+        assert!(refresher.run().await.unwrap().is_none());
+
+        let failing = CookieRefresher::new("echo oops >&2; exit 3".to_string(), Duration::ZERO);
+        let err = failing.run().await.unwrap_err().to_string();
+        assert!(err.contains("oops"), "{}", err);
+
+        let silent = CookieRefresher::new("true".to_string(), Duration::ZERO);
+        assert!(silent.run().await.is_err());
+    }
+
+    #[test]
+    fn test_set_cookies_replaces_by_name() {
+        let domains = vec!["https://a.example/".to_string()];
+        let jar = CookieJar::new(None, Some("cf_clearance=old; sessionid=xyz"), &domains).unwrap();
+
+        jar.set_cookies("cf_clearance=new").unwrap();
+        assert_eq!(
+            cookie_header(&jar, "https://a.example/"),
+            "cf_clearance=new; sessionid=xyz"
+        );
+    }
+}
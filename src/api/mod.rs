@@ -1,3 +1,10 @@
 mod client;
+mod cookies;
+mod rate_limit;
+mod resolver;
+#[cfg(feature = "tls-impersonate")]
+mod tls;
+mod webhook;
 
 pub use client::ChaturbateClient;
+pub use webhook::{sign_payload, Severity, WebhookNotifier};
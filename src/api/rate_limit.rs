@@ -0,0 +1,82 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Token bucket shared by every clone of a client, so all rooms together
+/// stay under `rate` requests per second. Up to one second's worth of
+/// requests may burst after an idle period.
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Negative while callers are queued for tokens not yet refilled
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// `None` when `rate` is 0 (unlimited)
+    pub fn new(rate: f64) -> Option<Self> {
+        if rate <= 0.0 {
+            return None;
+        }
+
+        let capacity = rate.max(1.0);
+        Some(Self {
+            rate,
+            capacity,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+        })
+    }
+
+    /// Wait for a token. Each caller reserves its token up front, so waiters
+    /// are served in the order they arrived.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.updated).as_secs_f64() * self.rate;
+            bucket.tokens = (bucket.tokens + refill).min(self.capacity);
+            bucket.updated = now;
+
+            bucket.tokens -= 1.0;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        };
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_rate_is_unlimited() {
+        assert!(RateLimiter::new(0.0).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_throttles_after_burst() {
+        let limiter = RateLimiter::new(50.0).unwrap();
+        let started = Instant::now();
+
+        // A full bucket of 50 goes through at once; the next 5 wait 20ms each
+        for _ in 0..55 {
+            limiter.acquire().await;
+        }
+
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(90), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    }
+}
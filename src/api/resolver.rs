@@ -0,0 +1,52 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::SocketAddr;
+
+use crate::config::IpVersion;
+
+/// System DNS lookup that keeps only addresses of one IP version, so
+/// connections never leave over the other one
+pub struct FamilyResolver {
+    version: IpVersion,
+}
+
+impl FamilyResolver {
+    pub fn new(version: IpVersion) -> Self {
+        Self { version }
+    }
+}
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let version = self.version;
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| version.allows(addr))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!("{} has no {} address", host, version).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolver_keeps_one_family() {
+        let resolver = FamilyResolver::new(IpVersion::V4);
+        let addrs: Vec<_> = resolver
+            .resolve("localhost".parse().unwrap())
+            .await
+            .unwrap()
+            .collect();
+
+        assert!(!addrs.is_empty());
+        assert!(addrs.iter().all(SocketAddr::is_ipv4));
+    }
+}
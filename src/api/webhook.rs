@@ -0,0 +1,365 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+use crate::config::{MonitorConfig, WebhookEndpoint, WebhookEvent, WebhookFormat};
+use crate::output::stats::format_duration;
+
+/// Slack rejects section text longer than this
+const SLACK_SECTION_LIMIT: usize = 3000;
+
+/// How good or bad the news in a notification is; colors Slack messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    /// Something recovered
+    Good,
+    Warning,
+    /// Needs attention, e.g. cookie death
+    Critical,
+}
+
+impl Severity {
+    fn slack_color(self) -> &'static str {
+        match self {
+            Severity::Info => "#439fe0",
+            Severity::Good => "#2eb67d",
+            Severity::Warning => "#ecb22e",
+            Severity::Critical => "#e01e5a",
+        }
+    }
+}
+
+/// Delivers monitor notifications to the configured webhook endpoints
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    endpoints: Vec<WebhookEndpoint>,
+    secret: Option<String>,
+    max_length: usize,
+    format: WebhookFormat,
+}
+
+impl WebhookNotifier {
+    /// Notifier for `config`'s endpoints, delivering through `client`
+    /// (see `ChaturbateClient::webhook_client`)
+    pub fn new(config: &MonitorConfig, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            endpoints: config.webhook_endpoints(),
+            secret: config.webhook_secret.clone().filter(|s| !s.is_empty()),
+            max_length: config.webhook_max_length,
+            format: config.webhook_format,
+        }
+    }
+
+    pub fn endpoint_count(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// Send a notification to every endpoint subscribed to `event`
+    /// (fire-and-forget; failures are only logged)
+    pub async fn send(&self, event: WebhookEvent, message: &str) {
+        self.send_with_severity(event, Severity::Info, message)
+            .await;
+    }
+
+    /// `send`, with a severity that colors Slack messages
+    pub async fn send_with_severity(&self, event: WebhookEvent, severity: Severity, message: &str) {
+        let endpoints: Vec<&WebhookEndpoint> =
+            self.endpoints.iter().filter(|w| w.accepts(event)).collect();
+        self.deliver(&endpoints, event, severity, message, serde_json::Map::new())
+            .await;
+    }
+
+    /// Send a still-alive ping carrying the number of running recordings and
+    /// the monitor's uptime
+    pub async fn send_heartbeat(&self, active_recordings: usize, uptime: Duration) {
+        let message = format!(
+            "💓 Recorder alive: {} recording(s), up {}",
+            active_recordings,
+            format_duration(uptime.as_secs_f64())
+        );
+        let mut fields = serde_json::Map::new();
+        fields.insert("active_recordings".to_string(), active_recordings.into());
+        fields.insert("uptime_seconds".to_string(), uptime.as_secs().into());
+
+        let endpoints = heartbeat_endpoints(&self.endpoints);
+        self.deliver(
+            &endpoints,
+            WebhookEvent::Heartbeat,
+            Severity::Info,
+            &message,
+            fields,
+        )
+        .await;
+    }
+
+    /// Sent to heartbeat endpoints instead of a heartbeat when the monitor
+    /// hasn't started a check cycle for `since`
+    pub async fn send_stalled(&self, since: Duration) {
+        let message = format!(
+            "⚠️ Recorder stalled: no room checks for {}",
+            format_duration(since.as_secs_f64())
+        );
+        let mut fields = serde_json::Map::new();
+        fields.insert("stalled_seconds".to_string(), since.as_secs().into());
+
+        let endpoints = heartbeat_endpoints(&self.endpoints);
+        self.deliver(
+            &endpoints,
+            WebhookEvent::Heartbeat,
+            Severity::Warning,
+            &message,
+            fields,
+        )
+        .await;
+    }
+
+    /// POST one notification to each of `endpoints`; `extra` is merged into
+    /// JSON payloads
+    async fn deliver(
+        &self,
+        endpoints: &[&WebhookEndpoint],
+        event: WebhookEvent,
+        severity: Severity,
+        message: &str,
+        extra: serde_json::Map<String, serde_json::Value>,
+    ) {
+        if endpoints.is_empty() {
+            return;
+        }
+
+        let text = sanitize_message(message, self.max_length);
+        let timestamp = chrono::Utc::now().to_rfc3339();
+
+        for endpoint in endpoints {
+            let payload = match endpoint.format.unwrap_or(self.format) {
+                WebhookFormat::Json => {
+                    let mut payload = serde_json::json!({
+                        "text": text,
+                        "event": event,
+                        "source": "chaturbate-recorder",
+                        "timestamp": timestamp,
+                    });
+                    if let Some(object) = payload.as_object_mut() {
+                        object.extend(extra.clone());
+                    }
+                    payload
+                }
+                WebhookFormat::Slack => slack_payload(event, severity, &text, &timestamp),
+            };
+            let body = serde_json::to_string(&payload).unwrap_or_default();
+
+            let url = &endpoint.url;
+            let mut req = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .timeout(Duration::from_secs(10));
+
+            // Signed over the exact bytes that go on the wire
+            if let Some(ref secret) = self.secret {
+                req = req.header("X-Signature", sign_payload(secret, &body));
+            }
+            let req = req.body(body);
+
+            match req.send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    tracing::debug!("Webhook sent successfully to {}", url);
+                }
+                Ok(resp) => {
+                    tracing::warn!("Webhook returned {}: {}", resp.status(), url);
+                }
+                Err(e) => {
+                    tracing::warn!("Webhook failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Endpoints that receive heartbeats: those subscribed to them explicitly,
+/// or, when there are none, every endpoint that takes all events. Heartbeats
+/// can then go to a separate dead-man's-switch service without also landing
+/// in the notification channels.
+fn heartbeat_endpoints(endpoints: &[WebhookEndpoint]) -> Vec<&WebhookEndpoint> {
+    let subscribed: Vec<&WebhookEndpoint> = endpoints
+        .iter()
+        .filter(|w| w.events.contains(&WebhookEvent::Heartbeat))
+        .collect();
+    if !subscribed.is_empty() {
+        return subscribed;
+    }
+    endpoints.iter().filter(|w| w.events.is_empty()).collect()
+}
+
+/// Slack incoming-webhook message: a colored attachment holding a header,
+/// the message as a section, and a context line. `text` is the notification
+/// fallback for clients that don't render blocks.
+fn slack_payload(
+    event: WebhookEvent,
+    severity: Severity,
+    text: &str,
+    timestamp: &str,
+) -> serde_json::Value {
+    let title = match event {
+        WebhookEvent::Cookie => "Cookie status",
+        WebhookEvent::RecordingStart => "Recording started",
+        WebhookEvent::RecordingStop => "Recording stopped",
+        WebhookEvent::RecordingPrivate => "Show went private",
+        WebhookEvent::Heartbeat => "Heartbeat",
+    };
+    let section: String = escape_slack(text)
+        .chars()
+        .take(SLACK_SECTION_LIMIT)
+        .collect();
+
+    serde_json::json!({
+        "text": text,
+        "attachments": [{
+            "color": severity.slack_color(),
+            "blocks": [
+                {
+                    "type": "header",
+                    "text": { "type": "plain_text", "text": title, "emoji": true },
+                },
+                {
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": section },
+                },
+                {
+                    "type": "context",
+                    "elements": [
+                        { "type": "mrkdwn", "text": format!("chaturbate-recorder · {}", timestamp) },
+                    ],
+                },
+            ],
+        }],
+    })
+}
+
+/// Escape the characters Slack treats as markup in message text
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Strip control characters (keeping newlines) and cut the text to
+/// `max_length` characters, so untrusted content like room titles can't
+/// break or bloat a chat message. `max_length` 0 means no limit.
+fn sanitize_message(message: &str, max_length: usize) -> String {
+    let clean: String = message
+        .chars()
+        .filter(|c| *c == '\n' || !c.is_control())
+        .collect();
+
+    if max_length == 0 || clean.chars().count() <= max_length {
+        return clean;
+    }
+
+    let mut truncated: String = clean.chars().take(max_length.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// GitHub-style `sha256=<hex>` HMAC-SHA256 signature of `body`
+pub fn sign_payload(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        assert_eq!(
+            sign_payload("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_slack_payload_for_cookie_death() {
+        let payload = slack_payload(
+            WebhookEvent::Cookie,
+            Severity::Critical,
+            "🍪 Cookie death: 3/4 rooms <blocked>",
+            "2024-01-02T03:04:05+00:00",
+        );
+
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "text": "🍪 Cookie death: 3/4 rooms <blocked>",
+                "attachments": [{
+                    "color": "#e01e5a",
+                    "blocks": [
+                        {
+                            "type": "header",
+                            "text": { "type": "plain_text", "text": "Cookie status", "emoji": true },
+                        },
+                        {
+                            "type": "section",
+                            "text": { "type": "mrkdwn", "text": "🍪 Cookie death: 3/4 rooms &lt;blocked&gt;" },
+                        },
+                        {
+                            "type": "context",
+                            "elements": [{
+                                "type": "mrkdwn",
+                                "text": "chaturbate-recorder · 2024-01-02T03:04:05+00:00",
+                            }],
+                        },
+                    ],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_endpoints() {
+        let endpoint = |url: &str, events: Vec<WebhookEvent>| WebhookEndpoint {
+            url: url.to_string(),
+            events,
+            format: None,
+        };
+        let all = endpoint("https://example.com/all", vec![]);
+        let cookie = endpoint("https://example.com/cookie", vec![WebhookEvent::Cookie]);
+        let heartbeat = endpoint(
+            "https://example.com/heartbeat",
+            vec![WebhookEvent::Heartbeat],
+        );
+
+        let urls = |endpoints: &[WebhookEndpoint]| -> Vec<String> {
+            heartbeat_endpoints(endpoints)
+                .iter()
+                .map(|w| w.url.clone())
+                .collect()
+        };
+        assert_eq!(
+            urls(&[all.clone(), cookie.clone()]),
+            ["https://example.com/all"]
+        );
+        assert_eq!(
+            urls(&[all, cookie.clone(), heartbeat]),
+            ["https://example.com/heartbeat"]
+        );
+        assert!(urls(&[cookie]).is_empty());
+    }
+
+    #[test]
+    fn test_sanitize_message() {
+        let message = format!("🔴 alice\u{1b}[31m started\r\n{}\u{0}", "x".repeat(100));
+        let clean = sanitize_message(&message, 25);
+
+        assert_eq!(clean, "🔴 alice[31m started\nxxxx…");
+        assert_eq!(clean.chars().count(), 25);
+
+        assert_eq!(sanitize_message("short\tmsg", 0), "shortmsg");
+    }
+}
@@ -1,18 +1,50 @@
 use clap::Parser;
+use std::io::BufRead;
+use std::path::PathBuf;
 
-use crate::config::Config;
+use crate::config::{normalize_tagged_room_list, validate_room_name, Config, QualityMode};
+use crate::error::Result;
+use crate::output::console;
+use crate::output::stats::OutputFormat;
+use crate::stream::UrlSource;
 
-#[derive(Parser, Debug)]
+/// Room argument that means "read room names from stdin"
+const STDIN_ROOM: &str = "-";
+
+#[derive(Parser, Debug, Clone)]
 #[command(
     name = "chaturbate-recorder",
     about = "Record live video streams from Chaturbate",
     version
 )]
 pub struct Args {
-    /// Room(s) to record. Can be specified multiple times.
+    /// Room(s) to record. Can be specified multiple times. Use "-" to read
+    /// room names from stdin.
     #[arg(short, long = "room", value_name = "ROOM")]
     pub rooms: Vec<String>,
 
+    /// Room(s) to record, as positional arguments ("-" reads from stdin)
+    #[arg(value_name = "ROOMS")]
+    pub positional_rooms: Vec<String>,
+
+    /// Read newline-separated room names from stdin (blank and # lines ignored)
+    #[arg(long)]
+    pub rooms_stdin: bool,
+
+    /// Also record the HLS playlist URLs in this file, one `url|label` per
+    /// line; the label names the files like a room name would
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["monitor", "once"])]
+    pub url_file: Option<PathBuf>,
+
+    /// Re-attempt rooms that failed up to N more times once the others are
+    /// done (0 = don't retry)
+    #[arg(long, value_name = "N", default_value_t = 0, conflicts_with_all = ["monitor", "once"])]
+    pub retry_failed: u32,
+
+    /// Seconds to wait before each --retry-failed attempt
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub retry_delay: u64,
+
     /// Output directory for recordings
     #[arg(short, long, value_name = "DIR")]
     pub output: Option<String>,
@@ -29,6 +61,19 @@ pub struct Args {
     #[arg(long, value_name = "FPS")]
     pub fps: Option<u32>,
 
+    /// Accept any --resolution/--fps, e.g. for resolutions not common yet
+    #[arg(long)]
+    pub allow_any: bool,
+
+    /// Only record the exact --resolution/--fps; rooms that don't offer it
+    /// are skipped (quality_mode = "exact")
+    #[arg(long)]
+    pub exact_quality: bool,
+
+    /// Lowest acceptable resolution; see skip_below_min in the config (0 = no floor)
+    #[arg(long, value_name = "HEIGHT")]
+    pub min_resolution: Option<u32>,
+
     /// Cookies for private streams (semicolon-separated)
     #[arg(long, value_name = "COOKIES", env = "CB_COOKIES")]
     pub cookies: Option<String>,
@@ -37,18 +82,56 @@ pub struct Args {
     #[arg(long, value_name = "UA")]
     pub user_agent: Option<String>,
 
+    /// Maximum requests per second across all rooms (0 = unlimited)
+    #[arg(long, value_name = "N")]
+    pub rate_limit: Option<f64>,
+
     /// Maximum recording duration in minutes (0 = unlimited)
     #[arg(long, value_name = "MINUTES")]
     pub max_duration: Option<u32>,
 
+    /// Stop recording entirely after this many minutes, across splits (0 = unlimited)
+    #[arg(long, value_name = "MINUTES")]
+    pub record_for: Option<u32>,
+
     /// Maximum file size in MB (0 = unlimited)
     #[arg(long, value_name = "MB")]
     pub max_filesize: Option<u32>,
 
+    /// Continue an existing recording file of the same name instead of
+    /// overwriting it
+    #[arg(long)]
+    pub append: bool,
+
     /// Check interval in seconds for monitor mode
     #[arg(long, value_name = "SECONDS")]
     pub check_interval: Option<u64>,
 
+    /// Check all rooms once, record those online until they end, then exit.
+    /// Implies --monitor.
+    #[arg(long)]
+    pub once: bool,
+
+    /// Monitor mode: print every room's status after each check cycle
+    #[arg(long)]
+    pub status_table: bool,
+
+    /// Monitor mode: only record rooms whose title or a tag matches this regex
+    #[arg(long, value_name = "REGEX")]
+    pub title_filter: Option<String>,
+
+    /// Monitor mode: stop everything after this many minutes (0 = unlimited)
+    #[arg(long, value_name = "MINUTES")]
+    pub max_session_duration: Option<u64>,
+
+    /// Monitor mode: exit with an error if any recording failed
+    #[arg(long)]
+    pub exit_on_error: bool,
+
+    /// Async worker threads (0 = one per CPU core)
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+
     /// Path to config file
     #[arg(short, long, default_value = "config.toml")]
     pub config: String,
@@ -60,13 +143,38 @@ pub struct Args {
     /// Enable debug logging
     #[arg(long)]
     pub debug: bool,
+
+    /// Print recording results and summaries as text or JSON lines
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Write check, recording and cookie events to stdout as JSON lines;
+    /// console messages go to stderr
+    #[arg(long, conflicts_with = "format")]
+    pub events_ndjson: bool,
+
+    /// Save fetched room pages, decoded dossiers and playlists to this
+    /// directory for bug reports (cookie values are redacted)
+    #[arg(long, value_name = "DIR")]
+    pub debug_dump: Option<PathBuf>,
+
+    /// Print the rooms (and --url-file labels) that would be recorded, one
+    /// per line, and exit. With --debug, each is followed by where it came from.
+    #[arg(long)]
+    pub list_rooms: bool,
+
+    /// Write a JSON report of every room's result and the session totals here
+    /// when a direct recording run ends
+    #[arg(long, value_name = "PATH")]
+    pub stats_file: Option<PathBuf>,
 }
 
 impl Args {
     pub fn merge_into_config(&self, config: &mut Config) {
         // Merge rooms from CLI and config
-        if !self.rooms.is_empty() {
-            config.monitor.rooms = self.rooms.clone();
+        let cli_rooms = self.cli_rooms();
+        if !cli_rooms.is_empty() {
+            config.monitor.rooms = cli_rooms;
         }
 
         // Override output directory
@@ -84,6 +192,18 @@ impl Args {
             config.recording.framerate = fps;
         }
 
+        if self.allow_any {
+            config.recording.allow_any_quality = true;
+        }
+
+        if self.exact_quality {
+            config.recording.quality_mode = QualityMode::Exact;
+        }
+
+        if let Some(min_resolution) = self.min_resolution {
+            config.recording.min_resolution = min_resolution;
+        }
+
         // Override cookies
         if let Some(ref cookies) = self.cookies {
             config.network.cookies = Some(cookies.clone());
@@ -94,27 +214,290 @@ impl Args {
             config.network.user_agent = Some(ua.clone());
         }
 
+        if let Some(rate) = self.rate_limit {
+            config.network.max_requests_per_second = rate;
+        }
+
         // Override max duration
         if let Some(max_duration) = self.max_duration {
             config.recording.max_duration_minutes = max_duration;
         }
 
+        // Override total recording duration
+        if let Some(record_for) = self.record_for {
+            config.recording.total_duration_minutes = record_for;
+        }
+
         // Override max filesize
         if let Some(max_filesize) = self.max_filesize {
             config.recording.max_filesize_mb = max_filesize;
         }
 
+        if self.append {
+            config.recording.append = true;
+        }
+
         // Override check interval
         if let Some(interval) = self.check_interval {
             config.monitor.check_interval_seconds = interval;
         }
+
+        // Single-pass monitor
+        if self.once {
+            config.monitor.once = true;
+        }
+
+        if self.status_table {
+            config.monitor.status_table = true;
+        }
+
+        if let Some(ref filter) = self.title_filter {
+            config.monitor.record_if_title_matches = Some(filter.clone());
+        }
+
+        // Override session limit
+        if let Some(minutes) = self.max_session_duration {
+            config.monitor.max_session_duration_minutes = minutes;
+        }
+
+        // Strict exit status
+        if self.exit_on_error {
+            config.monitor.exit_on_error = true;
+        }
+
+        // Override worker thread count
+        if let Some(threads) = self.threads {
+            config.runtime.worker_threads = threads;
+        }
+    }
+
+    /// Rooms to act on: CLI rooms plus any read from stdin, or the config's
+    /// rooms when none were given. Stdin is only read when asked for. Names
+    /// are lowercased and de-duplicated.
+    pub fn get_rooms(&self, config: &Config) -> Result<Vec<String>> {
+        let rooms = self.get_rooms_with_sources(config)?;
+        Ok(rooms.into_iter().map(|(room, _)| room).collect())
+    }
+
+    /// [`Args::get_rooms`], with where each room came from. A room listed
+    /// more than once keeps the source of its first occurrence.
+    pub fn get_rooms_with_sources(&self, config: &Config) -> Result<Vec<(String, RoomSource)>> {
+        let mut rooms: Vec<_> = self
+            .cli_rooms()
+            .into_iter()
+            .map(|room| (room, RoomSource::Cli))
+            .collect();
+
+        if self.reads_stdin() {
+            let stdin = parse_room_list(std::io::stdin().lock())?;
+            rooms.extend(stdin.into_iter().map(|room| (room, RoomSource::Stdin)));
+        } else if rooms.is_empty() {
+            rooms = config
+                .monitor
+                .rooms
+                .iter()
+                .map(|room| (room.clone(), RoomSource::Config))
+                .collect();
+        }
+
+        Ok(normalize_tagged_room_list(rooms))
+    }
+
+    /// Whether the room list comes from the command line or stdin rather
+    /// than the config file
+    pub fn has_cli_rooms(&self) -> bool {
+        self.reads_stdin() || !self.cli_rooms().is_empty()
+    }
+
+    fn reads_stdin(&self) -> bool {
+        self.rooms_stdin
+            || self
+                .rooms
+                .iter()
+                .chain(&self.positional_rooms)
+                .any(|r| r == STDIN_ROOM)
+    }
+
+    /// Rooms named directly on the command line
+    fn cli_rooms(&self) -> Vec<String> {
+        self.rooms
+            .iter()
+            .chain(&self.positional_rooms)
+            .filter(|r| *r != STDIN_ROOM)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Where a room of the effective room list was named
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomSource {
+    Cli,
+    Stdin,
+    Config,
+}
+
+impl RoomSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            RoomSource::Cli => "cli",
+            RoomSource::Stdin => "stdin",
+            RoomSource::Config => "config",
+        }
+    }
+}
+
+/// Parse newline-separated room names, skipping blank lines and `#` comments
+pub fn parse_room_list<R: BufRead>(reader: R) -> Result<Vec<String>> {
+    let mut rooms = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let room = line.trim();
+        if room.is_empty() || room.starts_with('#') {
+            continue;
+        }
+        rooms.push(room.to_string());
     }
 
-    pub fn get_rooms(&self, config: &Config) -> Vec<String> {
-        if !self.rooms.is_empty() {
-            self.rooms.clone()
-        } else {
-            config.monitor.rooms.clone()
+    Ok(rooms)
+}
+
+/// Parse `url|label` lines, skipping blank lines and `#` comments. A missing
+/// label becomes `stream<line number>`. Lines with a non-HTTP(S) URL, a
+/// label that isn't a valid room name, or a label already used by an earlier
+/// line are skipped with a warning.
+pub fn parse_url_list<R: BufRead>(reader: R) -> Result<Vec<UrlSource>> {
+    let mut sources = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (url, label) = match line.split_once('|') {
+            Some((url, label)) => (url.trim(), label.trim().to_string()),
+            None => (line, format!("stream{}", index + 1)),
+        };
+
+        match url::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            _ => {
+                console::print_warning(&format!(
+                    "Skipping line {}: not an HTTP(S) URL: {}",
+                    index + 1,
+                    url
+                ));
+                continue;
+            }
+        }
+
+        if let Err(e) = validate_room_name(&label) {
+            console::print_warning(&format!("Skipping line {}: bad label: {}", index + 1, e));
+            continue;
+        }
+
+        // Recordings and their results are keyed by label
+        if sources
+            .iter()
+            .any(|source: &UrlSource| source.label == label)
+        {
+            console::print_warning(&format!(
+                "Skipping line {}: label {} is already used",
+                index + 1,
+                label
+            ));
+            continue;
         }
+
+        sources.push(UrlSource {
+            url: url.to_string(),
+            label,
+        });
+    }
+
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url_list() {
+        let input = "\
+# my streams
+https://cdn.example/a/master.m3u8|alpha
+
+http://cdn.example/b/chunklist.m3u8
+ftp://cdn.example/c.m3u8|gamma
+not a url|delta
+https://cdn.example/e.m3u8|bad/label
+https://cdn.example/f.m3u8|alpha
+";
+        let sources = parse_url_list(input.as_bytes()).unwrap();
+        assert_eq!(
+            sources,
+            vec![
+                UrlSource {
+                    url: "https://cdn.example/a/master.m3u8".to_string(),
+                    label: "alpha".to_string(),
+                },
+                UrlSource {
+                    url: "http://cdn.example/b/chunklist.m3u8".to_string(),
+                    label: "stream4".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_room_list() {
+        let input = "room1\n\n# a comment\n  room2  \nroom3\n";
+        let rooms = parse_room_list(input.as_bytes()).unwrap();
+        assert_eq!(rooms, vec!["room1", "room2", "room3"]);
+    }
+
+    #[test]
+    fn test_rooms_with_sources() {
+        let mut config = Config::default();
+        config.monitor.rooms = vec!["alice".to_string(), "bob".to_string()];
+
+        let args = Args::parse_from(["chaturbate-recorder"]);
+        let rooms = args.get_rooms_with_sources(&config).unwrap();
+        assert_eq!(
+            rooms,
+            vec![
+                ("alice".to_string(), RoomSource::Config),
+                ("bob".to_string(), RoomSource::Config)
+            ]
+        );
+
+        // Rooms on the command line replace the config's; duplicates collapse
+        let args = Args::parse_from(["chaturbate-recorder", "-r", "Carol", "carol", "dave"]);
+        let rooms = args.get_rooms_with_sources(&config).unwrap();
+        assert_eq!(
+            rooms,
+            vec![
+                ("carol".to_string(), RoomSource::Cli),
+                ("dave".to_string(), RoomSource::Cli)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stdin_only_when_requested() {
+        let args = Args::parse_from(["chaturbate-recorder", "-r", "room1"]);
+        assert!(!args.reads_stdin());
+
+        let args = Args::parse_from(["chaturbate-recorder", "-"]);
+        assert!(args.reads_stdin());
+        assert!(args.cli_rooms().is_empty());
+
+        let args = Args::parse_from(["chaturbate-recorder", "--rooms-stdin", "-r", "room1"]);
+        assert!(args.reads_stdin());
+        assert_eq!(args.cli_rooms(), vec!["room1"]);
     }
 }
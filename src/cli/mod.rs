@@ -1,3 +1,3 @@
 mod args;
 
-pub use args::Args;
+pub use args::{parse_url_list, Args, RoomSource};
@@ -0,0 +1,124 @@
+/// Expand a leading `~` to the home directory and `$VAR` / `${VAR}` to
+/// environment values. Unset variables and a `~` with no known home are left
+/// as written, so strings without anything to expand come back unchanged.
+pub fn expand_path(input: &str) -> String {
+    expand_path_with(input, env_var)
+}
+
+/// `expand_path` with variables (including `HOME`) looked up in `lookup`
+fn expand_path_with(input: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let expanded = expand_env_vars(input, &lookup);
+    expand_tilde(&expanded, &lookup)
+}
+
+fn expand_tilde(input: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let rest = match input.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => rest,
+        _ => return input.to_string(),
+    };
+
+    match home_dir(lookup) {
+        Some(home) => format!("{}{}", home, rest),
+        None => input.to_string(),
+    }
+}
+
+fn home_dir(lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    lookup("HOME")
+        .or_else(|| lookup("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+}
+
+fn expand_env_vars(input: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        // ${VAR}
+        if let Some(braced) = after.strip_prefix('{') {
+            if let Some(end) = braced.find('}') {
+                let name = &braced[..end];
+                match lookup(name) {
+                    Some(value) => result.push_str(&value),
+                    None => result.push_str(&rest[pos..pos + end + 3]),
+                }
+                rest = &braced[end + 1..];
+                continue;
+            }
+        }
+
+        // $VAR
+        let len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        let name = &after[..len];
+        match lookup(name) {
+            Some(value) => result.push_str(&value),
+            None => {
+                result.push('$');
+                result.push_str(name);
+            }
+        }
+        rest = &after[len..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn env_var(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    std::env::var(name).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `HOME=/home/me` and `DIR=/data`, without touching the process environment
+    fn test_env(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".to_string()),
+            "DIR" => Some("/data".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        assert_eq!(
+            expand_path_with("~/recordings", test_env),
+            "/home/me/recordings"
+        );
+        assert_eq!(expand_path_with("~", test_env), "/home/me");
+        // Only a leading ~ (optionally followed by a separator) is expanded
+        assert_eq!(expand_path_with("~user/x", test_env), "~user/x");
+        assert_eq!(expand_path_with("./a~b", test_env), "./a~b");
+        // No known home leaves it as written
+        assert_eq!(expand_path_with("~/x", |_| None), "~/x");
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        assert_eq!(expand_path_with("$DIR/rec", test_env), "/data/rec");
+        assert_eq!(
+            expand_path_with("{{.Username}}_${DIR}_x", test_env),
+            "{{.Username}}_/data_x"
+        );
+    }
+
+    #[test]
+    fn test_expand_leaves_literals_untouched() {
+        let pattern = "{{.Username}}_{{.Year}}-{{.Month}}-{{.Day}}";
+        assert_eq!(expand_path_with(pattern, test_env), pattern);
+        assert_eq!(expand_path_with("./recordings", test_env), "./recordings");
+        assert_eq!(expand_path_with("$UNSET/x", test_env), "$UNSET/x");
+        assert_eq!(expand_path_with("${UNSET}/x", test_env), "${UNSET}/x");
+        assert_eq!(expand_path_with("cost$", test_env), "cost$");
+    }
+}
@@ -1,7 +1,10 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 
-use crate::error::Result;
+use crate::config::expand::expand_path;
+use crate::error::{Error, Result};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -11,9 +14,122 @@ pub struct Config {
     pub monitor: MonitorConfig,
     #[serde(default)]
     pub network: NetworkConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeConfig {
+    /// Async worker threads (0 = one per CPU core)
+    #[serde(default)]
+    pub worker_threads: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// chrono format string for console and log timestamps
+    #[serde(default = "default_timestamp_format")]
+    pub timestamp_format: String,
+    /// Timezone for log timestamps and filename date/time variables
+    #[serde(default)]
+    pub timezone: Timezone,
+}
+
+/// What a new output file does when its name is already taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnExistingFile {
+    /// Add `.1`, `.2`, ... to the name until it's free
+    #[default]
+    Suffix,
+    /// Replace the existing file
+    Overwrite,
+    /// Don't record; the recording fails
+    Skip,
+    /// Add the current date and time to the name
+    Timestamp,
+}
+
+/// Which IP version outgoing connections use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    /// Whatever the system resolver returns
+    #[default]
+    Auto,
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    /// Whether connecting to `addr` is allowed
+    pub fn allows(self, addr: &std::net::SocketAddr) -> bool {
+        match self {
+            IpVersion::Auto => true,
+            IpVersion::V4 => addr.is_ipv4(),
+            IpVersion::V6 => addr.is_ipv6(),
+        }
+    }
+}
+
+impl std::fmt::Display for IpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IpVersion::Auto => "IP",
+            IpVersion::V4 => "IPv4",
+            IpVersion::V6 => "IPv6",
+        })
+    }
+}
+
+/// What to do when an output file stops growing on disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StallAction {
+    /// Log a warning and keep recording
+    #[default]
+    Warn,
+    /// Fail the recording, like any other write error
+    Stop,
+}
+
+/// How the stream variant is chosen from `resolution` and `framerate`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityMode {
+    /// Exact match, else the best variant at or below the target
+    #[default]
+    AtOrBelow,
+    /// Variant closest to the target, resolution weighing more than framerate
+    Target,
+    /// Exact match only; a room without one isn't recorded
+    Exact,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Timezone {
+    #[default]
+    Local,
+    Utc,
+}
+
+impl Timezone {
+    /// Format `instant` in this timezone
+    pub fn format(self, instant: DateTime<Utc>, format: &str) -> String {
+        match self {
+            Timezone::Local => instant
+                .with_timezone(&chrono::Local)
+                .format(format)
+                .to_string(),
+            Timezone::Utc => instant.format(format).to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RecordingConfig {
     #[serde(default = "default_output_directory")]
     pub output_directory: String,
@@ -21,12 +137,113 @@ pub struct RecordingConfig {
     pub filename_pattern: String,
     #[serde(default)]
     pub max_duration_minutes: u32,
+    /// Stop the recording once it has run this long in total, regardless of
+    /// splits (0 = unlimited)
+    #[serde(default)]
+    pub total_duration_minutes: u32,
+    /// How long to keep verifying a stream whose playlist announced its end
+    /// before giving up on it (0 = stop at the first EXT-X-ENDLIST)
+    #[serde(default)]
+    pub reconnect_grace_seconds: u64,
+    /// Unparseable media playlists in a row before the stream is looked up
+    /// again, and the recording ends if that fails too (0 = after 100)
+    #[serde(default = "default_max_playlist_parse_failures")]
+    pub max_playlist_parse_failures: u32,
+    /// Log a progress line per recording this often, with totals for the
+    /// whole recording across splits (0 = off)
+    #[serde(default)]
+    pub progress_log_interval_seconds: u64,
     #[serde(default)]
     pub max_filesize_mb: u32,
     #[serde(default = "default_resolution")]
     pub resolution: u32,
     #[serde(default = "default_framerate")]
     pub framerate: u32,
+    #[serde(default)]
+    pub quality_mode: QualityMode,
+    /// Lowest acceptable variant height (0 = no floor)
+    #[serde(default)]
+    pub min_resolution: u32,
+    /// Accept any `resolution`/`framerate`, not just plausible ones
+    #[serde(default)]
+    pub allow_any_quality: bool,
+    /// Skip rooms that offer nothing at `min_resolution` instead of recording
+    /// their best variant with a warning
+    #[serde(default)]
+    pub skip_below_min: bool,
+    /// Switch to a lower variant when segments download slower than real time
+    #[serde(default)]
+    pub adaptive_quality: bool,
+    /// Total size ceiling for the output directory in GB (0 = unlimited).
+    /// Oldest finished recordings are deleted to stay under it.
+    #[serde(default)]
+    pub max_total_size_gb: u64,
+    /// Keep segments as individual files and write a VOD `.m3u8` per part
+    /// instead of concatenating into a single `.ts`
+    #[serde(default)]
+    pub write_playlist: bool,
+    /// Keep each segment as its own numbered file in a per-recording folder,
+    /// without splitting into parts
+    #[serde(default)]
+    pub keep_segments: bool,
+    /// Extension for MPEG-TS output files instead of `ts` (e.g. `m2ts`).
+    /// fMP4 recordings are always `.mp4`.
+    #[serde(default)]
+    pub file_extension: Option<String>,
+    /// Continue an existing MPEG-TS file of the same name (or its leftover
+    /// `.part`) instead of starting it over
+    #[serde(default)]
+    pub append: bool,
+    /// What to do when an output name is already taken and not appended to
+    #[serde(default)]
+    pub on_existing_file: OnExistingFile,
+    /// Leave out segments between a cue-out and a cue-in tag (ad breaks)
+    #[serde(default)]
+    pub skip_ads: bool,
+    /// Media playlist tags that start an ad break, for `skip_ads`
+    #[serde(default = "default_ad_cue_out_tags")]
+    pub ad_cue_out_tags: Vec<String>,
+    /// Media playlist tags that end an ad break, for `skip_ads`
+    #[serde(default = "default_ad_cue_in_tags")]
+    pub ad_cue_in_tags: Vec<String>,
+    /// Reject downloaded segments that don't start with the MPEG-TS sync byte
+    /// or aren't a whole number of 188-byte packets
+    #[serde(default = "default_true")]
+    pub verify_ts_sync: bool,
+    /// Start a new file when the stream comes back at a different
+    /// resolution or framerate
+    #[serde(default = "default_true")]
+    pub split_on_resolution_change: bool,
+    /// Resolve filename date/time variables in UTC regardless of `[logging]
+    /// timezone`, so recordings from hosts in different zones sort together
+    #[serde(default)]
+    pub use_utc_timestamps: bool,
+    /// Stop recording when the show turns private (segments and playlist
+    /// return 403) instead of waiting for it to become public again
+    #[serde(default = "default_true")]
+    pub stop_on_private: bool,
+    /// Regex with one capture group that extracts the sequence number from a
+    /// segment URI, for CDNs that don't use the default `_<n>.ts` naming
+    #[serde(default)]
+    pub segment_pattern: Option<String>,
+    /// Write a `<basename>.concat.txt` ffmpeg concat list when a recording
+    /// is split into several files
+    #[serde(default)]
+    pub write_concat_list: bool,
+    /// Buffer this many KB of output before writing to disk (0 = unbuffered)
+    #[serde(default = "default_write_buffer_kb")]
+    pub write_buffer_kb: u32,
+    /// Compare the output file's size on disk with the bytes written to it
+    /// this often, to catch a full disk or a wedged mount (0 = off)
+    #[serde(default)]
+    pub output_growth_check_seconds: u64,
+    /// What to do when the output file hasn't grown on disk since the last check
+    #[serde(default)]
+    pub output_stall_action: StallAction,
+    /// SQLite database that gets a row per completed recording (needs the
+    /// `database` feature)
+    #[serde(default)]
+    pub database: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,19 +252,213 @@ pub struct MonitorConfig {
     pub check_interval_seconds: u64,
     #[serde(default)]
     pub rooms: Vec<String>,
+    /// Per-room settings keyed by room name, e.g. `[monitor.room_overrides.alice]`
+    #[serde(default)]
+    pub room_overrides: HashMap<String, RoomOverrides>,
     /// Webhook URL for notifications (cookie death, recovery). POST with JSON body.
+    /// Shorthand for a single entry in `webhooks` that receives every event.
     #[serde(default)]
     pub webhook_url: Option<String>,
+    /// Webhook endpoints, each optionally limited to certain events
+    #[serde(default)]
+    pub webhooks: Vec<WebhookEndpoint>,
+    /// Shared secret for signing webhook bodies (`X-Signature: sha256=<hex>`)
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Truncate webhook message text to this many characters (0 = unlimited)
+    #[serde(default = "default_webhook_max_length")]
+    pub webhook_max_length: usize,
+    /// Payload shape for webhook endpoints that don't set their own `format`
+    #[serde(default)]
+    pub webhook_format: WebhookFormat,
+    /// Exit with an error if any recording failed, not only when all did
+    #[serde(default)]
+    pub exit_on_error: bool,
+    /// Check every room once, record whatever is online until it ends, then exit
+    #[serde(default)]
+    pub once: bool,
+    /// Stop monitoring (and all recordings) after this many minutes (0 = unlimited)
+    #[serde(default)]
+    pub max_session_duration_minutes: u64,
+    /// Append every room status change to this JSONL file
+    #[serde(default)]
+    pub event_log: Option<String>,
+    /// Send a webhook when a recording starts
+    #[serde(default)]
+    pub notify_on_start: bool,
+    /// Send a webhook when a recording stops
+    #[serde(default)]
+    pub notify_on_stop: bool,
+    /// Send a "still alive" webhook this often, for dead-man's-switch
+    /// monitoring (0 = no heartbeats)
+    #[serde(default)]
+    pub heartbeat_webhook_interval_seconds: u64,
+    /// Percentage of checked rooms failing auth that triggers an early
+    /// cookie-expiry warning (0 = no warning)
+    #[serde(default = "default_cookie_warn_threshold")]
+    pub cookie_warn_threshold_percent: u32,
+    /// Percentage of checked rooms failing auth that counts as cookie death
+    #[serde(default = "default_cookie_dead_threshold")]
+    pub cookie_dead_threshold_percent: u32,
+    /// Rooms that must be checked in a cycle before the cookie heuristics apply
+    #[serde(default = "default_cookie_dead_min_rooms")]
+    pub cookie_dead_min_rooms: u32,
+    /// Maximum number of rooms recorded at once (0 = unlimited)
+    #[serde(default)]
+    pub max_concurrent_recordings: u32,
+    /// When all slots are in use, stop a lower-priority recording to make
+    /// room for a higher-priority room that comes online
+    #[serde(default)]
+    pub preempt_lower_priority: bool,
+    /// Hold off starting recordings until at least this many monitored rooms
+    /// are online at once (0 = record as soon as any room is online)
+    #[serde(default)]
+    pub min_online_to_record: u32,
+    /// On shutdown, how long to wait for recordings to flush before aborting
+    /// them (0 = wait indefinitely)
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout_seconds: u64,
+    /// Leave a room unchecked for this long after its recording ends, so a
+    /// stream still tearing down isn't recorded again (0 = recheck at once)
+    #[serde(default = "default_post_recording_cooldown")]
+    pub post_recording_cooldown_seconds: u64,
+    /// Print a table of every room's status after each check cycle
+    #[serde(default)]
+    pub status_table: bool,
+    /// Only start recording rooms whose title or one of whose tags matches
+    /// this regex
+    #[serde(default)]
+    pub record_if_title_matches: Option<String>,
+}
+
+/// Settings that can differ per room; unset fields use the global value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoomOverrides {
+    /// Base check interval for this room in monitor mode
+    #[serde(default)]
+    pub check_interval_seconds: Option<u64>,
+    /// Higher values win recording slots first (default 0)
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// Cookies sent for this room instead of `[network] cookies`, e.g. a
+    /// session that can see a subscription-only room
+    #[serde(default)]
+    pub cookies: Option<String>,
+}
+
+/// Kinds of notification the monitor can send
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// Cookie death and recovery
+    Cookie,
+    RecordingStart,
+    RecordingStop,
+    /// A recording stopped because the show went private
+    RecordingPrivate,
+    /// Periodic still-alive ping. Sent to endpoints that list it, or to
+    /// every catch-all endpoint when none do.
+    Heartbeat,
+}
+
+/// Payload shape sent to a webhook endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// `{"text", "event", "source", "timestamp"}`
+    #[default]
+    Json,
+    /// Slack incoming-webhook message with Block Kit blocks
+    Slack,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    /// Events delivered to this endpoint (empty = all events)
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// Payload shape for this endpoint (default: `webhook_format`)
+    #[serde(default)]
+    pub format: Option<WebhookFormat>,
+}
+
+impl WebhookEndpoint {
+    pub fn accepts(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NetworkConfig {
     #[serde(default)]
     pub user_agent: Option<String>,
     #[serde(default)]
     pub cookies: Option<String>,
+    /// File holding the cookie string, used when `cookies` isn't set
+    #[serde(default)]
+    pub cookies_file: Option<String>,
+    /// JSON file where cookies set by the server are kept between runs
+    #[serde(default)]
+    pub cookie_jar_file: Option<String>,
+    /// Shell command printing a fresh cookie string, run when Cloudflare
+    /// blocks a check or cookies look dead
+    #[serde(default)]
+    pub cookie_refresh_command: Option<String>,
+    /// Minimum time between runs of `cookie_refresh_command`
+    #[serde(default = "default_cookie_refresh_cooldown")]
+    pub cookie_refresh_cooldown_seconds: u64,
     #[serde(default = "default_domain")]
     pub domain: String,
+    /// Mirrors tried in order when the primary domain is blocked or unreachable
+    #[serde(default)]
+    pub fallback_domains: Vec<String>,
+    /// How long a room's master playlist is reused between checks (0 = always fetch)
+    #[serde(default = "default_master_playlist_cache")]
+    pub master_playlist_cache_seconds: u64,
+    /// Attempts per request when the connection fails (DNS, refused, reset,
+    /// timeout). HTTP error statuses are never retried. 0 or 1 = no retry.
+    #[serde(default = "default_max_request_attempts")]
+    pub max_request_attempts: u32,
+    /// Requests per second across all rooms: page checks, playlists and
+    /// segments (0 = unlimited)
+    #[serde(default)]
+    pub max_requests_per_second: f64,
+    /// Present a Chrome-like TLS fingerprint (needs the `tls-impersonate` feature)
+    #[serde(default)]
+    pub tls_impersonate: bool,
+    /// Local IP address every connection is made from, e.g. a VPN interface's
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    /// Connect over IPv4 or IPv6 only
+    #[serde(default)]
+    pub ip_version: IpVersion,
+    /// Extra headers sent with every request, after (and overriding) the
+    /// built-in browser headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Built-in browser headers not to send, e.g. `X-Requested-With`
+    #[serde(default)]
+    pub disabled_headers: Vec<String>,
+    /// Value sent as `Accept-Encoding` instead of the built-in
+    /// `gzip, deflate, br`; `headers` still takes precedence
+    #[serde(default)]
+    pub accept_encoding: Option<String>,
+    /// Decode gzip/deflate response bodies. Turned off, `Accept-Encoding` is
+    /// still sent, so servers keep compressing: `get` returns the compressed
+    /// bytes (lossily as text) and debug dumps keep an exact copy of each body.
+    #[serde(default = "default_true")]
+    pub decompress: bool,
+    /// Page body substrings that indicate a Cloudflare challenge
+    #[serde(default = "default_cloudflare_markers")]
+    pub cloudflare_markers: Vec<String>,
+    /// Page body substrings that indicate an interactive (CAPTCHA) challenge;
+    /// checked before `cloudflare_markers`
+    #[serde(default = "default_captcha_markers")]
+    pub captcha_markers: Vec<String>,
+    /// Page body substrings that indicate an age verification wall
+    #[serde(default = "default_age_markers")]
+    pub age_markers: Vec<String>,
 }
 
 fn default_output_directory() -> String {
@@ -58,6 +469,17 @@ fn default_filename_pattern() -> String {
     "{{.Username}}_{{.Year}}-{{.Month}}-{{.Day}}_{{.Hour}}-{{.Minute}}-{{.Second}}".to_string()
 }
 
+fn default_ad_cue_out_tags() -> Vec<String> {
+    vec![
+        "EXT-X-CUE-OUT".to_string(),
+        "EXT-X-CUE-OUT-CONT".to_string(),
+    ]
+}
+
+fn default_ad_cue_in_tags() -> Vec<String> {
+    vec!["EXT-X-CUE-IN".to_string()]
+}
+
 fn default_resolution() -> u32 {
     1080
 }
@@ -66,20 +488,95 @@ fn default_framerate() -> u32 {
     30
 }
 
+fn default_true() -> bool {
+    true
+}
+
 fn default_check_interval() -> u64 {
     60
 }
 
+fn default_cookie_warn_threshold() -> u32 {
+    25
+}
+
+fn default_cookie_dead_threshold() -> u32 {
+    50
+}
+
+fn default_cookie_dead_min_rooms() -> u32 {
+    1
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30
+}
+
+fn default_post_recording_cooldown() -> u64 {
+    20
+}
+
+fn default_timestamp_format() -> String {
+    "%Y-%m-%dT%H:%M:%S%:z".to_string()
+}
+
 fn default_domain() -> String {
     "https://chaturbate.com/".to_string()
 }
 
+fn default_master_playlist_cache() -> u64 {
+    300
+}
+
+fn default_max_playlist_parse_failures() -> u32 {
+    5
+}
+
+fn default_write_buffer_kb() -> u32 {
+    256
+}
+
+fn default_webhook_max_length() -> usize {
+    2000
+}
+
+fn default_cookie_refresh_cooldown() -> u64 {
+    600
+}
+
+fn default_max_request_attempts() -> u32 {
+    3
+}
+
+fn default_cloudflare_markers() -> Vec<String> {
+    vec![
+        "<title>Just a moment...</title>".to_string(),
+        "cf-challenge".to_string(),
+    ]
+}
+
+fn default_captcha_markers() -> Vec<String> {
+    vec![
+        "cf-turnstile".to_string(),
+        "challenges.cloudflare.com/turnstile".to_string(),
+        "cType: 'managed'".to_string(),
+        "cType: 'interactive'".to_string(),
+    ]
+}
+
+fn default_age_markers() -> Vec<String> {
+    vec!["Verify your age".to_string()]
+}
+
+#[allow(clippy::derivable_impls)]
 impl Default for Config {
     fn default() -> Self {
         Self {
             recording: RecordingConfig::default(),
             monitor: MonitorConfig::default(),
             network: NetworkConfig::default(),
+            logging: LoggingConfig::default(),
+            runtime: RuntimeConfig::default(),
         }
     }
 }
@@ -90,9 +587,37 @@ impl Default for RecordingConfig {
             output_directory: default_output_directory(),
             filename_pattern: default_filename_pattern(),
             max_duration_minutes: 0,
+            total_duration_minutes: 0,
+            reconnect_grace_seconds: 0,
+            max_playlist_parse_failures: default_max_playlist_parse_failures(),
+            progress_log_interval_seconds: 0,
             max_filesize_mb: 0,
             resolution: default_resolution(),
             framerate: default_framerate(),
+            quality_mode: QualityMode::default(),
+            min_resolution: 0,
+            allow_any_quality: false,
+            skip_below_min: false,
+            adaptive_quality: false,
+            max_total_size_gb: 0,
+            write_playlist: false,
+            keep_segments: false,
+            file_extension: None,
+            append: false,
+            on_existing_file: OnExistingFile::default(),
+            skip_ads: false,
+            ad_cue_out_tags: default_ad_cue_out_tags(),
+            ad_cue_in_tags: default_ad_cue_in_tags(),
+            verify_ts_sync: true,
+            split_on_resolution_change: true,
+            use_utc_timestamps: false,
+            stop_on_private: true,
+            segment_pattern: None,
+            write_concat_list: false,
+            write_buffer_kb: default_write_buffer_kb(),
+            output_growth_check_seconds: 0,
+            output_stall_action: StallAction::default(),
+            database: None,
         }
     }
 }
@@ -102,7 +627,38 @@ impl Default for MonitorConfig {
         Self {
             check_interval_seconds: default_check_interval(),
             rooms: Vec::new(),
+            room_overrides: HashMap::new(),
             webhook_url: None,
+            webhooks: Vec::new(),
+            webhook_secret: None,
+            webhook_max_length: default_webhook_max_length(),
+            webhook_format: WebhookFormat::default(),
+            exit_on_error: false,
+            once: false,
+            max_session_duration_minutes: 0,
+            event_log: None,
+            notify_on_start: false,
+            notify_on_stop: false,
+            heartbeat_webhook_interval_seconds: 0,
+            cookie_warn_threshold_percent: default_cookie_warn_threshold(),
+            cookie_dead_threshold_percent: default_cookie_dead_threshold(),
+            cookie_dead_min_rooms: default_cookie_dead_min_rooms(),
+            max_concurrent_recordings: 0,
+            preempt_lower_priority: false,
+            min_online_to_record: 0,
+            shutdown_timeout_seconds: default_shutdown_timeout(),
+            post_recording_cooldown_seconds: default_post_recording_cooldown(),
+            status_table: false,
+            record_if_title_matches: None,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            timestamp_format: default_timestamp_format(),
+            timezone: Timezone::default(),
         }
     }
 }
@@ -112,7 +668,25 @@ impl Default for NetworkConfig {
         Self {
             user_agent: None,
             cookies: None,
+            cookies_file: None,
+            cookie_jar_file: None,
+            cookie_refresh_command: None,
+            cookie_refresh_cooldown_seconds: default_cookie_refresh_cooldown(),
             domain: default_domain(),
+            fallback_domains: Vec::new(),
+            master_playlist_cache_seconds: default_master_playlist_cache(),
+            max_request_attempts: default_max_request_attempts(),
+            max_requests_per_second: 0.0,
+            tls_impersonate: false,
+            bind_address: None,
+            ip_version: IpVersion::default(),
+            headers: HashMap::new(),
+            disabled_headers: Vec::new(),
+            accept_encoding: None,
+            decompress: true,
+            cloudflare_markers: default_cloudflare_markers(),
+            captcha_markers: default_captcha_markers(),
+            age_markers: default_age_markers(),
         }
     }
 }
@@ -125,22 +699,84 @@ impl Config {
         }
 
         let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let mut config: Config = toml::from_str(&content)?;
+        config.expand_paths()?;
         Ok(config)
     }
 
+    /// Expand `~` and environment variables in path-like settings, and load
+    /// cookies from `cookies_file` if no inline cookies are configured
+    fn expand_paths(&mut self) -> Result<()> {
+        self.recording.output_directory = expand_path(&self.recording.output_directory);
+        self.recording.filename_pattern = expand_path(&self.recording.filename_pattern);
+        self.monitor.event_log = self.monitor.event_log.as_deref().map(expand_path);
+        self.recording.database = self.recording.database.as_deref().map(expand_path);
+        self.network.cookie_jar_file = self.network.cookie_jar_file.as_deref().map(expand_path);
+
+        if let Some(ref file) = self.network.cookies_file {
+            let file = expand_path(file);
+            if self.network.cookies.is_none() {
+                let cookies = std::fs::read_to_string(&file).map_err(|e| {
+                    Error::Config(format!("Failed to read cookies_file {}: {}", file, e))
+                })?;
+                self.network.cookies = Some(cookies.trim().to_string());
+            }
+            self.network.cookies_file = Some(file);
+        }
+
+        Ok(())
+    }
+
     pub fn load_or_default() -> Self {
         Self::load("config.toml").unwrap_or_default()
     }
 }
 
+impl MonitorConfig {
+    /// Overrides for `room`, matched case-insensitively
+    pub fn room_overrides(&self, room: &str) -> Option<&RoomOverrides> {
+        self.room_overrides
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(room))
+            .map(|(_, overrides)| overrides)
+    }
+
+    /// All configured webhook endpoints, with the legacy `webhook_url` as a
+    /// catch-all first entry
+    pub fn webhook_endpoints(&self) -> Vec<WebhookEndpoint> {
+        let mut endpoints = Vec::new();
+
+        if let Some(url) = self.webhook_url.as_ref().filter(|u| !u.is_empty()) {
+            endpoints.push(WebhookEndpoint {
+                url: url.clone(),
+                events: Vec::new(),
+                format: None,
+            });
+        }
+
+        endpoints.extend(self.webhooks.iter().cloned());
+        endpoints
+    }
+}
+
 impl NetworkConfig {
     pub fn domain_with_trailing_slash(&self) -> String {
-        if self.domain.ends_with('/') {
-            self.domain.clone()
-        } else {
-            format!("{}/", self.domain)
-        }
+        with_trailing_slash(&self.domain)
+    }
+
+    pub fn fallback_domains_with_trailing_slash(&self) -> Vec<String> {
+        self.fallback_domains
+            .iter()
+            .map(|d| with_trailing_slash(d))
+            .collect()
+    }
+}
+
+fn with_trailing_slash(domain: &str) -> String {
+    if domain.ends_with('/') {
+        domain.to_string()
+    } else {
+        format!("{}/", domain)
     }
 }
 
@@ -148,4 +784,128 @@ impl RecordingConfig {
     pub fn poll_interval_ms(&self) -> u64 {
         1000 // Fixed 1 second polling interval
     }
+
+    /// Extension of MPEG-TS output files: `file_extension` without a leading
+    /// dot, or `ts`
+    pub fn ts_extension(&self) -> &str {
+        self.file_extension
+            .as_deref()
+            .map(|ext| ext.trim_start_matches('.'))
+            .filter(|ext| !ext.is_empty())
+            .unwrap_or("ts")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_webhook_url_receives_all_events() {
+        let config: Config = toml::from_str(
+            r#"
+            [monitor]
+            webhook_url = "https://example.com/hook"
+            "#,
+        )
+        .unwrap();
+
+        let endpoints = config.monitor.webhook_endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert!(endpoints[0].accepts(WebhookEvent::Cookie));
+        assert!(endpoints[0].accepts(WebhookEvent::RecordingStart));
+    }
+
+    #[test]
+    fn test_missing_cookies_file_is_an_error() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [network]
+            cookies_file = "/nonexistent/cbr-cookies.txt"
+            "#,
+        )
+        .unwrap();
+
+        let err = config.expand_paths().unwrap_err();
+        assert!(
+            matches!(err, Error::Config(ref m) if m.contains("cookies_file")),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_webhook_event_routing() {
+        let config: Config = toml::from_str(
+            r#"
+            [[monitor.webhooks]]
+            url = "https://ops.example.com/hook"
+            events = ["cookie"]
+
+            [[monitor.webhooks]]
+            url = "https://rec.example.com/hook"
+            events = ["recording_start", "recording_stop"]
+            "#,
+        )
+        .unwrap();
+
+        let endpoints = config.monitor.webhook_endpoints();
+        assert_eq!(endpoints.len(), 2);
+        assert!(endpoints[0].accepts(WebhookEvent::Cookie));
+        assert!(!endpoints[0].accepts(WebhookEvent::RecordingStart));
+        assert!(!endpoints[1].accepts(WebhookEvent::Cookie));
+        assert!(endpoints[1].accepts(WebhookEvent::RecordingStart));
+    }
+
+    #[test]
+    fn test_room_overrides_lookup() {
+        let config: Config = toml::from_str(
+            r#"
+            [monitor]
+            check_interval_seconds = 60
+
+            [monitor.room_overrides.Alice]
+            check_interval_seconds = 15
+            "#,
+        )
+        .unwrap();
+
+        let overrides = config.monitor.room_overrides("alice").unwrap();
+        assert_eq!(overrides.check_interval_seconds, Some(15));
+        assert!(config.monitor.room_overrides("bob").is_none());
+    }
+
+    #[test]
+    fn test_timezone_format() {
+        let instant = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let format = default_timestamp_format();
+
+        assert_eq!(
+            Timezone::Utc.format(instant, &format),
+            "2024-01-02T03:04:05+00:00"
+        );
+        // Local time carries the host's offset, so it reads unambiguously
+        assert_eq!(
+            Timezone::Local.format(instant, &format),
+            instant
+                .with_timezone(&chrono::Local)
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+        );
+    }
+
+    #[test]
+    fn test_logging_config_parse() {
+        let config: Config = toml::from_str(
+            r#"
+            [logging]
+            timezone = "utc"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.logging.timezone, Timezone::Utc);
+        assert_eq!(config.logging.timestamp_format, default_timestamp_format());
+    }
 }
@@ -1,5 +1,14 @@
+mod expand;
 mod loader;
 mod validation;
 
-pub use loader::{Config, MonitorConfig, NetworkConfig, RecordingConfig};
-pub use validation::validate_room_name;
+pub use expand::expand_path;
+pub use loader::{
+    Config, IpVersion, LoggingConfig, MonitorConfig, NetworkConfig, OnExistingFile, QualityMode,
+    RecordingConfig, RoomOverrides, RuntimeConfig, StallAction, Timezone, WebhookEndpoint,
+    WebhookEvent, WebhookFormat,
+};
+pub use validation::{
+    compile_segment_pattern, compile_title_filter, normalize_room_list, normalize_tagged_room_list,
+    validate_file_extension, validate_quality, validate_room_name,
+};
@@ -28,10 +28,153 @@ pub fn validate_room_name(room: &str) -> Result<()> {
     Ok(())
 }
 
+/// Framerates streams are actually offered at
+const ALLOWED_FRAMERATES: [u32; 6] = [24, 25, 30, 48, 50, 60];
+
+/// Plausible video heights, from 144p up to 8K
+const RESOLUTION_RANGE: std::ops::RangeInclusive<u32> = 144..=4320;
+
+/// Reject target resolutions and framerates no stream will ever match, which
+/// are almost always typos (`--fps 600`). `allow_any` skips the check.
+pub fn validate_quality(resolution: u32, framerate: u32, allow_any: bool) -> Result<()> {
+    if allow_any {
+        return Ok(());
+    }
+
+    if !RESOLUTION_RANGE.contains(&resolution) {
+        return Err(Error::Config(format!(
+            "Resolution {} is out of range ({}-{}); use --allow-any to record it anyway",
+            resolution,
+            RESOLUTION_RANGE.start(),
+            RESOLUTION_RANGE.end()
+        )));
+    }
+
+    if !ALLOWED_FRAMERATES.contains(&framerate) {
+        return Err(Error::Config(format!(
+            "Framerate {} is not one of {:?}; use --allow-any to record it anyway",
+            framerate, ALLOWED_FRAMERATES
+        )));
+    }
+
+    Ok(())
+}
+
+/// A `file_extension` must be a short alphanumeric suffix (a leading dot is
+/// allowed), so it can't add path components or hide the file
+pub fn validate_file_extension(extension: &str) -> Result<()> {
+    let bare = extension.trim_start_matches('.');
+    if bare.is_empty() || bare.len() > 10 || !bare.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(Error::Config(format!(
+            "Invalid file_extension '{}': use letters and digits only, e.g. \"m2ts\"",
+            extension
+        )));
+    }
+    Ok(())
+}
+
+/// Compile a user-supplied `segment_pattern`, which must have a capture
+/// group for the segment sequence number
+pub fn compile_segment_pattern(pattern: &str) -> Result<Regex> {
+    let re = Regex::new(pattern)
+        .map_err(|e| Error::Config(format!("Invalid segment_pattern '{}': {}", pattern, e)))?;
+
+    // Group 0 is the whole match
+    if re.captures_len() < 2 {
+        return Err(Error::Config(format!(
+            "segment_pattern '{}' needs a capture group for the sequence number, e.g. _(\\d+)\\.ts",
+            pattern
+        )));
+    }
+
+    Ok(re)
+}
+
+/// Compile `record_if_title_matches`
+pub fn compile_title_filter(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| {
+        Error::Config(format!(
+            "Invalid record_if_title_matches '{}': {}",
+            pattern, e
+        ))
+    })
+}
+
+/// Trim and lowercase room names (Chaturbate names are case-insensitive) and
+/// drop duplicates, keeping the first occurrence's position
+pub fn normalize_room_list(rooms: Vec<String>) -> Vec<String> {
+    let tagged = rooms.into_iter().map(|room| (room, ())).collect();
+    normalize_tagged_room_list(tagged)
+        .into_iter()
+        .map(|(room, ())| room)
+        .collect()
+}
+
+/// [`normalize_room_list`] for rooms that carry a tag, such as where they
+/// were named; a duplicate keeps the tag of its first occurrence
+pub fn normalize_tagged_room_list<T>(rooms: Vec<(String, T)>) -> Vec<(String, T)> {
+    let total = rooms.len();
+    let mut seen = std::collections::HashSet::new();
+    let mut normalized = Vec::with_capacity(total);
+
+    for (room, tag) in rooms {
+        let room = room.trim().to_lowercase();
+        if seen.insert(room.clone()) {
+            normalized.push((room, tag));
+        }
+    }
+
+    if normalized.len() < total {
+        tracing::info!(
+            "Collapsed {} duplicate room(s) from the room list",
+            total - normalized.len()
+        );
+    }
+
+    normalized
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validate_quality() {
+        assert!(validate_quality(1080, 30, false).is_ok());
+        assert!(validate_quality(720, 60, false).is_ok());
+        assert!(validate_quality(240, 25, false).is_ok());
+
+        assert!(matches!(
+            validate_quality(10800, 30, false),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            validate_quality(0, 30, false),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            validate_quality(1080, 600, false),
+            Err(Error::Config(_))
+        ));
+
+        assert!(validate_quality(10800, 600, true).is_ok());
+    }
+
+    #[test]
+    fn test_validate_file_extension() {
+        assert!(validate_file_extension("m2ts").is_ok());
+        assert!(validate_file_extension(".mkv").is_ok());
+        assert!(validate_file_extension("").is_err());
+        assert!(validate_file_extension("../ts").is_err());
+    }
+
+    #[test]
+    fn test_compile_segment_pattern() {
+        assert!(compile_segment_pattern(r"seg(\d+)\.m4s").is_ok());
+        assert!(compile_segment_pattern(r"seg\d+\.ts").is_err());
+        assert!(compile_segment_pattern(r"seg(\d+").is_err());
+    }
+
     #[test]
     fn test_valid_room_names() {
         assert!(validate_room_name("testroom").is_ok());
@@ -47,4 +190,29 @@ mod tests {
         assert!(validate_room_name("test room").is_err());
         assert!(validate_room_name("test.room").is_err());
     }
+
+    #[test]
+    fn test_normalize_room_list() {
+        let rooms = vec![
+            "Alice".to_string(),
+            "bob".to_string(),
+            " alice ".to_string(),
+            "BOB".to_string(),
+            "carol".to_string(),
+        ];
+        assert_eq!(normalize_room_list(rooms), vec!["alice", "bob", "carol"]);
+    }
+
+    #[test]
+    fn test_normalize_tagged_room_list_keeps_first_tag() {
+        let rooms = vec![
+            ("Alice".to_string(), 1),
+            ("bob".to_string(), 2),
+            (" ALICE".to_string(), 3),
+        ];
+        assert_eq!(
+            normalize_tagged_room_list(rooms),
+            vec![("alice".to_string(), 1), ("bob".to_string(), 2)]
+        );
+    }
 }
@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+use crate::error::Result;
+use crate::stream::{redact_url, RecordingStats, StreamInfo};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS recordings (
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    room             TEXT    NOT NULL,
+    started_at       TEXT    NOT NULL,
+    ended_at         TEXT    NOT NULL,
+    files            TEXT    NOT NULL,
+    resolution       INTEGER NOT NULL,
+    framerate        INTEGER NOT NULL,
+    segments         INTEGER NOT NULL,
+    bytes            INTEGER NOT NULL,
+    duration_seconds REAL    NOT NULL,
+    title            TEXT,
+    tags             TEXT    NOT NULL DEFAULT '[]',
+    playlist_url     TEXT
+);
+CREATE INDEX IF NOT EXISTS recordings_room ON recordings (room, started_at);
+";
+
+/// Columns added after the first schema, created on databases that predate them
+const ADDED_COLUMNS: [(&str, &str); 3] = [
+    ("title", "TEXT"),
+    ("tags", "TEXT NOT NULL DEFAULT '[]'"),
+    ("playlist_url", "TEXT"),
+];
+
+/// Open the database at `path`, creating the file and schema if needed
+pub fn open(path: &Path) -> Result<Connection> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    add_missing_columns(&conn)?;
+    Ok(conn)
+}
+
+fn add_missing_columns(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('recordings')")?;
+    let existing = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (name, definition) in ADDED_COLUMNS {
+        if !existing.iter().any(|c| c == name) {
+            conn.execute_batch(&format!(
+                "ALTER TABLE recordings ADD COLUMN {} {}",
+                name, definition
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Insert one completed recording. Timestamps are stored as RFC 3339 UTC,
+/// `files` as a JSON array of paths in recording order, `tags` as a JSON
+/// array of strings, and `playlist_url` redacted like the logs, since the
+/// database may be shared or backed up where tokens shouldn't end up.
+pub fn insert_recording(
+    conn: &Connection,
+    stream_info: &StreamInfo,
+    stats: &RecordingStats,
+    started_at: DateTime<Utc>,
+    ended_at: DateTime<Utc>,
+) -> Result<()> {
+    let files: Vec<String> = stats
+        .files
+        .iter()
+        .map(|f| f.to_string_lossy().into_owned())
+        .collect();
+
+    conn.execute(
+        "INSERT INTO recordings
+            (room, started_at, ended_at, files, resolution, framerate, segments, bytes, duration_seconds, title, tags,
+             playlist_url)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            stream_info.room,
+            started_at.to_rfc3339(),
+            ended_at.to_rfc3339(),
+            serde_json::to_string(&files)?,
+            stream_info.resolution,
+            stream_info.framerate,
+            stats.segments_downloaded as i64,
+            stats.bytes_written as i64,
+            stats.duration_seconds,
+            stream_info.title,
+            serde_json::to_string(&stream_info.tags)?,
+            redact_url(&stats.playlist_url),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Record a finished recording in the database at `path`. Failures are
+/// logged and never affect the recording itself.
+pub async fn log_recording(
+    path: &str,
+    stream_info: &StreamInfo,
+    stats: &RecordingStats,
+    started_at: DateTime<Utc>,
+    ended_at: DateTime<Utc>,
+) {
+    let path = path.to_string();
+    let stream_info = stream_info.clone();
+    let stats = stats.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = open(Path::new(&path))?;
+        insert_recording(&conn, &stream_info, &stats, started_at, ended_at)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::warn!("Failed to save recording to database: {}", e),
+        Err(e) => tracing::warn!("Database task error: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_insert_recording() {
+        let path = std::env::temp_dir().join(format!("cbr-db-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let conn = open(&path).unwrap();
+        let info = StreamInfo {
+            hls_source: "https://example.com/playlist.m3u8".to_string(),
+            room: "alice".to_string(),
+            resolution: 1080,
+            framerate: 30,
+            bandwidth: 5_000_000,
+            title: Some("Friday show".to_string()),
+            tags: vec!["music".to_string()],
+            source: Default::default(),
+        };
+        let stats = RecordingStats {
+            segments_downloaded: 10,
+            bytes_written: 2048,
+            duration_seconds: 20.0,
+            files: vec![PathBuf::from("a.ts"), PathBuf::from("a_1.ts")],
+            playlist_url: "https://example.com/playlist.m3u8?token=secret".to_string(),
+            ..Default::default()
+        };
+        let now = Utc::now();
+        insert_recording(&conn, &info, &stats, now, now).unwrap();
+
+        // Re-opening keeps existing rows
+        drop(conn);
+        let conn = open(&path).unwrap();
+        let (room, files, bytes, title, tags): (String, String, i64, Option<String>, String) = conn
+            .query_row(
+                "SELECT room, files, bytes, title, tags FROM recordings",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .unwrap();
+
+        assert_eq!(room, "alice");
+        assert_eq!(files, r#"["a.ts","a_1.ts"]"#);
+        assert_eq!(bytes, 2048);
+        assert_eq!(title.as_deref(), Some("Friday show"));
+        assert_eq!(tags, r#"["music"]"#);
+
+        let playlist_url: Option<String> = conn
+            .query_row("SELECT playlist_url FROM recordings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(
+            playlist_url.as_deref(),
+            Some("https://example.com/playlist.m3u8?token=<redacted>")
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -8,12 +9,24 @@ pub enum Error {
     #[error("Broadcaster offline: {0}")]
     BroadcasterOffline(String),
 
+    #[error("{0} only offers up to {1}p, below the minimum of {2}p")]
+    BelowMinResolution(String, u32, u32),
+
+    #[error("{0} doesn't offer {1} (available: {2})")]
+    QualityUnavailable(String, String, String),
+
     #[error("Stream URL not found for room: {0}")]
     StreamNotFound(String),
 
     #[error("Cloudflare blocked request - cookies expired or User-Agent mismatch. Refresh cf_clearance cookie.")]
     CloudflareBlocked,
 
+    #[error("Cloudflare CAPTCHA required - solve it in a browser with the same IP and User-Agent, then update the cf_clearance cookie.")]
+    CaptchaRequired,
+
+    #[error("Cookie refresh command failed: {0}")]
+    CookieRefresh(String),
+
     #[error("Age verification required")]
     AgeVerification,
 
@@ -58,6 +71,28 @@ pub enum Error {
 
     #[error("Segment download failed after retries: {0}")]
     SegmentDownloadFailed(String),
+
+    #[error("Invalid segment data: {0}")]
+    InvalidSegment(String),
+
+    #[error("All {0} room(s) were private or blocked by Cloudflare - refresh your cookies (sessionid, cf_clearance)")]
+    AuthFailed(u32),
+
+    #[error("{0} of {1} recordings failed")]
+    RecordingsFailed(u32, u32),
+
+    #[error("{} already exists (on_existing_file = \"skip\")", .0.display())]
+    OutputExists(PathBuf),
+
+    #[error("Output file stopped growing on disk: {0}")]
+    OutputStalled(String),
+
+    #[error("Recording task failed: {0}")]
+    TaskFailed(String),
+
+    #[cfg(feature = "database")]
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -75,11 +110,46 @@ impl Error {
             Error::Config(_) | Error::InvalidRoomName(_) | Error::NoRoomsSpecified => {
                 EXIT_CONFIG_ERROR
             }
-            Error::Network(_) | Error::CloudflareBlocked | Error::AgeVerification | Error::ServerError(_, _) => {
-                EXIT_NETWORK_ERROR
-            }
+            Error::Network(_)
+            | Error::CloudflareBlocked
+            | Error::CaptchaRequired
+            | Error::AgeVerification
+            | Error::AuthFailed(_)
+            | Error::ServerError(_, _) => EXIT_NETWORK_ERROR,
             Error::Interrupted => EXIT_INTERRUPTED,
             _ => EXIT_RECORDING_ERROR,
         }
     }
+
+    /// Short machine-readable name for the kind of failure
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::RoomNotFound(_) => "room_not_found",
+            Error::BroadcasterOffline(_) => "offline",
+            Error::BelowMinResolution(..) => "below_min_resolution",
+            Error::QualityUnavailable(..) => "quality_unavailable",
+            Error::StreamNotFound(_) => "stream_not_found",
+            Error::CloudflareBlocked => "cloudflare",
+            Error::CaptchaRequired => "captcha",
+            Error::AgeVerification => "age_verification",
+            Error::PrivateStream => "private",
+            Error::ServerError(..) => "server_error",
+            Error::Interrupted => "interrupted",
+            Error::Network(_) => "network",
+            Error::Io(_) | Error::OutputStalled(_) => "io",
+            Error::OutputExists(_) => "output_exists",
+            Error::M3u8(_) => "playlist",
+            Error::SegmentDownloadFailed(_) | Error::InvalidSegment(_) => "segment",
+            Error::TaskFailed(_) => "task",
+            _ => "error",
+        }
+    }
+
+    /// Access was refused in a way fresh cookies may fix
+    pub fn is_auth_failure(&self) -> bool {
+        matches!(
+            self,
+            Error::PrivateStream | Error::CloudflareBlocked | Error::CaptchaRequired
+        )
+    }
 }
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Directory that discovery traffic is saved to, set by `--debug-dump`
+static DUMP_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Dumps kept per room and kind; older ones are deleted as new ones arrive
+const KEEP_PER_KIND: usize = 20;
+
+/// Save room pages, dossiers and playlists under `dir` from now on
+pub fn enable(dir: PathBuf) {
+    let _ = DUMP_DIR.set(dir);
+}
+
+pub fn enabled() -> bool {
+    DUMP_DIR.get().is_some()
+}
+
+/// Write `contents` to `<dir>/<timestamp>_<room>_<kind>.<extension>` when
+/// dumping is enabled, keeping the newest [`KEEP_PER_KIND`] of each room and
+/// kind. Failures are only logged; they never affect a check or recording.
+pub async fn save(room: &str, kind: &str, extension: &str, contents: &str) {
+    save_bytes(room, kind, extension, contents.as_bytes()).await;
+}
+
+/// `save` for data that isn't text, e.g. a still-compressed response body
+pub async fn save_bytes(room: &str, kind: &str, extension: &str, contents: &[u8]) {
+    let Some(dir) = DUMP_DIR.get() else {
+        return;
+    };
+
+    let path = dump_path(dir, room, kind, extension);
+    let result = match tokio::fs::create_dir_all(dir).await {
+        Ok(()) => tokio::fs::write(&path, contents).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => tracing::debug!("Saved {}", path.display()),
+        Err(e) => {
+            tracing::warn!("Failed to write debug dump {}: {}", path.display(), e);
+            return;
+        }
+    }
+
+    if let Err(e) = prune(dir, &format!("{}_{}.{}", room, kind, extension)).await {
+        tracing::warn!("Failed to prune debug dumps in {}: {}", dir.display(), e);
+    }
+}
+
+fn dump_path(dir: &Path, room: &str, kind: &str, extension: &str) -> PathBuf {
+    let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ");
+    dir.join(format!("{}_{}_{}.{}", timestamp, room, kind, extension))
+}
+
+/// Delete all but the newest [`KEEP_PER_KIND`] dumps named
+/// `<timestamp>_<suffix>`. Timestamps sort by name.
+async fn prune(dir: &Path, suffix: &str) -> std::io::Result<()> {
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // The timestamp has no `_`, so this can't match another room's dumps
+        if name.split_once('_').is_some_and(|(_, rest)| rest == suffix) {
+            names.push(name);
+        }
+    }
+
+    names.sort();
+    let excess = names.len().saturating_sub(KEEP_PER_KIND);
+    for name in &names[..excess] {
+        tokio::fs::remove_file(dir.join(name)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_prune_keeps_newest_of_a_kind() {
+        let dir = std::env::temp_dir().join(format!("cbr-dump-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for i in 0..KEEP_PER_KIND + 2 {
+            std::fs::write(dir.join(format!("2024010{:02}_alice_master.m3u8", i)), "").unwrap();
+        }
+        std::fs::write(dir.join("20240101_x_alice_master.m3u8"), "").unwrap();
+        std::fs::write(dir.join("20240101_alice_media.m3u8"), "").unwrap();
+
+        prune(&dir, "alice_master.m3u8").await.unwrap();
+
+        assert!(!dir.join("202401000_alice_master.m3u8").exists());
+        assert!(!dir.join("202401001_alice_master.m3u8").exists());
+        assert!(dir.join("202401002_alice_master.m3u8").exists());
+        assert!(dir.join("20240101_x_alice_master.m3u8").exists());
+        assert!(dir.join("20240101_alice_media.m3u8").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
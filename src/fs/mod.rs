@@ -1,3 +1,9 @@
+pub mod dump;
 mod paths;
+mod quota;
 
-pub use paths::generate_output_path;
+pub use paths::{
+    check_output_dir, check_output_dir_within, generate_output_path, output_path_at,
+    resolve_existing,
+};
+pub use quota::{enforce_quota, mark_active, run_quota_task, ActiveFileGuard};
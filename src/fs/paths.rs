@@ -1,25 +1,48 @@
-use chrono::Local;
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::error::Result;
+use crate::config::{OnExistingFile, Timezone};
+use crate::error::{Error, Result};
 
 pub fn generate_output_path(
     output_dir: &str,
     pattern: &str,
     room: &str,
     sequence: u32,
+    timezone: Timezone,
+    extension: &str,
 ) -> Result<PathBuf> {
-    let now = Local::now();
+    output_path_at(
+        output_dir,
+        pattern,
+        room,
+        sequence,
+        timezone,
+        extension,
+        Utc::now(),
+    )
+}
 
+/// `generate_output_path` for a given instant
+pub fn output_path_at(
+    output_dir: &str,
+    pattern: &str,
+    room: &str,
+    sequence: u32,
+    timezone: Timezone,
+    extension: &str,
+    now: DateTime<Utc>,
+) -> Result<PathBuf> {
     // Replace template variables
     let filename = pattern
         .replace("{{.Username}}", room)
-        .replace("{{.Year}}", &now.format("%Y").to_string())
-        .replace("{{.Month}}", &now.format("%m").to_string())
-        .replace("{{.Day}}", &now.format("%d").to_string())
-        .replace("{{.Hour}}", &now.format("%H").to_string())
-        .replace("{{.Minute}}", &now.format("%M").to_string())
-        .replace("{{.Second}}", &now.format("%S").to_string());
+        .replace("{{.Year}}", &timezone.format(now, "%Y"))
+        .replace("{{.Month}}", &timezone.format(now, "%m"))
+        .replace("{{.Day}}", &timezone.format(now, "%d"))
+        .replace("{{.Hour}}", &timezone.format(now, "%H"))
+        .replace("{{.Minute}}", &timezone.format(now, "%M"))
+        .replace("{{.Second}}", &timezone.format(now, "%S"));
 
     // Add sequence suffix if > 0
     let filename = if sequence > 0 {
@@ -28,18 +51,145 @@ pub fn generate_output_path(
         filename
     };
 
-    // Add .ts extension
-    let filename = format!("{}.ts", filename);
+    let filename = format!("{}.{}", filename, extension.trim_start_matches('.'));
 
     let path = PathBuf::from(output_dir).join(filename);
 
     Ok(path)
 }
 
+/// Where to write a file meant for `path`, following `strategy` when
+/// `taken` reports the name in use. `stamp` is added for `Timestamp`.
+pub fn resolve_existing(
+    path: &Path,
+    strategy: OnExistingFile,
+    stamp: &str,
+    taken: impl Fn(&Path) -> bool,
+) -> Result<PathBuf> {
+    if !taken(path) {
+        return Ok(path.to_path_buf());
+    }
+
+    match strategy {
+        OnExistingFile::Overwrite => Ok(path.to_path_buf()),
+        OnExistingFile::Skip => Err(Error::OutputExists(path.to_path_buf())),
+        OnExistingFile::Suffix => Ok(first_free(path, &taken)),
+        OnExistingFile::Timestamp => {
+            let stamped = with_name_suffix(path, &format!("_{}", stamp));
+            if taken(&stamped) {
+                Ok(first_free(&stamped, &taken))
+            } else {
+                Ok(stamped)
+            }
+        }
+    }
+}
+
+/// `path` with the first `.<n>` that isn't taken. Not `_<n>`: split parts
+/// are already named `<name>_<sequence>`.
+fn first_free(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    (1u32..)
+        .map(|n| with_name_suffix(path, &format!(".{}", n)))
+        .find(|candidate| !taken(candidate))
+        .expect("some suffix is free")
+}
+
+/// `dir/name.ext` as `dir/name<suffix>.ext`
+fn with_name_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// Create `dir` if needed and check a file can be written there, so an
+/// unusable output directory is reported up front rather than as an IO
+/// error deep inside the first recording
+pub fn check_output_dir(dir: &Path) -> Result<()> {
+    let probe = dir.join(format!(".cbr-write-check-{}", std::process::id()));
+
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b""))
+        .map_err(|e| {
+            Error::Config(format!(
+                "Output directory {} is not writable: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// [`check_output_dir`] on the blocking pool, for the monitor loop: a hung
+/// mount fails the check after `timeout` instead of stalling every room
+pub async fn check_output_dir_within(dir: &Path, timeout: Duration) -> Result<()> {
+    let owned = dir.to_path_buf();
+    let check = tokio::task::spawn_blocking(move || check_output_dir(&owned));
+
+    match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(Error::Config(format!(
+            "Output directory check failed: {}",
+            e
+        ))),
+        Err(_) => Err(Error::Config(format!(
+            "Output directory {} didn't respond within {}s",
+            dir.display(),
+            timeout.as_secs()
+        ))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(unix)]
+    #[test]
+    fn test_check_output_dir_read_only() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("cbr-readonly-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(check_output_dir(&dir).is_ok());
+        assert!(check_output_dir(&dir.join("nested")).is_ok());
+
+        // Permission bits don't bind root; a new directory is owned by the euid
+        if std::fs::metadata(&dir).unwrap().uid() == 0 {
+            eprintln!("skipping read-only check: running as root");
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        }
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o555)).unwrap();
+        let result = check_output_dir(&dir.join("sub"));
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let err = result.unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+        assert!(err.to_string().contains("cbr-readonly"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_check_output_dir_within() {
+        let dir = std::env::temp_dir().join(format!("cbr-within-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        check_output_dir_within(&dir, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(dir.is_dir());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_generate_output_path() {
         let path = generate_output_path(
@@ -47,10 +197,12 @@ mod tests {
             "{{.Username}}_test",
             "testroom",
             0,
+            Timezone::Local,
+            "ts",
         )
         .unwrap();
 
-        assert!(path.to_string_lossy().contains("testroom_test.ts"));
+        assert!(path.to_string_lossy().ends_with("testroom_test.ts"));
     }
 
     #[test]
@@ -60,9 +212,62 @@ mod tests {
             "{{.Username}}_test",
             "testroom",
             5,
+            Timezone::Local,
+            "ts",
+        )
+        .unwrap();
+
+        assert!(path.to_string_lossy().ends_with("testroom_test_5.ts"));
+    }
+
+    #[test]
+    fn test_output_path_utc_timestamps() {
+        let instant = DateTime::parse_from_rfc3339("2024-01-02T23:04:05-02:00")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let path = output_path_at(
+            "./recordings",
+            "{{.Username}}_{{.Year}}-{{.Month}}-{{.Day}}_{{.Hour}}-{{.Minute}}-{{.Second}}",
+            "testroom",
+            0,
+            Timezone::Utc,
+            "ts",
+            instant,
+        )
+        .unwrap();
+
+        assert_eq!(
+            path,
+            PathBuf::from("./recordings").join("testroom_2024-01-03_01-04-05.ts")
+        );
+    }
+
+    #[test]
+    fn test_generate_output_path_extension() {
+        let path = generate_output_path(
+            "./recordings",
+            "{{.Username}}_test",
+            "testroom",
+            0,
+            Timezone::Local,
+            "mp4",
+        )
+        .unwrap();
+
+        assert!(path.to_string_lossy().ends_with("testroom_test.mp4"));
+
+        // A configured extension may come with its dot
+        let path = generate_output_path(
+            "./recordings",
+            "{{.Username}}_test",
+            "testroom",
+            2,
+            Timezone::Local,
+            ".m2ts",
         )
         .unwrap();
 
-        assert!(path.to_string_lossy().contains("testroom_test_5.ts"));
+        assert!(path.to_string_lossy().ends_with("testroom_test_2.m2ts"));
     }
 }
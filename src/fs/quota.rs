@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::RecordingConfig;
+use crate::error::Result;
+use crate::output::console;
+
+/// How often the quota task re-scans the output directory
+const QUOTA_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Extensions of files this tool writes, also matched with the `.part`
+/// suffix of a file still being written; nothing else is ever deleted
+const RECORDING_EXTENSIONS: &[&str] = &["ts", "mp4", "m4s"];
+
+/// Files currently being written by a recording, shared across all rooms
+static ACTIVE_FILES: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+fn active_files() -> &'static Mutex<HashSet<PathBuf>> {
+    ACTIVE_FILES.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Keeps a file off-limits to quota cleanup until dropped
+pub struct ActiveFileGuard {
+    path: PathBuf,
+}
+
+impl Drop for ActiveFileGuard {
+    fn drop(&mut self) {
+        active_files().lock().unwrap().remove(&self.path);
+    }
+}
+
+/// Register `path` as being written so quota cleanup won't touch it. A
+/// directory protects everything inside it.
+pub fn mark_active(path: &Path) -> ActiveFileGuard {
+    let path = normalize(path);
+    active_files().lock().unwrap().insert(path.clone());
+    ActiveFileGuard { path }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Debug)]
+struct RecordingFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Periodically enforce `max_total_size_gb` until cancelled. Returns
+/// immediately when no quota is configured.
+pub async fn run_quota_task(config: RecordingConfig, cancel_token: CancellationToken) {
+    if config.max_total_size_gb == 0 {
+        return;
+    }
+
+    let max_bytes = config.max_total_size_gb * 1024 * 1024 * 1024;
+    let dir = PathBuf::from(&config.output_directory);
+
+    loop {
+        let scan_dir = dir.clone();
+        let extension = config.file_extension.clone();
+        let cleanup = move || enforce_quota(&scan_dir, max_bytes, extension.as_deref());
+        match tokio::task::spawn_blocking(cleanup).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::warn!("Disk quota check failed: {}", e),
+            Err(e) => tracing::warn!("Disk quota task error: {}", e),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(QUOTA_CHECK_INTERVAL) => {}
+            _ = cancel_token.cancelled() => break,
+        }
+    }
+}
+
+/// Delete the oldest finished recordings under `dir` until their combined
+/// size is at most `max_bytes`. Returns the number of bytes reclaimed.
+/// `extra_extension` is a configured output extension to count as well.
+pub fn enforce_quota(dir: &Path, max_bytes: u64, extra_extension: Option<&str>) -> Result<u64> {
+    let mut extensions = RECORDING_EXTENSIONS.to_vec();
+    if let Some(extension) = extra_extension {
+        extensions.push(extension.trim_start_matches('.'));
+    }
+
+    let mut files = Vec::new();
+    collect_recordings(dir, &extensions, &mut files)?;
+
+    let mut total: u64 = files.iter().map(|f| f.size).sum();
+    if total <= max_bytes {
+        return Ok(0);
+    }
+
+    // Oldest first
+    files.sort_by_key(|f| f.modified);
+
+    let active = active_files().lock().unwrap().clone();
+    let mut reclaimed: u64 = 0;
+
+    for file in files {
+        if total <= max_bytes {
+            break;
+        }
+        let path = normalize(&file.path);
+        if active.iter().any(|a| path.starts_with(a)) {
+            continue;
+        }
+
+        match std::fs::remove_file(&file.path) {
+            Ok(()) => {
+                total -= file.size;
+                reclaimed += file.size;
+                console::print_info(&format!(
+                    "Disk quota: deleted {} ({:.2} MB)",
+                    file.path.display(),
+                    file.size as f64 / 1024.0 / 1024.0
+                ));
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Disk quota: failed to delete {}: {}",
+                    file.path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    if reclaimed > 0 {
+        console::print_info(&format!(
+            "Disk quota: reclaimed {:.2} MB",
+            reclaimed as f64 / 1024.0 / 1024.0
+        ));
+    }
+
+    Ok(reclaimed)
+}
+
+fn collect_recordings(
+    dir: &Path,
+    extensions: &[&str],
+    files: &mut Vec<RecordingFile>,
+) -> Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // Nothing recorded yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+
+        if metadata.is_dir() {
+            collect_recordings(&path, extensions, files)?;
+            continue;
+        }
+
+        if is_recording(&path, extensions) {
+            files.push(RecordingFile {
+                path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `path` ends in one of `extensions`, optionally followed by `.part`
+fn is_recording(path: &Path, extensions: &[&str]) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let name = name.strip_suffix(".part").unwrap_or(name);
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cbr-quota-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_file(dir: &Path, name: &str, size: usize, age_secs: u64) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_enforce_quota_deletes_oldest_first() {
+        let dir = temp_dir("oldest");
+        let oldest = write_file(&dir, "a.ts", 100, 300);
+        let middle = write_file(&dir, "b.ts", 100, 200);
+        let newest = write_file(&dir, "c.ts", 100, 100);
+        let other = write_file(&dir, "notes.txt", 1000, 400);
+        let foreign_part = write_file(&dir, "download.part", 1000, 400);
+
+        let reclaimed = enforce_quota(&dir, 200, None).unwrap();
+
+        assert_eq!(reclaimed, 100);
+        assert!(!oldest.exists());
+        assert!(middle.exists());
+        assert!(newest.exists());
+        assert!(other.exists());
+        assert!(foreign_part.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_recording() {
+        assert!(is_recording(Path::new("/r/alice.ts"), RECORDING_EXTENSIONS));
+        assert!(is_recording(
+            Path::new("/r/alice.mp4.part"),
+            RECORDING_EXTENSIONS
+        ));
+        assert!(!is_recording(
+            Path::new("/r/download.part"),
+            RECORDING_EXTENSIONS
+        ));
+        assert!(!is_recording(
+            Path::new("/r/notes.txt.part"),
+            RECORDING_EXTENSIONS
+        ));
+    }
+
+    #[test]
+    fn test_enforce_quota_skips_active_files() {
+        let dir = temp_dir("active");
+        let oldest = write_file(&dir, "a.ts", 100, 300);
+        let newest = write_file(&dir, "b.ts", 100, 100);
+
+        let guard = mark_active(&oldest);
+        enforce_quota(&dir, 100, None).unwrap();
+
+        assert!(oldest.exists());
+        assert!(!newest.exists());
+
+        drop(guard);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
 pub mod api;
 pub mod cli;
 pub mod config;
+#[cfg(feature = "database")]
+pub mod database;
 pub mod error;
 pub mod fs;
 pub mod output;
@@ -1,26 +1,40 @@
 use clap::Parser;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::Path;
 use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::EnvFilter;
 
 use chaturbate_recorder::api::ChaturbateClient;
 
-struct LocalTime;
+/// Log timestamps in the same format and timezone as console output
+struct ConsoleTime;
 
-impl FormatTime for LocalTime {
+impl FormatTime for ConsoleTime {
     fn format_time(&self, w: &mut tracing_subscriber::fmt::format::Writer<'_>) -> std::fmt::Result {
-        write!(w, "{}", chrono::Local::now().format("%Y-%m-%dT%H:%M:%S"))
+        write!(w, "{}", console::timestamp())
     }
 }
-use chaturbate_recorder::cli::Args;
-use chaturbate_recorder::config::{validate_room_name, Config};
+use chaturbate_recorder::cli::{parse_url_list, Args};
+use chaturbate_recorder::config::{
+    compile_segment_pattern, compile_title_filter, validate_file_extension, validate_quality,
+    validate_room_name, Config, MonitorConfig,
+};
 use chaturbate_recorder::error::{Error, EXIT_SUCCESS};
-use chaturbate_recorder::output::console;
-use chaturbate_recorder::stream::{get_stream_info, record_stream, RoomMonitor};
+use chaturbate_recorder::fs::check_output_dir;
+use chaturbate_recorder::output::stats::SessionReport;
+use chaturbate_recorder::output::{console, events, progress};
+use chaturbate_recorder::stream::{
+    record_sources, MonitorObserver, RecordingStats, RoomMonitor, StreamInfo, UrlSource,
+};
 
-#[tokio::main]
-async fn main() -> ExitCode {
+fn main() -> ExitCode {
     let args = Args::parse();
 
     // Setup logging
@@ -29,31 +43,120 @@ async fn main() -> ExitCode {
     } else if args.quiet {
         EnvFilter::new("error")
     } else {
-        EnvFilter::new("info")
+        // RUST_LOG can narrow output per room via the room spans, e.g.
+        // RUST_LOG='[recording{room=alice}]=debug'
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+    };
+
+    // With NDJSON events or JSON output, stdout carries nothing else. Log
+    // lines clear any progress bars first so they aren't drawn over.
+    if args.events_ndjson {
+        events::enable_ndjson();
+    }
+    chaturbate_recorder::output::stats::set_format(args.format);
+    // The room listing is the only thing --list-rooms prints to stdout
+    if args.list_rooms {
+        console::reserve_stdout();
+    }
+    let writer = if console::stdout_is_data() {
+        BoxMakeWriter::new(progress::Suspending(std::io::stderr))
+    } else {
+        BoxMakeWriter::new(progress::Suspending(std::io::stdout))
     };
 
     tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(false)
-        .with_timer(LocalTime)
+        .with_timer(ConsoleTime)
+        .with_writer(writer)
         .init();
 
-    // Load and merge config
-    let mut config = Config::load(&args.config).unwrap_or_else(|e| {
-        console::print_warning(&format!("Failed to load config: {}. Using defaults.", e));
-        Config::default()
-    });
+    // Load and merge config. A config that exists but can't be used stops
+    // here: recording with the defaults instead would ignore the user's rooms,
+    // output directory and network settings.
+    let mut config = match Config::load(&args.config) {
+        Ok(config) => config,
+        Err(e) => {
+            console::print_error(&format!("Failed to load config: {}", e));
+            return ExitCode::from(1);
+        }
+    };
 
     args.merge_into_config(&mut config);
+    console::configure(&config.logging);
+    if let Some(ref dir) = args.debug_dump {
+        chaturbate_recorder::fs::dump::enable(dir.clone());
+    }
 
-    // Get rooms to record
-    let rooms = args.get_rooms(&config);
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.enable_all();
+    if config.runtime.worker_threads > 0 {
+        runtime.worker_threads(config.runtime.worker_threads);
+    }
+    let runtime = match runtime.build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            console::print_error(&format!("Failed to start async runtime: {}", e));
+            return ExitCode::from(1);
+        }
+    };
 
-    if rooms.is_empty() {
+    runtime.block_on(run(args, config))
+}
+
+async fn run(args: Args, config: Config) -> ExitCode {
+    let urls = match args.url_file {
+        Some(ref path) => match read_url_file(path) {
+            Ok(urls) if urls.is_empty() => {
+                console::print_error(&format!("No usable URLs in {}", path.display()));
+                return ExitCode::from(1);
+            }
+            Ok(urls) => urls,
+            Err(e) => {
+                console::print_error(&format!("Failed to read {}: {}", path.display(), e));
+                return ExitCode::from(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    // Before the room list is read: stdin can only be read once
+    if args.list_rooms {
+        return list_rooms(&args, &config, &urls);
+    }
+
+    // Get rooms to record; with --url-file only the ones named explicitly
+    let rooms = if !urls.is_empty() && !args.has_cli_rooms() {
+        Vec::new()
+    } else {
+        match args.get_rooms(&config) {
+            Ok(rooms) => rooms,
+            Err(e) => {
+                console::print_error(&format!("Failed to read rooms: {}", e));
+                return ExitCode::from(1);
+            }
+        }
+    };
+
+    if rooms.is_empty() && urls.is_empty() {
         console::print_error("No rooms specified. Use -r <room> or configure rooms in config.toml");
         return ExitCode::from(1);
     }
 
+    // Recordings and their results are keyed by room name or label
+    if let Some(source) = urls.iter().find(|source| rooms.contains(&source.label)) {
+        console::print_error(&format!(
+            "--url-file label {} is also a room name; give the URL another label",
+            source.label
+        ));
+        return ExitCode::from(1);
+    }
+
+    if !urls.is_empty() && config.monitor.once {
+        console::print_error("--url-file records directly and can't be combined with once mode");
+        return ExitCode::from(1);
+    }
+
     // Validate room names
     for room in &rooms {
         if let Err(e) = validate_room_name(room) {
@@ -62,6 +165,56 @@ async fn main() -> ExitCode {
         }
     }
 
+    let recording = &config.recording;
+    if let Err(e) = validate_quality(
+        recording.resolution,
+        recording.framerate,
+        recording.allow_any_quality,
+    ) {
+        console::print_error(&format!("{}", e));
+        return ExitCode::from(1);
+    }
+
+    if let Some(ref extension) = recording.file_extension {
+        if let Err(e) = validate_file_extension(extension) {
+            console::print_error(&format!("{}", e));
+            return ExitCode::from(1);
+        }
+    }
+
+    if let Err(e) = check_output_dir(Path::new(&recording.output_directory)) {
+        console::print_error(&format!("{}", e));
+        return ExitCode::from(1);
+    }
+
+    #[cfg(not(feature = "database"))]
+    if config.recording.database.is_some() {
+        console::print_warning(
+            "database is set but this build has no SQLite support; rebuild with --features database",
+        );
+    }
+
+    #[cfg(not(feature = "tls-impersonate"))]
+    if config.network.tls_impersonate {
+        console::print_warning(
+            "tls_impersonate is set but this build has no TLS impersonation; rebuild with --features tls-impersonate",
+        );
+    }
+
+    if let Some(ref pattern) = config.recording.segment_pattern {
+        if let Err(e) = compile_segment_pattern(pattern) {
+            console::print_error(&format!("{}", e));
+            return ExitCode::from(1);
+        }
+    }
+
+    if let Some(ref pattern) = config.monitor.record_if_title_matches {
+        if let Err(e) = compile_title_filter(pattern) {
+            console::print_error(&format!("{}", e));
+            return ExitCode::from(1);
+        }
+    }
+
     // Create HTTP client
     let client = match ChaturbateClient::new(&config.network) {
         Ok(c) => c,
@@ -75,24 +228,44 @@ async fn main() -> ExitCode {
     let cancel_token = CancellationToken::new();
     let cancel_token_clone = cancel_token.clone();
 
-    // Handle Ctrl+C
+    // Handle Ctrl+C / SIGTERM
     tokio::spawn(async move {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to listen for Ctrl+C");
-        console::print_info("Received interrupt signal, shutting down...");
+        let signal = shutdown_signal().await;
+        console::print_info(&format!("Received {}, shutting down...", signal));
         cancel_token_clone.cancel();
     });
 
+    // Disk quota enforcement runs alongside either mode
+    tokio::spawn(chaturbate_recorder::fs::run_quota_task(
+        config.recording.clone(),
+        cancel_token.clone(),
+    ));
+
     if !args.quiet {
         console::print_banner();
+
+        // Live per-room progress lines only make sense on an interactive terminal
+        if std::io::stdout().is_terminal() && !console::stdout_is_data() {
+            progress::enable();
+        }
     }
 
     // Run in monitor mode or direct recording mode
-    let result = if args.monitor {
-        run_monitor_mode(client, rooms, &config, cancel_token).await
+    let result = if args.monitor || config.monitor.once {
+        let (reload_tx, reload_rx) = mpsc::unbounded_channel();
+
+        #[cfg(unix)]
+        {
+            let mut current = config.clone();
+            current.monitor.rooms = rooms.clone();
+            tokio::spawn(reload_on_sighup(args.clone(), current, reload_tx));
+        }
+        #[cfg(not(unix))]
+        drop(reload_tx);
+
+        run_monitor_mode(client, rooms, &config, reload_rx, cancel_token).await
     } else {
-        run_direct_mode(client, rooms, &config, cancel_token).await
+        run_direct_mode(client, rooms, urls, &config, &args, cancel_token).await
     };
 
     match result {
@@ -104,97 +277,314 @@ async fn main() -> ExitCode {
     }
 }
 
+/// `--list-rooms`: print the effective room list without touching the network
+fn list_rooms(args: &Args, config: &Config, urls: &[UrlSource]) -> ExitCode {
+    let rooms = if !urls.is_empty() && !args.has_cli_rooms() {
+        Vec::new()
+    } else {
+        match args.get_rooms_with_sources(config) {
+            Ok(rooms) => rooms,
+            Err(e) => {
+                console::print_error(&format!("Failed to read rooms: {}", e));
+                return ExitCode::from(1);
+            }
+        }
+    };
+
+    if let Some(e) = rooms
+        .iter()
+        .find_map(|(room, _)| validate_room_name(room).err())
+    {
+        console::print_error(&format!("{}", e));
+        return ExitCode::from(1);
+    }
+
+    for (room, source) in rooms {
+        if args.debug {
+            println!("{}\t{}", room, source.name());
+        } else {
+            println!("{}", room);
+        }
+    }
+    for source in urls {
+        if args.debug {
+            println!("{}\tfile ({})", source.label, source.url);
+        } else {
+            println!("{}", source.label);
+        }
+    }
+
+    ExitCode::from(EXIT_SUCCESS as u8)
+}
+
+fn read_url_file(path: &Path) -> Result<Vec<UrlSource>, Error> {
+    let file = std::fs::File::open(path)?;
+    parse_url_list(std::io::BufReader::new(file))
+}
+
+/// Wait for a shutdown request and return the name of the signal. On Unix
+/// this covers SIGTERM (docker stop, systemctl stop) as well as SIGINT.
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        let mut sigint = signal(SignalKind::interrupt()).expect("Failed to listen for SIGINT");
+
+        tokio::select! {
+            _ = sigterm.recv() => "SIGTERM",
+            _ = sigint.recv() => "interrupt signal",
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+        "interrupt signal"
+    }
+}
+
+/// Re-read the config file on every SIGHUP and pass the monitor settings on.
+/// Rooms given on the command line or stdin stay as they are; `[network]` and
+/// `[recording]` changes are only reported, since they need a restart.
+#[cfg(unix)]
+async fn reload_on_sighup(
+    args: Args,
+    running: Config,
+    reload_tx: mpsc::UnboundedSender<MonitorConfig>,
+) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            console::print_warning(&format!("Config reload on SIGHUP unavailable: {}", e));
+            return;
+        }
+    };
+
+    while sighup.recv().await.is_some() {
+        console::print_info(&format!("Received SIGHUP, reloading {}...", args.config));
+
+        let config = match load_reloaded_config(&args, &running) {
+            Ok(config) => config,
+            Err(e) => {
+                console::print_error(&format!(
+                    "Config reload failed: {}. Keeping current settings.",
+                    e
+                ));
+                continue;
+            }
+        };
+
+        if config.network != running.network {
+            console::print_warning("[network] changes need a restart to take effect");
+        }
+        if config.recording != running.recording {
+            console::print_warning("[recording] changes need a restart to take effect");
+        }
+        if config.runtime != running.runtime {
+            console::print_warning("[runtime] changes need a restart to take effect");
+        }
+
+        if reload_tx.send(config.monitor).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn load_reloaded_config(args: &Args, running: &Config) -> Result<Config, Error> {
+    let mut config = Config::load(&args.config)?;
+    args.merge_into_config(&mut config);
+
+    config.monitor.rooms = if args.has_cli_rooms() {
+        running.monitor.rooms.clone()
+    } else {
+        chaturbate_recorder::config::normalize_room_list(config.monitor.rooms)
+    };
+
+    if config.monitor.rooms.is_empty() {
+        return Err(Error::Config("no rooms configured".to_string()));
+    }
+    for room in &config.monitor.rooms {
+        validate_room_name(room)?;
+    }
+    if let Some(ref pattern) = config.monitor.record_if_title_matches {
+        compile_title_filter(pattern)?;
+    }
+    // [recording] only applies after a restart, but a bad target would then
+    // refuse to start
+    let recording = &config.recording;
+    validate_quality(
+        recording.resolution,
+        recording.framerate,
+        recording.allow_any_quality,
+    )?;
+
+    Ok(config)
+}
+
 async fn run_monitor_mode(
     client: ChaturbateClient,
     rooms: Vec<String>,
     config: &Config,
+    reload_rx: mpsc::UnboundedReceiver<MonitorConfig>,
     cancel_token: CancellationToken,
 ) -> Result<(), Error> {
-    let monitor = RoomMonitor::new(
-        client,
-        rooms,
-        &config.monitor,
-        config.recording.clone(),
-    );
+    let mut monitor = RoomMonitor::new(client, rooms, &config.monitor, config.recording.clone())
+        .with_reload(reload_rx);
 
     monitor.run(cancel_token).await
 }
 
+/// Reports each direct-mode recording as it starts
+struct DirectModeConsole;
+
+impl MonitorObserver for DirectModeConsole {
+    fn on_recording_start(&self, room: &str, stream_info: &StreamInfo) {
+        if stream_info.resolution > 0 {
+            console::print_success(&format!(
+                "{} is online at {}p{}fps",
+                room, stream_info.resolution, stream_info.framerate
+            ));
+        } else {
+            console::print_success(&format!("{} is online", room));
+        }
+    }
+}
+
+/// Record `rooms` and `urls` once, announcing each before it's checked
+async fn record_direct(
+    client: &ChaturbateClient,
+    rooms: Vec<String>,
+    urls: Vec<UrlSource>,
+    config: &Config,
+    cancel_token: &CancellationToken,
+) -> Vec<(String, Result<RecordingStats, Error>)> {
+    for name in rooms.iter().chain(urls.iter().map(|source| &source.label)) {
+        console::print_info(&format!("Checking {}...", name));
+    }
+
+    record_sources(
+        client.clone(),
+        rooms,
+        urls,
+        &config.recording,
+        Some(Arc::new(DirectModeConsole)),
+        cancel_token.clone(),
+    )
+    .await
+}
+
 async fn run_direct_mode(
     client: ChaturbateClient,
     rooms: Vec<String>,
+    urls: Vec<UrlSource>,
     config: &Config,
+    args: &Args,
     cancel_token: CancellationToken,
 ) -> Result<(), Error> {
-    use std::sync::Arc;
-    use tokio::task::JoinSet;
-
-    let client = Arc::new(client);
-    let mut tasks: JoinSet<(String, Result<chaturbate_recorder::stream::RecordingStats, Error>)> =
-        JoinSet::new();
-
-    // Start recording tasks for each room
-    for room in rooms {
-        let client = Arc::clone(&client);
-        let recording_config = config.recording.clone();
-        let cancel_token = cancel_token.clone();
-
-        tasks.spawn(async move {
-            console::print_info(&format!("Checking {}...", room));
-
-            // Get stream info
-            let stream_info = match get_stream_info(
-                &client,
-                &room,
-                recording_config.resolution,
-                recording_config.framerate,
-            )
-            .await
-            {
-                Ok(info) => info,
-                Err(e) => {
-                    return (room, Err(e));
-                }
-            };
+    let mut results = record_direct(&client, rooms, urls.clone(), config, &cancel_token).await;
+    let mut attempts: HashMap<String, u32> =
+        results.iter().map(|(room, _)| (room.clone(), 1)).collect();
 
-            console::print_success(&format!(
-                "{} is online at {}p{}fps",
-                room, stream_info.resolution, stream_info.framerate
-            ));
+    // Re-run the whole check and recording for just the rooms that failed
+    for attempt in 1..=args.retry_failed {
+        let retry: Vec<String> = results
+            .iter()
+            .filter(|(_, result)| matches!(result, Err(e) if !matches!(e, Error::Interrupted)))
+            .map(|(room, _)| room.clone())
+            .collect();
+        if retry.is_empty() || cancel_token.is_cancelled() {
+            break;
+        }
 
-            // Start recording
-            let result = record_stream(&client, &stream_info, &recording_config, cancel_token).await;
+        for (room, result) in &results {
+            if let Err(e) = result {
+                console::print_warning(&format!("{} failed ({}): {}", room, e.kind(), e));
+            }
+        }
+        console::print_info(&format!(
+            "Retrying {} room(s) in {}s (attempt {}/{})",
+            retry.len(),
+            args.retry_delay,
+            attempt,
+            args.retry_failed
+        ));
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(args.retry_delay)) => {}
+            _ = cancel_token.cancelled() => break,
+        }
+
+        let (retry_urls, retry_rooms): (Vec<_>, Vec<_>) = retry
+            .into_iter()
+            .partition(|room| urls.iter().any(|source| &source.label == room));
+        let retry_urls = urls
+            .iter()
+            .filter(|source| retry_urls.contains(&source.label))
+            .cloned()
+            .collect();
 
-            (room, result)
-        });
+        let retried = record_direct(&client, retry_rooms, retry_urls, config, &cancel_token).await;
+        for (room, result) in retried {
+            *attempts.entry(room.clone()).or_default() += 1;
+            match results.iter_mut().find(|(r, _)| *r == room) {
+                Some(entry) => entry.1 = result,
+                None => results.push((room, result)),
+            }
+        }
     }
 
     let mut successful = 0;
     let mut failed = 0;
+    let mut auth_failed = 0;
+    let mut report = SessionReport::default();
+    let mut failures = Vec::new();
 
-    // Wait for all tasks to complete
-    while let Some(result) = tasks.join_next().await {
+    for (room, result) in &results {
+        let room_attempts = attempts.get(room).copied().unwrap_or(1);
+        report.add(room, result, room_attempts);
         match result {
-            Ok((room, Ok(stats))) => {
-                chaturbate_recorder::output::stats::print_recording_stats(&room, &stats);
+            Ok(stats) => {
+                chaturbate_recorder::output::stats::print_recording_stats(room, stats);
                 successful += 1;
             }
-            Ok((room, Err(e))) => {
-                console::print_error(&format!("{}: {}", room, e));
-                failed += 1;
-            }
             Err(e) => {
-                console::print_error(&format!("Task error: {}", e));
+                console::print_error(&format!("{}: {}", room, e));
+                failures.push((room.as_str(), e, room_attempts));
                 failed += 1;
+                if e.is_auth_failure() {
+                    auth_failed += 1;
+                }
             }
         }
     }
 
+    if let Some(path) = args.stats_file.as_deref() {
+        if let Err(e) = report.write(path) {
+            console::print_warning(&format!(
+                "Failed to write stats file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    }
+
     if !cancel_token.is_cancelled() {
+        chaturbate_recorder::output::stats::print_failed_rooms(&failures);
         chaturbate_recorder::output::stats::print_summary(successful + failed, successful, failed);
     }
 
     if failed > 0 && successful == 0 {
+        // Every room refused access: likely the cookies, so say so and exit
+        // with the network code a wrapper can retry on after refreshing them
+        if auth_failed == failed {
+            return Err(Error::AuthFailed(failed as u32));
+        }
         Err(Error::Config("All recordings failed".to_string()))
     } else {
         Ok(())
@@ -1,8 +1,68 @@
-use chrono::Local;
+use chrono::Utc;
 use console::style;
+use std::sync::OnceLock;
 
-fn timestamp() -> String {
-    Local::now().format("%Y-%m-%dT%H:%M:%S").to_string()
+use crate::config::{LoggingConfig, Timezone};
+use crate::output::stats::{self, OutputFormat};
+use crate::output::{events, progress};
+
+/// Timestamp settings, set once at startup
+static LOGGING: OnceLock<LoggingConfig> = OnceLock::new();
+
+/// Use `config` for all console and log timestamps. Only the first call has
+/// an effect.
+pub fn configure(config: &LoggingConfig) {
+    let _ = LOGGING.set(config.clone());
+}
+
+/// Timezone shared by log timestamps and output filenames
+pub fn timezone() -> Timezone {
+    LOGGING.get().map(|c| c.timezone).unwrap_or_default()
+}
+
+/// Current time in the configured format and timezone
+pub fn timestamp() -> String {
+    // Messages printed before `configure` (e.g. config load errors) use the defaults
+    let default;
+    let config = match LOGGING.get() {
+        Some(config) => config,
+        None => {
+            default = LoggingConfig::default();
+            &default
+        }
+    };
+    config.timezone.format(Utc::now(), &config.timestamp_format)
+}
+
+/// Set when stdout is the program's output, e.g. the `--list-rooms` listing
+static STDOUT_RESERVED: OnceLock<bool> = OnceLock::new();
+
+/// Keep console messages and logs off stdout for the rest of the run
+pub fn reserve_stdout() {
+    let _ = STDOUT_RESERVED.set(true);
+}
+
+/// Whether stdout carries machine-readable output (NDJSON events,
+/// `--format json` documents, or a reserved listing), so messages for people
+/// go to stderr
+pub fn stdout_is_data() -> bool {
+    STDOUT_RESERVED.get().copied().unwrap_or(false)
+        || events::ndjson_enabled()
+        || stats::format() == OutputFormat::Json
+}
+
+/// Print a console line to stdout, or to stderr while stdout carries data
+fn print_line(line: std::fmt::Arguments) {
+    if stdout_is_data() {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Print `message` as is, without timestamp or level
+pub fn print_plain(message: &str) {
+    progress::suspend(|| print_line(format_args!("{}", message)));
 }
 
 pub fn print_banner() {
@@ -11,31 +71,61 @@ pub fn print_banner() {
 ║           Chaturbate Stream Recorder                  ║
 ╚═══════════════════════════════════════════════════════╝
 "#;
-    println!("{}", style(banner).cyan());
+    progress::suspend(|| print_line(format_args!("{}", style(banner).cyan())));
 }
 
 pub fn print_info(message: &str) {
-    println!("{} {} {}", timestamp(), style("INFO").cyan().bold(), message);
+    progress::suspend(|| {
+        print_line(format_args!(
+            "{} {} {}",
+            timestamp(),
+            style("INFO").cyan().bold(),
+            message
+        ))
+    });
 }
 
 pub fn print_success(message: &str) {
-    println!("{} {} {}", timestamp(), style("OK").green().bold(), message);
+    progress::suspend(|| {
+        print_line(format_args!(
+            "{} {} {}",
+            timestamp(),
+            style("OK").green().bold(),
+            message
+        ))
+    });
 }
 
 pub fn print_warning(message: &str) {
-    println!("{} {} {}", timestamp(), style("WARN").yellow().bold(), message);
+    progress::suspend(|| {
+        print_line(format_args!(
+            "{} {} {}",
+            timestamp(),
+            style("WARN").yellow().bold(),
+            message
+        ))
+    });
 }
 
 pub fn print_error(message: &str) {
-    eprintln!("{} {} {}", timestamp(), style("ERROR").red().bold(), message);
+    progress::suspend(|| {
+        eprintln!(
+            "{} {} {}",
+            timestamp(),
+            style("ERROR").red().bold(),
+            message
+        )
+    });
 }
 
 pub fn print_recording(room: &str, message: &str) {
-    println!(
-        "{} {} [{}] {}",
-        timestamp(),
-        style("REC").red().bold(),
-        style(room).cyan(),
-        message
-    );
+    progress::suspend(|| {
+        print_line(format_args!(
+            "{} {} [{}] {}",
+            timestamp(),
+            style("REC").red().bold(),
+            style(room).cyan(),
+            message
+        ))
+    });
 }
@@ -0,0 +1,180 @@
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::Result;
+
+/// A significant event, written to stdout as one JSON object per line with
+/// `--events-ndjson`. The `type` field names the variant; every line also
+/// carries an RFC 3339 UTC `timestamp`. Fields are only ever added, so
+/// consumers can rely on the ones present.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Result of one monitor check: online, offline, private, cloudflare,
+    /// captcha, below_min_resolution, server_error, or error. An online room
+    /// whose best stream turns out too small when a recording would start
+    /// gets a second event, below_min_resolution.
+    Check {
+        room: &'a str,
+        result: &'a str,
+    },
+    RecordingStart {
+        room: &'a str,
+        resolution: u32,
+        framerate: u32,
+    },
+    RecordingStop {
+        room: &'a str,
+        segments: u64,
+        bytes: u64,
+        duration_seconds: f64,
+        went_private: bool,
+        files: &'a [PathBuf],
+    },
+    RecordingError {
+        room: &'a str,
+        error: String,
+    },
+    CookieDeath {
+        failing_rooms: u32,
+        checked_rooms: u32,
+    },
+    CookieRecovery,
+}
+
+/// Whether events go to stdout, set once at startup
+static NDJSON: OnceLock<bool> = OnceLock::new();
+
+/// Write events to stdout from now on. Console messages move to stderr so the
+/// two never interleave. Only the first call has an effect.
+pub fn enable_ndjson() {
+    let _ = NDJSON.set(true);
+}
+
+pub fn ndjson_enabled() -> bool {
+    NDJSON.get().copied().unwrap_or(false)
+}
+
+/// Print `event` as one line on stdout if NDJSON events are enabled
+pub fn emit(event: &Event) {
+    if !ndjson_enabled() {
+        return;
+    }
+
+    let line = event_json(event, chrono::Utc::now()).to_string();
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+}
+
+fn event_json(event: &Event, now: chrono::DateTime<chrono::Utc>) -> serde_json::Value {
+    let mut json = serde_json::to_value(event).unwrap_or_default();
+    if let Some(object) = json.as_object_mut() {
+        object.insert("timestamp".to_string(), now.to_rfc3339().into());
+    }
+    json
+}
+
+/// Append-only JSONL log of room status transitions, one object per line:
+/// `{"timestamp": "<RFC 3339 UTC>", "room": "...", "from": "...", "to": "..."}`
+pub struct EventLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl EventLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one transition. Each line is written and flushed on its own so
+    /// the log survives a crash.
+    pub fn record(&self, room: &str, from: &str, to: &str) {
+        let event = serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "room": room,
+            "from": from,
+            "to": to,
+        });
+
+        let mut line = event.to_string();
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.write_all(line.as_bytes()).and_then(|_| file.flush()) {
+            tracing::warn!("Failed to write event log {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json_schema() {
+        let now = chrono::DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        let json = event_json(
+            &Event::Check {
+                room: "alice",
+                result: "offline",
+            },
+            now,
+        );
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "check",
+                "room": "alice",
+                "result": "offline",
+                "timestamp": "2024-01-02T03:04:05+00:00",
+            })
+        );
+
+        let json = event_json(&Event::CookieRecovery, now);
+        assert_eq!(json["type"], "cookie_recovery");
+    }
+
+    #[test]
+    fn test_event_log_appends_jsonl() {
+        let path = std::env::temp_dir().join(format!("cbr-events-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let log = EventLog::open(&path).unwrap();
+        log.record("alice", "offline", "recording");
+        log.record("alice", "recording", "offline");
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let events: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["room"], "alice");
+        assert_eq!(events[0]["from"], "offline");
+        assert_eq!(events[0]["to"], "recording");
+        assert_eq!(events[1]["to"], "offline");
+        assert!(events[1]["timestamp"].is_string());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,72 @@
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressFinish, ProgressStyle};
+use std::io::{self, Write};
+use std::sync::OnceLock;
 use std::time::Duration;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Shared container for all live bars, set once by `enable()`. Recordings in
+/// monitor mode each get their own line instead of overwriting one another.
+static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Turn on live progress bars for the rest of the process
+pub fn enable() {
+    let _ = MULTI.set(MultiProgress::with_draw_target(ProgressDrawTarget::stdout()));
+}
+
+/// Run `f` with the bars temporarily cleared so printed lines don't get
+/// drawn over. A plain call when progress bars are disabled.
+pub fn suspend<F: FnOnce() -> R, R>(f: F) -> R {
+    match MULTI.get() {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
+
+/// Log writer for the tracing subscriber that clears the bars around every
+/// line, like `suspend` does for console messages
+pub struct Suspending<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Suspending<M> {
+    type Writer = SuspendingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SuspendingWriter(self.0.make_writer())
+    }
+}
+
+/// Writer handed out by [`Suspending`]; tracing writes each event in one call
+pub struct SuspendingWriter<W>(W);
+
+impl<W: Write> Write for SuspendingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        suspend(|| self.0.write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Per-room recording line showing elapsed time and the message set by the
+/// recorder. Returns a hidden bar when progress bars are disabled.
+pub fn create_recording_bar(room: &str) -> ProgressBar {
+    let Some(multi) = MULTI.get() else {
+        return ProgressBar::hidden();
+    };
+
+    // Clear on drop too, so a recording that errors out doesn't leave a stale line
+    let bar = multi.add(ProgressBar::new_spinner().with_finish(ProgressFinish::AndClear));
+    bar.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.red} [{elapsed_precise}] {prefix:.cyan} {msg}")
+            .unwrap(),
+    );
+    bar.set_prefix(room.to_string());
+    bar.set_message("waiting for segments");
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
 
 pub fn create_spinner(message: &str) -> ProgressBar {
     let spinner = ProgressBar::new_spinner();
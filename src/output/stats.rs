@@ -1,36 +1,300 @@
 use console::style;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
 
+use crate::error::Result;
+use crate::output::events;
 use crate::stream::RecordingStats;
 
+/// How recording results and summaries are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables
+    #[default]
+    Text,
+    /// One JSON object per line on stdout, tagged with a `type` field
+    Json,
+}
+
+/// Output format, set once at startup
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Print results in `format` from now on. Only the first call has an effect.
+pub fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> OutputFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Recordings of one room over a monitor run
+#[derive(Debug, Clone, Default)]
+pub struct RoomTotals {
+    pub sessions: u32,
+    pub bytes_written: u64,
+    /// Media recorded, in seconds
+    pub duration_seconds: f64,
+}
+
+impl RoomTotals {
+    pub fn add(&mut self, stats: &RecordingStats) {
+        self.sessions += 1;
+        self.bytes_written += stats.bytes_written;
+        self.duration_seconds += stats.duration_seconds;
+    }
+}
+
 pub fn print_recording_stats(room: &str, stats: &RecordingStats) {
+    // The recording_stop event carries these
+    if events::ndjson_enabled() {
+        return;
+    }
+
+    if format() == OutputFormat::Json {
+        println!("{}", recording_stats_json(room, stats));
+        return;
+    }
+
     println!("{}", style("═".repeat(50)).dim());
     println!("Recording stats for {}:", style(room).cyan().bold());
     println!("  Segments:    {}", stats.segments_downloaded);
+    if stats.segments_failed > 0 {
+        println!(
+            "  Failed:      {}",
+            style(stats.segments_failed.to_string()).yellow()
+        );
+    }
+    println!("  Retries:     {}", stats.segment_retries);
     println!(
         "  Total size:  {:.2} MB",
         stats.bytes_written as f64 / 1024.0 / 1024.0
     );
     println!("  Duration:    {}", format_duration(stats.duration_seconds));
+    println!("  Bitrate:     {:.2} Mbps", stats.average_bitrate_mbps);
+    println!(
+        "  Throughput:  {:.2} MB/s",
+        stats.throughput_bytes_per_second / 1024.0 / 1024.0
+    );
     println!("  Files:       {}", stats.files_created);
     println!("{}", style("═".repeat(50)).dim());
 }
 
 pub fn print_summary(total_rooms: usize, successful: usize, failed: usize) {
+    if events::ndjson_enabled() {
+        return;
+    }
+
+    if format() == OutputFormat::Json {
+        let summary = serde_json::json!({
+            "type": "summary",
+            "total": total_rooms,
+            "successful": successful,
+            "failed": failed,
+        });
+        println!("{}", summary);
+        return;
+    }
+
     println!();
     println!("{}", style("═".repeat(50)).dim());
     println!("Session Summary:");
     println!("  Total rooms:  {}", total_rooms);
-    println!(
-        "  Successful:   {}",
-        style(successful.to_string()).green()
-    );
+    println!("  Successful:   {}", style(successful.to_string()).green());
     if failed > 0 {
         println!("  Failed:       {}", style(failed.to_string()).red());
     }
     println!("{}", style("═".repeat(50)).dim());
 }
 
-fn format_duration(seconds: f64) -> String {
+/// Rooms of a direct-mode run that failed, with why and after how many
+/// attempts
+pub fn print_failed_rooms(failures: &[(&str, &crate::error::Error, u32)]) {
+    if failures.is_empty() || events::ndjson_enabled() {
+        return;
+    }
+
+    if format() == OutputFormat::Json {
+        let rooms: Vec<_> = failures
+            .iter()
+            .map(|(room, error, attempts)| {
+                serde_json::json!({
+                    "room": room,
+                    "kind": error.kind(),
+                    "error": error.to_string(),
+                    "attempts": attempts,
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({ "type": "failed_rooms", "rooms": rooms })
+        );
+        return;
+    }
+
+    println!();
+    println!("Failed rooms:");
+    for (room, error, attempts) in failures {
+        println!(
+            "  {:<20} {:<16} {} attempt(s)  {}",
+            style(room).cyan(),
+            style(error.kind()).red(),
+            attempts,
+            style(error).dim()
+        );
+    }
+}
+
+/// Per-room session counts, sizes and hours at the end of a monitor run,
+/// with the run's totals and the most recordings that ran at once
+pub fn print_room_summary(totals: &HashMap<String, RoomTotals>, peak_recordings: usize) {
+    if totals.is_empty() || events::ndjson_enabled() {
+        return;
+    }
+
+    let mut rooms: Vec<_> = totals.iter().collect();
+    rooms.sort_by(|a, b| a.0.cmp(b.0));
+    let total_bytes: u64 = rooms.iter().map(|(_, totals)| totals.bytes_written).sum();
+    let total_hours: f64 = rooms
+        .iter()
+        .map(|(_, totals)| totals.duration_seconds)
+        .sum::<f64>()
+        / 3600.0;
+
+    if format() == OutputFormat::Json {
+        let rooms: Vec<_> = rooms
+            .into_iter()
+            .map(|(room, totals)| {
+                serde_json::json!({
+                    "room": room,
+                    "sessions": totals.sessions,
+                    "bytes": totals.bytes_written,
+                    "hours": totals.duration_seconds / 3600.0,
+                })
+            })
+            .collect();
+        let summary = serde_json::json!({
+            "type": "room_summary",
+            "rooms": rooms,
+            "total_bytes": total_bytes,
+            "total_hours": total_hours,
+            "peak_recordings": peak_recordings,
+        });
+        println!("{}", summary);
+        return;
+    }
+
+    println!();
+    println!("{}", style("═".repeat(50)).dim());
+    println!("Recordings by room:");
+    for (room, totals) in rooms {
+        println!(
+            "  {:<20} {:>3} session(s)  {:>10.2} MB  {:>7.2} h",
+            style(room).cyan(),
+            totals.sessions,
+            totals.bytes_written as f64 / 1024.0 / 1024.0,
+            totals.duration_seconds / 3600.0
+        );
+    }
+    println!(
+        "  {:<20}               {:>10.2} MB  {:>7.2} h",
+        "Total",
+        total_bytes as f64 / 1024.0 / 1024.0,
+        total_hours
+    );
+    println!("  Peak concurrent recordings: {}", peak_recordings);
+    println!("{}", style("═".repeat(50)).dim());
+}
+
+/// Outcome of one room in a direct-mode session report
+#[derive(Debug, Serialize)]
+pub struct RoomReport {
+    pub room: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<&'static str>,
+    /// Recording attempts, more than one when `--retry-failed` re-ran the room
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<RecordingStats>,
+}
+
+/// Every room of a direct-mode session with totals, as written by `--stats-file`
+#[derive(Debug, Default, Serialize)]
+pub struct SessionReport {
+    pub total: usize,
+    pub successful: usize,
+    pub failed: usize,
+    pub segments_downloaded: u64,
+    pub bytes_written: u64,
+    pub duration_seconds: f64,
+    pub rooms: Vec<RoomReport>,
+}
+
+impl SessionReport {
+    pub fn add(&mut self, room: &str, result: &Result<RecordingStats>, attempts: u32) {
+        self.total += 1;
+        let report = match result {
+            Ok(stats) => {
+                self.successful += 1;
+                self.segments_downloaded += stats.segments_downloaded;
+                self.bytes_written += stats.bytes_written;
+                self.duration_seconds += stats.duration_seconds;
+                RoomReport {
+                    room: room.to_string(),
+                    success: true,
+                    error: None,
+                    error_kind: None,
+                    attempts,
+                    stats: Some(stats.clone()),
+                }
+            }
+            Err(e) => {
+                self.failed += 1;
+                RoomReport {
+                    room: room.to_string(),
+                    success: false,
+                    error: Some(e.to_string()),
+                    error_kind: Some(e.kind()),
+                    attempts,
+                    stats: None,
+                }
+            }
+        };
+        self.rooms.push(report);
+    }
+
+    /// Write the report as pretty-printed JSON, replacing `path`
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Machine-readable result of one recording
+pub fn recording_stats_json(room: &str, stats: &RecordingStats) -> serde_json::Value {
+    serde_json::json!({
+        "type": "recording",
+        "room": room,
+        "segments": stats.segments_downloaded,
+        "segments_failed": stats.segments_failed,
+        "segment_retries": stats.segment_retries,
+        "bytes": stats.bytes_written,
+        "duration_seconds": stats.duration_seconds,
+        "average_bitrate_mbps": stats.average_bitrate_mbps,
+        "went_private": stats.went_private,
+        "files": stats.files,
+    })
+}
+
+pub fn format_duration(seconds: f64) -> String {
     let total_secs = seconds as u64;
     let hours = total_secs / 3600;
     let minutes = (total_secs % 3600) / 60;
@@ -44,3 +308,62 @@ fn format_duration(seconds: f64) -> String {
         format!("{}s", secs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_recording_stats_json() {
+        let stats = RecordingStats {
+            segments_downloaded: 10,
+            segments_failed: 1,
+            segment_retries: 4,
+            bytes_written: 2048,
+            duration_seconds: 20.0,
+            files: vec![PathBuf::from("room.ts")],
+            ..Default::default()
+        };
+
+        let json = recording_stats_json("alice", &stats);
+        assert_eq!(json["type"], "recording");
+        assert_eq!(json["room"], "alice");
+        assert_eq!(json["segments"], 10);
+        assert_eq!(json["segments_failed"], 1);
+        assert_eq!(json["segment_retries"], 4);
+        assert_eq!(json["bytes"], 2048);
+        assert_eq!(json["duration_seconds"], 20.0);
+        assert_eq!(json["files"], serde_json::json!(["room.ts"]));
+    }
+
+    #[test]
+    fn test_session_report() {
+        let stats = RecordingStats {
+            segments_downloaded: 10,
+            bytes_written: 2048,
+            duration_seconds: 20.0,
+            ..Default::default()
+        };
+
+        let mut report = SessionReport::default();
+        report.add("alice", &Ok(stats), 1);
+        report.add(
+            "bob",
+            &Err(crate::error::Error::BroadcasterOffline("bob".to_string())),
+            3,
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["total"], 2);
+        assert_eq!(json["successful"], 1);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["bytes_written"], 2048);
+        assert_eq!(json["rooms"][0]["stats"]["segments_downloaded"], 10);
+        assert_eq!(json["rooms"][1]["success"], false);
+        assert_eq!(json["rooms"][1]["error"], "Broadcaster offline: bob");
+        assert_eq!(json["rooms"][1]["error_kind"], "offline");
+        assert_eq!(json["rooms"][1]["attempts"], 3);
+        assert!(json["rooms"][1].get("stats").is_none());
+    }
+}
@@ -2,65 +2,405 @@ use regex::Regex;
 use serde::Deserialize;
 
 use crate::api::ChaturbateClient;
+use crate::config::{QualityMode, RecordingConfig};
 use crate::error::{Error, Result};
+use crate::fs::dump;
+
+/// Weight of one pixel of height difference relative to one fps of framerate
+/// difference. Resolution steps are 100+ pixels apart, so resolution always
+/// dominates and framerate only decides between equal heights.
+const RESOLUTION_WEIGHT: u64 = 10;
 
 #[derive(Debug, Clone)]
 pub struct StreamInfo {
+    /// Media playlist being recorded; may carry access tokens, see
+    /// [`StreamInfo::redacted_source`]
     pub hls_source: String,
     pub room: String,
     pub resolution: u32,
     pub framerate: u32,
+    /// Advertised peak bitrate of the selected variant, in bits per second
+    pub bandwidth: u64,
+    /// The room's subject line, when the dossier has one
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub source: StreamSource,
+}
+
+impl StreamInfo {
+    /// `hls_source` with its query values and token-like path segments
+    /// hidden, for logs that may end up in bug reports
+    pub fn redacted_source(&self) -> String {
+        redact_url(&self.hls_source)
+    }
+}
+
+/// Path segments with a run of this many letters and digits are taken to be
+/// tokens
+const TOKEN_RUN: usize = 12;
+
+/// `url` with the value of every query parameter replaced, since CDN
+/// playlist URLs often carry access tokens there. Some CDNs put them in the
+/// path instead, so path segments that look like one are replaced too. The
+/// fragment is dropped.
+pub fn redact_url(url: &str) -> String {
+    let url = url.split('#').next().unwrap_or_default();
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (url, None),
+    };
+    let base = redact_path(base);
+    let Some(query) = query else {
+        return base;
+    };
+
+    let params: Vec<String> = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) => format!("{}=<redacted>", name),
+            None => "<redacted>".to_string(),
+        })
+        .collect();
+    format!("{}?{}", base, params.join("&"))
+}
+
+/// `base` (a URL without query) with token-like path segments replaced
+fn redact_path(base: &str) -> String {
+    let path_start = base
+        .find("://")
+        .map(|i| i + 3)
+        .and_then(|host| base[host..].find('/').map(|i| host + i));
+    let Some(path_start) = path_start else {
+        return base.to_string();
+    };
+
+    let (origin, path) = base.split_at(path_start);
+    let segments: Vec<&str> = path
+        .split('/')
+        .map(|segment| {
+            let token = segment
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|run| run.len() >= TOKEN_RUN);
+            if token {
+                "<redacted>"
+            } else {
+                segment
+            }
+        })
+        .collect();
+    format!("{}{}", origin, segments.join("/"))
+}
+
+/// Where a stream was found, and so how to look it up again
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum StreamSource {
+    /// The room page of `StreamInfo::room`
+    #[default]
+    Room,
+    /// A plain HLS playlist URL; `StreamInfo::room` is just its label
+    Url(String),
+}
+
+/// A playlist URL to record directly, named by `label` in place of a room
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlSource {
+    pub url: String,
+    pub label: String,
 }
 
 #[derive(Debug, Deserialize)]
 struct RoomDossier {
     hls_source: Option<String>,
+    /// Kept loose so an unexpected shape never costs us the stream URL
+    #[serde(default)]
+    room_title: Option<serde_json::Value>,
+    #[serde(default)]
+    tags: Option<serde_json::Value>,
+}
+
+/// Title and tags from the room dossier; empty when it has neither
+#[derive(Debug, Default, PartialEq)]
+struct RoomDetails {
+    title: Option<String>,
+    tags: Vec<String>,
+}
+
+impl RoomDossier {
+    fn details(&self) -> RoomDetails {
+        let title = self
+            .room_title
+            .as_ref()
+            .and_then(|t| t.as_str())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+
+        let tags = match self.tags {
+            Some(serde_json::Value::Array(ref tags)) => tags
+                .iter()
+                .filter_map(|t| t.as_str())
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        RoomDetails { title, tags }
+    }
 }
 
+/// A room whose page says it is live. Its master playlist hasn't been
+/// fetched yet; [`select_stream`] does that.
+#[derive(Debug, Clone)]
+pub struct LiveRoom {
+    pub room: String,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    master_url: String,
+}
+
+/// Live stream of `room` at the variant chosen by `config`'s resolution,
+/// framerate, quality mode and resolution floor
 pub async fn get_stream_info(
     client: &ChaturbateClient,
     room: &str,
-    target_resolution: u32,
-    target_framerate: u32,
+    config: &RecordingConfig,
+) -> Result<StreamInfo> {
+    let live = is_room_online(client, room).await?;
+    select_stream(client, &live, config).await
+}
+
+/// The variant of a live room's stream chosen by `config`, from its master
+/// playlist
+pub async fn select_stream(
+    client: &ChaturbateClient,
+    live: &LiveRoom,
+    config: &RecordingConfig,
+) -> Result<StreamInfo> {
+    let room = live.room.as_str();
+
+    // Fetch master playlist and select variant
+    let variants = match load_variants(client, room, &live.master_url).await {
+        Ok(variants) => variants,
+        Err(e) => {
+            client.invalidate_master_playlist(room);
+            return Err(e);
+        }
+    };
+
+    let variant = pick_variant(
+        room,
+        &variants,
+        config.resolution,
+        config.framerate,
+        config.quality_mode,
+    )?;
+    let variant = apply_min_resolution(
+        room,
+        &variants,
+        variant,
+        config.min_resolution,
+        config.skip_below_min,
+    )?;
+
+    let mut info = variant.stream_info(room);
+    info.title = live.title.clone();
+    info.tags = live.tags.clone();
+    Ok(info)
+}
+
+/// Stream of a plain HLS URL instead of a room page. A master playlist is
+/// narrowed to one variant like a room's; a media playlist is recorded as
+/// is, with its resolution and framerate unknown (0).
+pub async fn url_stream_info(
+    client: &ChaturbateClient,
+    source: &UrlSource,
+    config: &RecordingConfig,
+) -> Result<StreamInfo> {
+    // Through the master cache, so select_stream doesn't fetch it again
+    let content = client
+        .get_master_playlist(&source.label, &source.url)
+        .await?;
+
+    match m3u8_rs::parse_playlist_res(content.as_bytes()) {
+        Ok(m3u8_rs::Playlist::MasterPlaylist(_)) => {
+            let live = LiveRoom {
+                room: source.label.clone(),
+                title: None,
+                tags: Vec::new(),
+                master_url: source.url.clone(),
+            };
+            let info = select_stream(client, &live, config).await?;
+            Ok(StreamInfo {
+                source: StreamSource::Url(source.url.clone()),
+                ..info
+            })
+        }
+        Ok(m3u8_rs::Playlist::MediaPlaylist(_)) => Ok(StreamInfo {
+            hls_source: source.url.clone(),
+            room: source.label.clone(),
+            resolution: 0,
+            framerate: 0,
+            bandwidth: 0,
+            title: None,
+            tags: Vec::new(),
+            source: StreamSource::Url(source.url.clone()),
+        }),
+        Err(e) => Err(Error::M3u8(format!(
+            "Failed to parse playlist {}: {:?}",
+            source.url, e
+        ))),
+    }
+}
+
+/// Look `current` up again where it was found: its room page, or its URL
+pub async fn refresh_stream(
+    client: &ChaturbateClient,
+    current: &StreamInfo,
+    config: &RecordingConfig,
 ) -> Result<StreamInfo> {
+    match current.source {
+        StreamSource::Room => get_stream_info(client, &current.room, config).await,
+        StreamSource::Url(ref url) => {
+            let source = UrlSource {
+                url: url.clone(),
+                label: current.room.clone(),
+            };
+            url_stream_info(client, &source, config).await
+        }
+    }
+}
+
+/// The variant just below `current` in quality, for when downloads can't
+/// keep up. `None` when `current` is already the lowest on offer.
+pub async fn get_lower_stream_info(
+    client: &ChaturbateClient,
+    current: &StreamInfo,
+) -> Result<Option<StreamInfo>> {
+    let master_url = match current.source {
+        StreamSource::Room => is_room_online(client, &current.room).await?.master_url,
+        StreamSource::Url(ref url) => url.clone(),
+    };
+    let variants = load_variants(client, &current.room, &master_url).await?;
+
+    Ok(pick_lower(&variants, current).map(|v| StreamInfo {
+        title: current.title.clone(),
+        tags: current.tags.clone(),
+        source: current.source.clone(),
+        ..v.stream_info(&current.room)
+    }))
+}
+
+/// Whether `room` is live, from its room page alone: one request, no
+/// playlist. An offline room is `BroadcasterOffline`.
+pub async fn is_room_online(client: &ChaturbateClient, room: &str) -> Result<LiveRoom> {
     // Fetch room page
     let html = client.get_room_page(room).await?;
 
-    // Check if online (has playlist)
-    if !html.contains("playlist.m3u8") {
-        return Err(Error::BroadcasterOffline(room.to_string()));
+    let json = dossier_json(&html)?;
+    if let Some(ref json) = json {
+        dump::save(room, "dossier", "json", json).await;
     }
+    let dossier = parse_dossier(json.as_deref())?;
+
+    // The dossier's hls_source is the online signal: set when live, null or
+    // empty when offline
+    match extract_hls_source(&html, dossier.as_ref())? {
+        HlsSource::Live(master_url) => {
+            let details = dossier.map(|d| d.details()).unwrap_or_default();
+            Ok(LiveRoom {
+                room: room.to_string(),
+                title: details.title,
+                tags: details.tags,
+                master_url,
+            })
+        }
+        HlsSource::Offline => {
+            client.invalidate_master_playlist(room);
+            Err(Error::BroadcasterOffline(room.to_string()))
+        }
+        HlsSource::NoDossier if html.contains("playlist.m3u8") => {
+            // A playlist is on the page but we can't find it: markup changed
+            tracing::warn!(
+                "Room page for {} references a playlist but has no readable dossier; the page format may have changed",
+                room
+            );
+            Err(Error::StreamNotFound(format!(
+                "{} (no room dossier found; the page format may have changed)",
+                room
+            )))
+        }
+        HlsSource::NoDossier => {
+            tracing::debug!("No room dossier or playlist on the page for {}", room);
+            client.invalidate_master_playlist(room);
+            Err(Error::BroadcasterOffline(room.to_string()))
+        }
+    }
+}
 
-    // Extract initialRoomDossier JSON
-    let re = Regex::new(r#"window\.initialRoomDossier\s*=\s*"(.+?)""#)?;
-    let captures = re
-        .captures(&html)
-        .ok_or_else(|| Error::StreamNotFound(room.to_string()))?;
-    let encoded_json = &captures[1];
+/// `window.initialRoomDossier = <string>`, where the string may be single or
+/// double quoted and optionally wrapped in `JSON.parse(...)`
+const DOSSIER_PATTERN: &str = r#"window\.initialRoomDossier\s*=\s*(?:JSON\.parse\(\s*)?(?:"((?:[^"\\]|\\.)*)"|'((?:[^'\\]|\\.)*)')"#;
+
+/// `"hls_source": null` or `"hls_source": "<url>"` anywhere in the (decoded) page
+const HLS_SOURCE_PATTERN: &str = r#""hls_source"\s*:\s*(?:null|"([^"]*)")"#;
+
+/// What the room page's dossier says about the stream
+#[derive(Debug, PartialEq)]
+enum HlsSource {
+    Live(String),
+    /// Dossier present, but `hls_source` null or empty
+    Offline,
+    /// Neither a dossier nor an `hls_source` field found on the page
+    NoDossier,
+}
+
+impl HlsSource {
+    fn from_url(url: Option<String>) -> Self {
+        match url {
+            Some(url) if !url.is_empty() => HlsSource::Live(url),
+            _ => HlsSource::Offline,
+        }
+    }
+}
 
-    // Decode unicode escapes
-    let json_str = decode_unicode_escapes(encoded_json)?;
+/// The room dossier from its JSON text, if the page has one
+fn parse_dossier(json: Option<&str>) -> Result<Option<RoomDossier>> {
+    Ok(json.map(serde_json::from_str).transpose()?)
+}
 
-    // Parse JSON to get hls_source
-    let dossier: RoomDossier = serde_json::from_str(&json_str)?;
-    let master_url = dossier
-        .hls_source
-        .ok_or_else(|| Error::StreamNotFound(room.to_string()))?;
+/// The dossier's JSON text, decoded from its JavaScript string literal
+fn dossier_json(html: &str) -> Result<Option<String>> {
+    let re = Regex::new(DOSSIER_PATTERN)?;
+    let Some(captures) = re.captures(html) else {
+        return Ok(None);
+    };
+
+    let encoded = captures
+        .get(1)
+        .or_else(|| captures.get(2))
+        .map_or("", |m| m.as_str());
+    Ok(Some(decode_unicode_escapes(encoded)?))
+}
 
-    if master_url.is_empty() {
-        return Err(Error::BroadcasterOffline(room.to_string()));
+/// The stream state from the page's `dossier`, or from the page itself when
+/// it has none
+fn extract_hls_source(html: &str, dossier: Option<&RoomDossier>) -> Result<HlsSource> {
+    if let Some(dossier) = dossier {
+        return Ok(HlsSource::from_url(dossier.hls_source.clone()));
     }
 
-    // Fetch master playlist and select variant
-    let (playlist_url, resolution, framerate) =
-        select_variant(client, &master_url, target_resolution, target_framerate).await?;
+    // The dossier assignment changed shape; look for the field itself
+    let decoded = decode_unicode_escapes(html)?;
+    let re = Regex::new(HLS_SOURCE_PATTERN)?;
+    if let Some(captures) = re.captures(&decoded) {
+        tracing::debug!("Room dossier not found, using hls_source from the page");
+        let url = captures.get(1).map(|m| m.as_str().to_string());
+        return Ok(HlsSource::from_url(url));
+    }
 
-    Ok(StreamInfo {
-        hls_source: playlist_url,
-        room: room.to_string(),
-        resolution,
-        framerate,
-    })
+    Ok(HlsSource::NoDossier)
 }
 
 fn decode_unicode_escapes(input: &str) -> Result<String> {
@@ -100,6 +440,10 @@ fn decode_unicode_escapes(input: &str) -> Result<String> {
                     chars.next();
                     result.push('"');
                 }
+                Some('\'') => {
+                    chars.next();
+                    result.push('\'');
+                }
                 Some('\\') => {
                     chars.next();
                     result.push('\\');
@@ -120,7 +464,7 @@ fn decode_unicode_escapes(input: &str) -> Result<String> {
     Ok(result)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Variant {
     url: String,
     resolution: u32,
@@ -128,13 +472,29 @@ struct Variant {
     bandwidth: u64,
 }
 
-async fn select_variant(
+impl Variant {
+    fn stream_info(&self, room: &str) -> StreamInfo {
+        StreamInfo {
+            hls_source: self.url.clone(),
+            room: room.to_string(),
+            resolution: self.resolution,
+            framerate: self.framerate,
+            bandwidth: self.bandwidth,
+            title: None,
+            tags: Vec::new(),
+            source: StreamSource::Room,
+        }
+    }
+}
+
+/// Every variant in the room's master playlist; never empty
+async fn load_variants(
     client: &ChaturbateClient,
+    room: &str,
     master_url: &str,
-    target_resolution: u32,
-    target_framerate: u32,
-) -> Result<(String, u32, u32)> {
-    let content = client.get(master_url).await?;
+) -> Result<Vec<Variant>> {
+    let content = client.get_master_playlist(room, master_url).await?;
+    dump::save(room, "master", "m3u8", &content).await;
 
     // Parse master playlist
     let playlist = m3u8_rs::parse_master_playlist_res(content.as_bytes())
@@ -149,19 +509,7 @@ async fn select_variant(
             .map(|r| r.height as u32)
             .unwrap_or(0);
 
-        // Detect framerate from NAME or other attributes
-        // Chaturbate uses "FPS:60.0" in the NAME field for 60fps streams
-        let framerate = if variant
-            .other_attributes
-            .as_ref()
-            .and_then(|attrs| attrs.get("NAME"))
-            .map(|name| name.to_string().contains("FPS:60"))
-            .unwrap_or(false)
-        {
-            60
-        } else {
-            30
-        };
+        let framerate = variant_framerate(variant);
 
         let url = resolve_url(master_url, &variant.uri)?;
 
@@ -174,8 +522,75 @@ async fn select_variant(
     }
 
     if variants.is_empty() {
-        return Err(Error::M3u8("No variants found in master playlist".to_string()));
+        return Err(Error::M3u8(
+            "No variants found in master playlist".to_string(),
+        ));
+    }
+
+    Ok(variants)
+}
+
+/// Choose one of `variants`, which must not be empty. Only fails in
+/// `Exact` mode, when the target isn't offered.
+fn pick_variant<'a>(
+    room: &str,
+    variants: &'a [Variant],
+    target_resolution: u32,
+    target_framerate: u32,
+    mode: QualityMode,
+) -> Result<&'a Variant> {
+    match mode {
+        QualityMode::AtOrBelow => Ok(pick_at_or_below(
+            variants,
+            target_resolution,
+            target_framerate,
+        )),
+        QualityMode::Target => Ok(pick_closest(variants, target_resolution, target_framerate)),
+        QualityMode::Exact => {
+            pick_exact(variants, target_resolution, target_framerate).ok_or_else(|| {
+                Error::QualityUnavailable(
+                    room.to_string(),
+                    format!("{}p{}", target_resolution, target_framerate),
+                    describe_variants(variants),
+                )
+            })
+        }
     }
+}
+
+/// The variant at exactly the target, with the highest bandwidth if several
+fn pick_exact(
+    variants: &[Variant],
+    target_resolution: u32,
+    target_framerate: u32,
+) -> Option<&Variant> {
+    variants
+        .iter()
+        .filter(|v| v.resolution == target_resolution && v.framerate == target_framerate)
+        .max_by_key(|v| v.bandwidth)
+}
+
+/// Offered qualities, best first, e.g. `1080p60, 720p30`
+fn describe_variants(variants: &[Variant]) -> String {
+    let mut qualities: Vec<(u32, u32)> = variants
+        .iter()
+        .map(|v| (v.resolution, v.framerate))
+        .collect();
+    qualities.sort_by(|a, b| b.cmp(a));
+    qualities.dedup();
+    qualities
+        .iter()
+        .map(|(resolution, framerate)| format!("{}p{}", resolution, framerate))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn pick_at_or_below(
+    variants: &[Variant],
+    target_resolution: u32,
+    target_framerate: u32,
+) -> &Variant {
+    let mut variants: Vec<&Variant> = variants.iter().collect();
 
     // Sort by resolution (descending), then framerate (descending), then bandwidth (descending)
     variants.sort_by(|a, b| {
@@ -186,7 +601,7 @@ async fn select_variant(
     });
 
     // Find best match: exact resolution and framerate, or highest below target
-    let selected = variants
+    variants
         .iter()
         .find(|v| v.resolution == target_resolution && v.framerate == target_framerate)
         .or_else(|| {
@@ -194,13 +609,102 @@ async fn select_variant(
                 .iter()
                 .find(|v| v.resolution <= target_resolution && v.framerate <= target_framerate)
         })
-        .unwrap_or(&variants[0]);
+        .copied()
+        .unwrap_or(variants[0])
+}
+
+/// Enforce the `min_resolution` floor (0 = none) on the `chosen` variant. A
+/// chosen variant below the floor gives way to the lowest one meeting it; when
+/// none does, the room is either skipped or recorded at its best with a warning.
+fn apply_min_resolution<'a>(
+    room: &str,
+    variants: &'a [Variant],
+    chosen: &'a Variant,
+    min_resolution: u32,
+    skip_below_min: bool,
+) -> Result<&'a Variant> {
+    if chosen.resolution >= min_resolution {
+        return Ok(chosen);
+    }
+
+    let at_floor = variants
+        .iter()
+        .filter(|v| v.resolution >= min_resolution)
+        .min_by_key(|v| (v.resolution, std::cmp::Reverse((v.framerate, v.bandwidth))));
+    if let Some(variant) = at_floor {
+        return Ok(variant);
+    }
+
+    let best = variants
+        .iter()
+        .max_by_key(|v| (v.resolution, v.framerate, v.bandwidth))
+        .unwrap_or(chosen);
+    if skip_below_min {
+        return Err(Error::BelowMinResolution(
+            room.to_string(),
+            best.resolution,
+            min_resolution,
+        ));
+    }
+
+    tracing::warn!(
+        "⚠️  {} only offers up to {}p, below the minimum of {}p - recording it anyway",
+        room,
+        best.resolution,
+        min_resolution
+    );
+    Ok(best)
+}
+
+/// The best variant that is cheaper than `current`: lower advertised
+/// bandwidth when both are known, else lower resolution/framerate
+fn pick_lower<'a>(variants: &'a [Variant], current: &StreamInfo) -> Option<&'a Variant> {
+    variants
+        .iter()
+        .filter(|v| {
+            if v.bandwidth > 0 && current.bandwidth > 0 {
+                v.bandwidth < current.bandwidth
+            } else {
+                (v.resolution, v.framerate) < (current.resolution, current.framerate)
+            }
+        })
+        .max_by_key(|v| (v.resolution, v.framerate, v.bandwidth))
+}
+
+/// Minimum weighted distance to the target. Ties go to the variant at or
+/// above the target, then to the higher bandwidth.
+fn pick_closest(variants: &[Variant], target_resolution: u32, target_framerate: u32) -> &Variant {
+    variants
+        .iter()
+        .min_by_key(|v| {
+            let distance = u64::from(v.resolution.abs_diff(target_resolution)) * RESOLUTION_WEIGHT
+                + u64::from(v.framerate.abs_diff(target_framerate));
+            let below = v.resolution < target_resolution || v.framerate < target_framerate;
+            (distance, below, std::cmp::Reverse(v.bandwidth))
+        })
+        .expect("variants must not be empty")
+}
+
+/// Framerate of a variant, preferring the standard FRAME-RATE attribute
+fn variant_framerate(variant: &m3u8_rs::VariantStream) -> u32 {
+    if let Some(frame_rate) = variant.frame_rate.filter(|fps| *fps > 0.0) {
+        return frame_rate.round() as u32;
+    }
 
-    Ok((
-        selected.url.clone(),
-        selected.resolution,
-        selected.framerate,
-    ))
+    // Fallback heuristic: Chaturbate uses "FPS:60.0" in the NAME field for
+    // 60fps streams and omits it otherwise
+    let is_60fps = variant
+        .other_attributes
+        .as_ref()
+        .and_then(|attrs| attrs.get("NAME"))
+        .map(|name| name.to_string().contains("FPS:60"))
+        .unwrap_or(false);
+
+    if is_60fps {
+        60
+    } else {
+        30
+    }
 }
 
 fn resolve_url(base: &str, path: &str) -> Result<String> {
@@ -234,4 +738,274 @@ mod tests {
         let result = decode_unicode_escapes(input).unwrap();
         assert_eq!(result, r#"test"value""#);
     }
+
+    fn room_page(dossier: &str) -> String {
+        format!(
+            "<script>window.initialRoomDossier = \"{}\";</script>",
+            dossier.replace('"', "\\u0022")
+        )
+    }
+
+    fn page_dossier(html: &str) -> Option<RoomDossier> {
+        parse_dossier(dossier_json(html).unwrap().as_deref()).unwrap()
+    }
+
+    fn hls_source(html: &str) -> HlsSource {
+        extract_hls_source(html, page_dossier(html).as_ref()).unwrap()
+    }
+
+    fn room_details(html: &str) -> RoomDetails {
+        page_dossier(html).map(|d| d.details()).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_extract_hls_source() {
+        let live = room_page(r#"{"hls_source": "https://edge.example/live/playlist.m3u8"}"#);
+        assert_eq!(
+            hls_source(&live),
+            HlsSource::Live("https://edge.example/live/playlist.m3u8".to_string())
+        );
+
+        let null = room_page(r#"{"hls_source": null}"#);
+        assert_eq!(hls_source(&null), HlsSource::Offline);
+
+        let empty = room_page(r#"{"hls_source": ""}"#);
+        assert_eq!(hls_source(&empty), HlsSource::Offline);
+
+        let changed = "<script>var data = {hls: 'playlist.m3u8'};</script>";
+        assert_eq!(hls_source(changed), HlsSource::NoDossier);
+    }
+
+    #[test]
+    fn test_extract_room_details() {
+        let page = room_page(
+            r#"{"hls_source": "https://edge.example/playlist.m3u8", "room_title": " Friday show #fun ", "tags": ["fun", "", 3, "music"]}"#,
+        );
+        assert_eq!(
+            room_details(&page),
+            RoomDetails {
+                title: Some("Friday show #fun".to_string()),
+                tags: vec!["fun".to_string(), "music".to_string()],
+            }
+        );
+
+        // Older dossiers have neither, or unexpected shapes; the stream URL
+        // must still come through
+        let page =
+            room_page(r#"{"hls_source": "https://edge.example/playlist.m3u8", "tags": "fun"}"#);
+        assert_eq!(room_details(&page), RoomDetails::default());
+        assert!(matches!(hls_source(&page), HlsSource::Live(_)));
+
+        let page = room_page(
+            r#"{"hls_source": "https://edge.example/playlist.m3u8", "room_title": null}"#,
+        );
+        assert_eq!(room_details(&page), RoomDetails::default());
+    }
+
+    #[test]
+    fn test_extract_hls_source_quoting_variants() {
+        let live = HlsSource::Live("https://edge.example/playlist.m3u8".to_string());
+        let pages = [
+            // Single quotes, with an escaped quote elsewhere in the JSON
+            r#"window.initialRoomDossier = '{"hls_source": "https://edge.example/playlist.m3u8", "title": "it\'s"}';"#,
+            // Double quotes with backslash-escaped quotes, no semicolon
+            r#"window.initialRoomDossier="{\"hls_source\": \"https://edge.example/playlist.m3u8\"}""#,
+            // JSON.parse wrapper
+            r#"window.initialRoomDossier = JSON.parse("{\u0022hls_source\u0022: \u0022https://edge.example/playlist.m3u8\u0022}");"#,
+            r#"window.initialRoomDossier = JSON.parse('{"hls_source": "https://edge.example/playlist.m3u8"}');"#,
+        ];
+
+        for page in pages {
+            assert_eq!(hls_source(page), live, "{}", page);
+        }
+    }
+
+    #[test]
+    fn test_extract_hls_source_fallback_scan() {
+        let page = r#"<script>window.__room = {"hls_source": "https://edge.example/playlist.m3u8"};</script>"#;
+        assert_eq!(
+            hls_source(page),
+            HlsSource::Live("https://edge.example/playlist.m3u8".to_string())
+        );
+
+        let escaped = r#"<script>store.init("{\u0022hls_source\u0022: null}")</script>"#;
+        assert_eq!(hls_source(escaped), HlsSource::Offline);
+    }
+
+    fn variant(resolution: u32, framerate: u32) -> Variant {
+        Variant {
+            url: format!("{}p{}.m3u8", resolution, framerate),
+            resolution,
+            framerate,
+            bandwidth: u64::from(resolution) * u64::from(framerate),
+        }
+    }
+
+    #[test]
+    fn test_redact_url() {
+        assert_eq!(
+            redact_url(
+                "https://edge.example/live/chunklist_720p.m3u8?token=abc&expires=1700000000"
+            ),
+            "https://edge.example/live/chunklist_720p.m3u8?token=<redacted>&expires=<redacted>"
+        );
+        assert_eq!(
+            redact_url("https://edge.example/a.m3u8?secret#frag"),
+            "https://edge.example/a.m3u8?<redacted>"
+        );
+        assert_eq!(
+            redact_url("https://edge.example/a.m3u8"),
+            "https://edge.example/a.m3u8"
+        );
+        assert_eq!(
+            redact_url("https://edge.example/live-hls/amlst:room-sd-0a1b2c3d4e5f6a7b8c9d_trns_h264/chunklist.m3u8"),
+            "https://edge.example/live-hls/<redacted>/chunklist.m3u8"
+        );
+        assert_eq!(redact_url("https://edge.example"), "https://edge.example");
+    }
+
+    fn pick(offered: &[(u32, u32)], target: (u32, u32), mode: QualityMode) -> (u32, u32) {
+        let variants: Vec<Variant> = offered.iter().map(|&(r, f)| variant(r, f)).collect();
+        let selected = pick_variant("room", &variants, target.0, target.1, mode).unwrap();
+        (selected.resolution, selected.framerate)
+    }
+
+    #[test]
+    fn test_pick_exact_variant() {
+        let variants: Vec<Variant> = [(720, 30), (1080, 30), (1080, 60)]
+            .iter()
+            .map(|&(r, f)| variant(r, f))
+            .collect();
+
+        let hit = pick_variant("room", &variants, 1080, 30, QualityMode::Exact).unwrap();
+        assert_eq!((hit.resolution, hit.framerate), (1080, 30));
+
+        let miss = pick_variant("room", &variants, 720, 60, QualityMode::Exact).unwrap_err();
+        assert!(matches!(miss, Error::QualityUnavailable(..)));
+        assert_eq!(
+            miss.to_string(),
+            "room doesn't offer 720p60 (available: 1080p60, 1080p30, 720p30)"
+        );
+    }
+
+    #[test]
+    fn test_pick_closest_variant() {
+        let target = QualityMode::Target;
+
+        // Exact match wins
+        assert_eq!(
+            pick(&[(1080, 30), (1080, 60), (720, 60)], (1080, 60), target),
+            (1080, 60)
+        );
+        // Resolution dominates framerate
+        assert_eq!(
+            pick(&[(1080, 30), (720, 60)], (1080, 60), target),
+            (1080, 30)
+        );
+        // Nearest resolution, even when above the target
+        assert_eq!(
+            pick(&[(1080, 30), (480, 30)], (900, 30), target),
+            (1080, 30)
+        );
+        // Equal distance prefers at-or-above
+        assert_eq!(pick(&[(480, 30), (720, 30)], (600, 30), target), (720, 30));
+        assert_eq!(pick(&[(720, 24), (720, 36)], (720, 30), target), (720, 36));
+        // Nothing near: still the closest
+        assert_eq!(pick(&[(240, 30), (360, 30)], (1080, 60), target), (360, 30));
+    }
+
+    #[test]
+    fn test_pick_at_or_below_variant() {
+        let mode = QualityMode::AtOrBelow;
+
+        assert_eq!(
+            pick(&[(1080, 60), (1080, 30), (720, 30)], (1080, 30), mode),
+            (1080, 30)
+        );
+        assert_eq!(
+            pick(&[(1080, 60), (720, 60), (720, 30)], (900, 30), mode),
+            (720, 30)
+        );
+        // Nothing at or below: the highest variant
+        assert_eq!(pick(&[(1080, 60), (720, 60)], (480, 30), mode), (1080, 60));
+    }
+
+    #[test]
+    fn test_pick_lower_variant() {
+        let variants: Vec<Variant> = [(1080, 60), (1080, 30), (720, 30), (480, 30)]
+            .iter()
+            .map(|&(r, f)| variant(r, f))
+            .collect();
+        let lower = |r: u32, f: u32| {
+            pick_lower(&variants, &variant(r, f).stream_info("room"))
+                .map(|v| (v.resolution, v.framerate))
+        };
+
+        assert_eq!(lower(1080, 60), Some((1080, 30)));
+        assert_eq!(lower(1080, 30), Some((720, 30)));
+        assert_eq!(lower(480, 30), None);
+    }
+
+    #[test]
+    fn test_min_resolution_floor() {
+        let variants: Vec<Variant> = [(720, 30), (480, 30), (240, 30)]
+            .iter()
+            .map(|&(r, f)| variant(r, f))
+            .collect();
+        let floor = |chosen: usize, min: u32, skip: bool| {
+            apply_min_resolution("room", &variants, &variants[chosen], min, skip)
+                .map(|v| v.resolution)
+        };
+
+        assert_eq!(floor(1, 0, true).unwrap(), 480);
+        assert_eq!(floor(1, 480, true).unwrap(), 480);
+        // Target below the floor: the lowest variant that meets it
+        assert_eq!(floor(2, 360, true).unwrap(), 480);
+    }
+
+    #[test]
+    fn test_min_resolution_skip_and_warn() {
+        let variants: Vec<Variant> = [(360, 30), (240, 30)]
+            .iter()
+            .map(|&(r, f)| variant(r, f))
+            .collect();
+
+        let skipped = apply_min_resolution("room", &variants, &variants[1], 720, true);
+        assert!(matches!(
+            skipped,
+            Err(Error::BelowMinResolution(_, 360, 720))
+        ));
+
+        let recorded = apply_min_resolution("room", &variants, &variants[1], 720, false).unwrap();
+        assert_eq!(recorded.resolution, 360);
+    }
+
+    fn parse_framerates(master: &str) -> Vec<u32> {
+        let playlist = m3u8_rs::parse_master_playlist_res(master.as_bytes()).unwrap();
+        playlist.variants.iter().map(variant_framerate).collect()
+    }
+
+    #[test]
+    fn test_variant_framerate_attribute() {
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,FRAME-RATE=48.000\n\
+            1080p48.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1280x720,FRAME-RATE=23.976\n\
+            720p24.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=6000000,RESOLUTION=1920x1080,FRAME-RATE=60,NAME=\"FPS:30.0\"\n\
+            1080p60.m3u8\n";
+
+        assert_eq!(parse_framerates(master), vec![48, 24, 60]);
+    }
+
+    #[test]
+    fn test_variant_framerate_name_fallback() {
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=6000000,RESOLUTION=1920x1080,NAME=\"FPS:60.0\"\n\
+            1080p60.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080\n\
+            1080p.m3u8\n";
+
+        assert_eq!(parse_framerates(master), vec![60, 30]);
+    }
 }
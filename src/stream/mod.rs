@@ -1,9 +1,22 @@
 mod discovery;
 mod monitor;
+mod observer;
+mod playlist;
 mod recorder;
 mod segment;
+#[cfg(test)]
+mod test_util;
+mod writer;
 
-pub use discovery::{get_stream_info, StreamInfo};
-pub use monitor::RoomMonitor;
-pub use recorder::{record_stream, RecordingStats};
+pub use discovery::{
+    get_stream_info, is_room_online, redact_url, select_stream, url_stream_info, LiveRoom,
+    StreamInfo, StreamSource, UrlSource,
+};
+pub use monitor::{RoomMonitor, RoomStatus, RoomStatusHandle};
+pub use observer::MonitorObserver;
+pub use playlist::{build_vod_playlist, PlaylistEntry};
+pub use recorder::{
+    live_progress, record_rooms, record_sources, record_stream, record_stream_progress,
+    LiveProgress, RecordingProgress, RecordingStats,
+};
 pub use segment::SegmentTracker;
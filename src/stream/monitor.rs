@@ -1,18 +1,42 @@
-use std::collections::HashMap;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 use crate::api::ChaturbateClient;
-use crate::config::{MonitorConfig, RecordingConfig};
+use crate::api::{Severity, WebhookNotifier};
+use crate::config::{compile_title_filter, MonitorConfig, RecordingConfig, WebhookEvent};
 use crate::error::{Error, Result};
+use crate::fs::check_output_dir_within;
 use crate::output::console;
-use crate::stream::discovery::get_stream_info;
-use crate::stream::recorder::{record_stream, RecordingStats};
+use crate::output::events::{self, Event, EventLog};
+use crate::output::stats::{
+    format_duration, print_recording_stats, print_room_summary, OutputFormat, RoomTotals,
+};
+use crate::stream::discovery::{is_room_online, select_stream, LiveRoom};
+use crate::stream::observer::MonitorObserver;
+use crate::stream::recorder::{live_progress, record_stream, RecordingStats};
+use ::console::{style, StyledObject};
 
-#[derive(Debug, Clone, PartialEq)]
+/// How often once mode checks whether its recordings have finished
+const ONCE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the output directory check may take before a room is skipped
+const OUTPUT_DIR_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Check intervals without a new cycle before heartbeats report the monitor
+/// as stalled instead of alive
+const STALL_AFTER_INTERVALS: u32 = 3;
+
+/// Serializes as the same names as [`RoomStatus::as_str`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RoomStatus {
     Unknown,
     Offline,
@@ -21,6 +45,33 @@ pub enum RoomStatus {
     CookieDead,
 }
 
+impl RoomStatus {
+    /// Stable name used in the event log
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomStatus::Unknown => "unknown",
+            RoomStatus::Offline => "offline",
+            RoomStatus::Private => "private",
+            RoomStatus::Recording => "recording",
+            RoomStatus::CookieDead => "cookie_dead",
+        }
+    }
+}
+
+/// Read-only view of a monitor's room statuses that stays valid while
+/// [`RoomMonitor::run`] holds the monitor. Cheap to clone and share.
+#[derive(Clone)]
+pub struct RoomStatusHandle {
+    room_status: Arc<RwLock<HashMap<String, RoomStatus>>>,
+}
+
+impl RoomStatusHandle {
+    /// Current status of every monitored room
+    pub async fn snapshot(&self) -> HashMap<String, RoomStatus> {
+        self.room_status.read().await.clone()
+    }
+}
+
 struct ActiveRecording {
     handle: JoinHandle<Result<RecordingStats>>,
     cancel_token: CancellationToken,
@@ -34,6 +85,9 @@ struct RoomCheckState {
     consecutive_same_error: u32,
     /// Next allowed check time (for backoff)
     next_check_at: Option<Instant>,
+    /// Recordings in a row that didn't start because the output file
+    /// exists (`on_existing_file = "skip"`); survives `reset`
+    skipped_existing: u32,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -42,15 +96,39 @@ enum RoomErrorKind {
     Private,
     ServerError,
     Cloudflare,
+    Captcha,
+    /// Online, but nothing offered at `min_resolution`
+    BelowMinResolution,
+    /// Online, but not at the exact quality `quality_mode = "exact"` wants
+    QualityUnavailable,
+    /// Online, but the title doesn't match `record_if_title_matches`
+    NotOfInterest,
     Other,
 }
 
+impl RoomErrorKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RoomErrorKind::Offline => "offline",
+            RoomErrorKind::Private => "private",
+            RoomErrorKind::ServerError => "server error",
+            RoomErrorKind::Cloudflare => "cloudflare",
+            RoomErrorKind::Captcha => "captcha",
+            RoomErrorKind::BelowMinResolution => "below min resolution",
+            RoomErrorKind::QualityUnavailable => "quality not offered",
+            RoomErrorKind::NotOfInterest => "title not matched",
+            RoomErrorKind::Other => "error",
+        }
+    }
+}
+
 impl RoomCheckState {
     fn new() -> Self {
         Self {
             last_error_kind: None,
             consecutive_same_error: 0,
             next_check_at: None,
+            skipped_existing: 0,
         }
     }
 
@@ -73,8 +151,35 @@ impl RoomCheckState {
         }
     }
 
-    /// Record a success — resets all backoff/dedup state
-    fn record_success(&mut self) {
+    /// Record a success — resets all backoff/dedup state and schedules the
+    /// next regular check after the room's interval
+    fn record_success(&mut self, interval: Duration) {
+        self.reset();
+        self.next_check_at = Some(Instant::now() + interval);
+    }
+
+    /// A recording didn't start because its output file exists. Backs off
+    /// like a repeated error; returns whether it's the first in a row (should
+    /// be logged).
+    fn skip_existing(&mut self, base_interval: Duration) -> bool {
+        self.skipped_existing += 1;
+        let multiplier = 2u32.pow((self.skipped_existing - 1).min(6));
+        self.next_check_at = Some(Instant::now() + base_interval * multiplier);
+        self.skipped_existing == 1
+    }
+
+    /// Don't check the room again for `cooldown`, e.g. after a recording
+    /// ended; the next result is logged as new
+    fn cool_down(&mut self, cooldown: Duration) {
+        self.reset();
+        self.skipped_existing = 0;
+        if !cooldown.is_zero() {
+            self.next_check_at = Some(Instant::now() + cooldown);
+        }
+    }
+
+    /// Clear all backoff/dedup state so the room is checked next cycle
+    fn reset(&mut self) {
         self.last_error_kind = None;
         self.consecutive_same_error = 0;
         self.next_check_at = None;
@@ -90,11 +195,95 @@ impl RoomCheckState {
 
 pub struct RoomMonitor {
     client: Arc<ChaturbateClient>,
+    /// Clients for rooms with their own cookies; others use `client`
+    room_clients: HashMap<String, Arc<ChaturbateClient>>,
     rooms: Vec<String>,
     check_interval: Duration,
+    /// Per-room overrides of `check_interval`
+    room_intervals: HashMap<String, Duration>,
+    /// Per-room priorities; rooms not listed have priority 0
+    room_priorities: HashMap<String, i32>,
+    /// Maximum simultaneous recordings (0 = unlimited)
+    max_concurrent_recordings: usize,
+    preempt_lower_priority: bool,
+    /// Start recordings only while this many rooms are online (0 = always)
+    min_online_to_record: usize,
+    /// Only rooms whose title or a tag matches are recorded
+    title_filter: Option<Regex>,
     recording_config: RecordingConfig,
     room_status: Arc<RwLock<HashMap<String, RoomStatus>>>,
-    webhook_url: Option<String>,
+    webhook: WebhookNotifier,
+    exit_on_error: bool,
+    once: bool,
+    /// Stop the whole monitor after this long (zero = unlimited)
+    max_session_duration: Duration,
+    /// How long shutdown waits for recordings to finish (zero = unlimited)
+    shutdown_timeout: Duration,
+    /// Rooms aren't checked for this long after a recording ends
+    post_recording_cooldown: Duration,
+    status_table: bool,
+    notify_on_start: bool,
+    notify_on_stop: bool,
+    /// Time between heartbeat webhooks (zero = none)
+    heartbeat_interval: Duration,
+    /// Sends heartbeats in the background while `run` is active
+    heartbeat_task: Option<JoinHandle<()>>,
+    /// When `run` started, for the heartbeat's uptime
+    started_at: Instant,
+    /// When `run` last started a cycle, so heartbeats can tell a wedged loop
+    last_cycle: Arc<std::sync::Mutex<Instant>>,
+    cookie_warn_threshold_percent: u32,
+    cookie_dead_threshold_percent: u32,
+    cookie_dead_min_rooms: u32,
+    reload_rx: Option<mpsc::UnboundedReceiver<MonitorConfig>>,
+    event_log: Option<EventLog>,
+    /// Finished recordings per room over this run
+    room_totals: HashMap<String, RoomTotals>,
+    /// Most recordings running at the same time over this run
+    peak_recordings: usize,
+    observer: Option<Arc<dyn MonitorObserver>>,
+}
+
+/// Room list ordered for checking, plus per-room overrides
+struct RoomSettings {
+    rooms: Vec<String>,
+    intervals: HashMap<String, Duration>,
+    priorities: HashMap<String, i32>,
+    cookies: HashMap<String, String>,
+}
+
+impl RoomSettings {
+    fn new(mut rooms: Vec<String>, monitor_config: &MonitorConfig) -> Self {
+        let mut intervals = HashMap::new();
+        let mut priorities = HashMap::new();
+        let mut cookies = HashMap::new();
+
+        for room in &rooms {
+            let Some(overrides) = monitor_config.room_overrides(room) else {
+                continue;
+            };
+            if let Some(secs) = overrides.check_interval_seconds {
+                intervals.insert(room.clone(), Duration::from_secs(secs));
+            }
+            if let Some(priority) = overrides.priority {
+                priorities.insert(room.clone(), priority);
+            }
+            if let Some(ref room_cookies) = overrides.cookies {
+                cookies.insert(room.clone(), room_cookies.clone());
+            }
+        }
+
+        // Check higher-priority rooms first so they take free slots first;
+        // the sort is stable, so equal priorities keep their configured order
+        rooms.sort_by_key(|room| std::cmp::Reverse(priorities.get(room).copied().unwrap_or(0)));
+
+        Self {
+            rooms,
+            intervals,
+            priorities,
+            cookies,
+        }
+    }
 }
 
 impl RoomMonitor {
@@ -109,60 +298,213 @@ impl RoomMonitor {
             initial_status.insert(room.clone(), RoomStatus::Unknown);
         }
 
+        let RoomSettings {
+            rooms,
+            intervals: room_intervals,
+            priorities: room_priorities,
+            cookies,
+        } = RoomSettings::new(rooms, monitor_config);
+        let room_clients = build_room_clients(&client, &cookies);
+        let webhook = WebhookNotifier::new(monitor_config, client.webhook_client());
+
         Self {
             client: Arc::new(client),
+            room_clients,
             rooms,
             check_interval: Duration::from_secs(monitor_config.check_interval_seconds),
+            room_intervals,
+            room_priorities,
+            max_concurrent_recordings: monitor_config.max_concurrent_recordings as usize,
+            preempt_lower_priority: monitor_config.preempt_lower_priority,
+            min_online_to_record: monitor_config.min_online_to_record as usize,
+            title_filter: title_filter(monitor_config),
             recording_config,
             room_status: Arc::new(RwLock::new(initial_status)),
-            webhook_url: monitor_config.webhook_url.clone(),
+            webhook,
+            exit_on_error: monitor_config.exit_on_error,
+            once: monitor_config.once,
+            max_session_duration: Duration::from_secs(
+                monitor_config.max_session_duration_minutes * 60,
+            ),
+            shutdown_timeout: Duration::from_secs(monitor_config.shutdown_timeout_seconds),
+            post_recording_cooldown: Duration::from_secs(
+                monitor_config.post_recording_cooldown_seconds,
+            ),
+            status_table: monitor_config.status_table,
+            notify_on_start: monitor_config.notify_on_start,
+            notify_on_stop: monitor_config.notify_on_stop,
+            heartbeat_interval: Duration::from_secs(
+                monitor_config.heartbeat_webhook_interval_seconds,
+            ),
+            heartbeat_task: None,
+            started_at: Instant::now(),
+            last_cycle: Arc::new(std::sync::Mutex::new(Instant::now())),
+            cookie_warn_threshold_percent: monitor_config.cookie_warn_threshold_percent,
+            cookie_dead_threshold_percent: monitor_config.cookie_dead_threshold_percent,
+            cookie_dead_min_rooms: monitor_config.cookie_dead_min_rooms,
+            reload_rx: None,
+            event_log: open_event_log(monitor_config),
+            room_totals: HashMap::new(),
+            peak_recordings: 0,
+            observer: None,
         }
     }
 
-    pub async fn run(&self, cancel_token: CancellationToken) -> Result<()> {
+    /// Apply monitor settings received on `reload_rx` while running (e.g.
+    /// after SIGHUP). The config's `rooms` replaces the monitored room list.
+    pub fn with_reload(mut self, reload_rx: mpsc::UnboundedReceiver<MonitorConfig>) -> Self {
+        self.reload_rx = Some(reload_rx);
+        self
+    }
+
+    /// Report status changes, recordings, and cookie health to `observer`
+    pub fn with_observer(mut self, observer: Arc<dyn MonitorObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub async fn run(&mut self, cancel_token: CancellationToken) -> Result<()> {
         let mut active_recordings: HashMap<String, ActiveRecording> = HashMap::new();
         let mut check_states: HashMap<String, RoomCheckState> = HashMap::new();
         let mut cookie_dead = false;
         let mut cookie_dead_alerted = false;
+        let mut cookie_warned = false;
+        // cookie_refresh_command runs in the background so a slow command
+        // doesn't hold up checks or shutdown
+        let mut cookie_refresh: Option<JoinHandle<Result<bool>>> = None;
+        // Finished recordings over the session, for the exit status
+        let mut successful_recordings: u32 = 0;
+        let mut failed_recordings: u32 = 0;
+        let mut first_pass = true;
+        // Online rooms already told they're waiting for a free slot
+        let mut waiting_for_slot: HashSet<String> = HashSet::new();
+        // Rooms whose latest check found them online, and those held back
+        // until min_online_to_record is met
+        let mut online_rooms: HashSet<String> = HashSet::new();
+        let mut held_back: HashSet<String> = HashSet::new();
+        let mut armed = self.min_online_to_record == 0;
+        let session_deadline = (!self.max_session_duration.is_zero())
+            .then(|| Instant::now() + self.max_session_duration);
 
         for room in &self.rooms {
             check_states.insert(room.clone(), RoomCheckState::new());
         }
 
-        console::print_info(&format!(
-            "Monitor mode started for {} room(s). Checking every {}s.",
-            self.rooms.len(),
-            self.check_interval.as_secs()
-        ));
+        if self.once {
+            console::print_info(&format!(
+                "Single-pass check of {} room(s).",
+                self.rooms.len()
+            ));
+        } else {
+            console::print_info(&format!(
+                "Monitor mode started for {} room(s). Checking every {}s.",
+                self.rooms.len(),
+                self.check_interval.as_secs()
+            ));
+        }
 
-        if self.webhook_url.is_some() {
-            console::print_info("Webhook notifications enabled.");
+        if self.max_concurrent_recordings > 0 {
+            console::print_info(&format!(
+                "Recording at most {} room(s) at once{}.",
+                self.max_concurrent_recordings,
+                if self.preempt_lower_priority {
+                    ", preempting lower-priority rooms"
+                } else {
+                    ""
+                }
+            ));
         }
 
+        if self.min_online_to_record > 0 {
+            console::print_info(&format!(
+                "Recording starts once {} room(s) are online at the same time.",
+                self.min_online_to_record
+            ));
+        }
+
+        if self.webhook.endpoint_count() > 0 {
+            console::print_info(&format!(
+                "Webhook notifications enabled ({} endpoint(s)).",
+                self.webhook.endpoint_count()
+            ));
+        }
+
+        self.started_at = Instant::now();
+        self.restart_heartbeat();
+
         loop {
-            if cancel_token.is_cancelled() {
-                console::print_info("Shutting down monitor...");
+            *self.last_cycle.lock().unwrap() = Instant::now();
+            let session_expired = session_deadline
+                .map(|deadline| Instant::now() >= deadline)
+                .unwrap_or(false);
+
+            if cancel_token.is_cancelled() || session_expired {
+                if cancel_token.is_cancelled() {
+                    console::print_info("Shutting down monitor...");
+                } else {
+                    console::print_info(&format!(
+                        "Maximum session duration of {} minute(s) reached, shutting down monitor...",
+                        self.max_session_duration.as_secs() / 60
+                    ));
+                }
 
                 for (room, recording) in active_recordings.iter() {
                     console::print_info(&format!("Stopping recording for {}...", room));
                     recording.cancel_token.cancel();
                 }
 
-                for (room, recording) in active_recordings.drain() {
-                    match recording.handle.await {
+                // One deadline for all recordings, so shutdown is bounded
+                // no matter how many are wedged
+                let deadline = (!self.shutdown_timeout.is_zero())
+                    .then(|| Instant::now() + self.shutdown_timeout);
+
+                for (room, mut recording) in active_recordings.drain() {
+                    let Some(result) = join_recording(&mut recording.handle, deadline).await else {
+                        failed_recordings += 1;
+                        self.observe(|o| o.on_recording_error(&room, &Error::Interrupted));
+                        // Completed segments stay on disk in the `.part` file
+                        console::print_warning(&format!(
+                            "{}: Recording did not finish within {}s, aborted",
+                            room,
+                            self.shutdown_timeout.as_secs()
+                        ));
+                        self.notify_recording_stopped(&room, "aborted at shutdown")
+                            .await;
+                        continue;
+                    };
+
+                    match result {
                         Ok(Ok(stats)) => {
-                            console::print_success(&format!(
-                                "{}: {} segments, {:.2} MB recorded",
-                                room,
-                                stats.segments_downloaded,
-                                stats.bytes_written as f64 / 1024.0 / 1024.0
-                            ));
+                            successful_recordings += 1;
+                            self.room_totals
+                                .entry(room.clone())
+                                .or_default()
+                                .add(&stats);
+                            self.observe(|o| o.on_recording_finish(&room, &stats));
+                            print_finished(&room, &stats, || {
+                                console::print_success(&format!(
+                                    "{}: {}, {:.2} MB recorded",
+                                    room,
+                                    segment_counts(&stats),
+                                    stats.bytes_written as f64 / 1024.0 / 1024.0
+                                ))
+                            });
+                            self.notify_recording_stopped(&room, &stats_summary(&stats))
+                                .await;
                         }
                         Ok(Err(e)) => {
+                            failed_recordings += 1;
+                            self.observe(|o| o.on_recording_error(&room, &e));
                             console::print_error(&format!("{}: Recording error: {}", room, e));
+                            self.notify_recording_stopped(&room, &format!("error: {}", e))
+                                .await;
                         }
                         Err(e) => {
+                            failed_recordings += 1;
+                            self.observe(|o| o.on_recording_error(&room, &e));
                             console::print_error(&format!("{}: Task error: {}", room, e));
+                            self.notify_recording_stopped(&room, &format!("task error: {}", e))
+                                .await;
                         }
                     }
                 }
@@ -173,28 +515,170 @@ impl RoomMonitor {
             // --- Check all rooms and collect results ---
             let mut private_count: u32 = 0;
             let mut cloudflare_count: u32 = 0;
+            let mut captcha_count: u32 = 0;
             let mut checked_count: u32 = 0;
 
-            for room in &self.rooms {
+            // In once mode only the first pass checks rooms; later passes just
+            // wait for the recordings it started
+            let rooms_to_check: &[String] = if self.once && !first_pass {
+                &[]
+            } else {
+                &self.rooms
+            };
+            first_pass = false;
+            let cycle_checked = !rooms_to_check.is_empty();
+
+            for room in rooms_to_check {
                 let is_recording = active_recordings.contains_key(room);
-                let check_state = check_states.entry(room.clone()).or_insert_with(RoomCheckState::new);
+                let check_state = check_states
+                    .entry(room.clone())
+                    .or_insert_with(RoomCheckState::new);
 
                 // Skip rooms in backoff (unless cookie was just fixed)
-                if !cookie_dead && check_state.should_skip() {
+                if !self.once && !cookie_dead && check_state.should_skip() {
                     continue;
                 }
 
-                checked_count += 1;
+                // Rooms with their own cookies say nothing about the shared ones
+                let shared_auth = !self.room_clients.contains_key(room);
+                if shared_auth {
+                    checked_count += 1;
+                }
+                let interval = self.interval_for(room);
+
+                let result = self.check_room(room).await;
+                events::emit(&Event::Check {
+                    room,
+                    result: check_result_name(&result),
+                });
+
+                if result.is_ok() {
+                    online_rooms.insert(room.clone());
+                } else {
+                    online_rooms.remove(room);
+                    held_back.remove(room);
+                }
+
+                match result {
+                    Ok(ref live) if !is_recording && !self.is_of_interest(live) => {
+                        // Doesn't count toward min_online_to_record either
+                        online_rooms.remove(room);
+                        held_back.remove(room);
+                        if check_state.record_error(RoomErrorKind::NotOfInterest, interval) {
+                            console::print_info(&format!(
+                                "Skipping {} - title {:?} doesn't match the title filter",
+                                room,
+                                live.title.as_deref().unwrap_or("")
+                            ));
+                        }
+                    }
+                    Ok(_) if !is_recording && self.holds_back(armed) => {
+                        // Started once enough rooms are online
+                        check_state.record_success(interval);
+                        held_back.insert(room.clone());
+                    }
+                    Ok(live) if !is_recording => {
+                        held_back.remove(room);
+                        let mut victim = None;
+                        if !self.has_free_slot(&active_recordings) {
+                            let priority = self.priority_of(room);
+                            victim = if self.preempt_lower_priority {
+                                let candidates = active_recordings
+                                    .iter()
+                                    .filter(|(_, r)| !r.cancel_token.is_cancelled())
+                                    .map(|(name, _)| (name.as_str(), self.priority_of(name)));
+                                pick_preemption_victim(candidates, priority).map(str::to_string)
+                            } else {
+                                None
+                            };
+
+                            if victim.is_none() {
+                                check_state.record_success(interval);
+                                if waiting_for_slot.insert(room.clone()) {
+                                    console::print_info(&format!(
+                                        "{} is online but all {} recording slot(s) are in use (priority {}) - waiting",
+                                        room, self.max_concurrent_recordings, priority
+                                    ));
+                                }
+                                continue;
+                            }
+                        }
+                        waiting_for_slot.remove(room);
+
+                        // The disk may have gone away since startup (unmounted,
+                        // permissions changed); say so rather than fail the recording
+                        let output_dir = Path::new(&self.recording_config.output_directory);
+                        if let Err(e) =
+                            check_output_dir_within(output_dir, OUTPUT_DIR_CHECK_TIMEOUT).await
+                        {
+                            if check_state.record_error(RoomErrorKind::Other, interval) {
+                                console::print_error(&format!(
+                                    "{} is online but can't be recorded: {}",
+                                    room, e
+                                ));
+                            }
+                            continue;
+                        }
+
+                        // Only now fetch the master playlist and pick a variant
+                        let stream_info = match self.select_stream(&live).await {
+                            Ok(stream_info) => stream_info,
+                            Err(e @ Error::BelowMinResolution(..)) => {
+                                events::emit(&Event::Check {
+                                    room,
+                                    result: "below_min_resolution",
+                                });
+                                // Not worth recording yet; back off like an offline room
+                                if check_state
+                                    .record_error(RoomErrorKind::BelowMinResolution, interval)
+                                {
+                                    console::print_info(&format!("Skipping {} - {}", room, e));
+                                }
+                                continue;
+                            }
+                            Err(e @ Error::QualityUnavailable(..)) => {
+                                if check_state
+                                    .record_error(RoomErrorKind::QualityUnavailable, interval)
+                                {
+                                    console::print_info(&format!("Skipping {} - {}", room, e));
+                                }
+                                continue;
+                            }
+                            Err(e) => {
+                                if check_state.record_error(RoomErrorKind::Other, interval) {
+                                    console::print_error(&format!("{}: {}", room, e));
+                                }
+                                continue;
+                            }
+                        };
+
+                        if let Some(victim) = victim {
+                            // The stopped recording is collected by the
+                            // cleanup below like any other finished one
+                            console::print_warning(&format!(
+                                "{} (priority {}) is online; stopping {} (priority {}) to free a recording slot",
+                                room, self.priority_of(room), victim, self.priority_of(&victim)
+                            ));
+                            active_recordings[&victim].cancel_token.cancel();
+                        }
 
-                match self.check_room(room).await {
-                    Ok(stream_info) if !is_recording => {
                         // Room is online — start recording
                         console::print_success(&format!(
                             "{} is ONLINE at {}p{}fps - starting recording",
                             room, stream_info.resolution, stream_info.framerate
                         ));
 
-                        check_state.record_success();
+                        check_state.record_success(interval);
+
+                        if self.notify_on_start {
+                            self.webhook
+                                .send(WebhookEvent::RecordingStart, &start_message(&stream_info))
+                                .await;
+                        }
+
+                        self.observe(|o| o.on_recording_start(room, &stream_info));
+                        // Later checks of this room look at the live stream afresh
+                        self.client_for(room).invalidate_master_playlist(room);
 
                         let recording_cancel = CancellationToken::new();
                         let handle = self.spawn_recording(
@@ -210,16 +694,18 @@ impl RoomMonitor {
                                 cancel_token: recording_cancel,
                             },
                         );
+                        self.peak_recordings = self.peak_recordings.max(active_recordings.len());
 
                         self.set_status(room, RoomStatus::Recording).await;
                     }
                     Ok(_) => {
                         // Room online but already recording — nothing to do
-                        check_state.record_success();
+                        check_state.record_success(interval);
                     }
                     Err(Error::BroadcasterOffline(_)) => {
+                        waiting_for_slot.remove(room);
                         if !is_recording {
-                            let is_new = check_state.record_error(RoomErrorKind::Offline, self.check_interval);
+                            let is_new = check_state.record_error(RoomErrorKind::Offline, interval);
                             if is_new {
                                 console::print_info(&format!("{} is offline", room));
                             }
@@ -227,9 +713,12 @@ impl RoomMonitor {
                         }
                     }
                     Err(Error::PrivateStream) => {
-                        private_count += 1;
+                        if shared_auth {
+                            private_count += 1;
+                        }
+                        waiting_for_slot.remove(room);
                         if !is_recording {
-                            let is_new = check_state.record_error(RoomErrorKind::Private, self.check_interval);
+                            let is_new = check_state.record_error(RoomErrorKind::Private, interval);
                             if is_new {
                                 console::print_info(&format!("{} is private", room));
                             }
@@ -237,24 +726,45 @@ impl RoomMonitor {
                         }
                     }
                     Err(Error::CloudflareBlocked) => {
-                        cloudflare_count += 1;
+                        if shared_auth {
+                            cloudflare_count += 1;
+                        }
                         if !is_recording {
-                            let is_new = check_state.record_error(RoomErrorKind::Cloudflare, self.check_interval);
+                            let is_new =
+                                check_state.record_error(RoomErrorKind::Cloudflare, interval);
                             if is_new {
                                 console::print_error(&format!("{}: Cloudflare blocked", room));
                             }
                         }
                     }
+                    Err(Error::CaptchaRequired) => {
+                        if shared_auth {
+                            captcha_count += 1;
+                        }
+                        if !is_recording {
+                            let is_new = check_state.record_error(RoomErrorKind::Captcha, interval);
+                            if is_new {
+                                console::print_error(&format!(
+                                    "{}: Cloudflare CAPTCHA required",
+                                    room
+                                ));
+                            }
+                        }
+                    }
                     Err(Error::ServerError(status, ref msg)) => {
                         if !is_recording {
-                            let is_new = check_state.record_error(RoomErrorKind::ServerError, self.check_interval);
+                            let is_new =
+                                check_state.record_error(RoomErrorKind::ServerError, interval);
                             if is_new {
-                                console::print_error(&format!("{}: Server error {} - {}", room, status, msg));
+                                console::print_error(&format!(
+                                    "{}: Server error {} - {}",
+                                    room, status, msg
+                                ));
                             }
                         }
                     }
                     Err(e) => {
-                        let is_new = check_state.record_error(RoomErrorKind::Other, self.check_interval);
+                        let is_new = check_state.record_error(RoomErrorKind::Other, interval);
                         if is_new {
                             console::print_error(&format!("{}: {}", room, e));
                         }
@@ -262,12 +772,57 @@ impl RoomMonitor {
                 }
             }
 
+            // --- Minimum online rooms ---
+            // Judged on the whole cycle; crossing the threshold rechecks the
+            // held-back rooms right away instead of after the next interval
+            let mut recheck_now = false;
+            if self.min_online_to_record > 0 && cycle_checked {
+                online_rooms.retain(|room| self.rooms.contains(room));
+                let online = online_rooms.len();
+                let now_armed = online >= self.min_online_to_record;
+
+                if now_armed && !armed {
+                    console::print_success(&format!(
+                        "{}/{} required rooms online - starting recordings",
+                        online, self.min_online_to_record
+                    ));
+                    for room in held_back.drain() {
+                        if let Some(state) = check_states.get_mut(&room) {
+                            state.reset();
+                        }
+                    }
+                    recheck_now = true;
+                    // Once mode only checks on its first pass
+                    first_pass = true;
+                } else if !now_armed && armed {
+                    console::print_info(&format!(
+                        "Only {}/{} required rooms online - not starting new recordings",
+                        online, self.min_online_to_record
+                    ));
+                }
+                armed = now_armed;
+            }
+
             // --- Global cookie death detection ---
-            // If >50% of checked rooms return Private or Cloudflare, cookies are dead
-            let auth_fail_count = private_count + cloudflare_count;
+            // If enough checked rooms return Private or Cloudflare, cookies are
+            // dead (default 50%); a lower ratio raises an early warning (25%)
+            let auth_fail_count = private_count + cloudflare_count + captcha_count;
             let _was_cookie_dead = cookie_dead;
+            let is_dead = exceeds_threshold(
+                auth_fail_count,
+                checked_count,
+                self.cookie_dead_threshold_percent,
+                self.cookie_dead_min_rooms,
+            );
+            let is_warning = self.cookie_warn_threshold_percent > 0
+                && exceeds_threshold(
+                    auth_fail_count,
+                    checked_count,
+                    self.cookie_warn_threshold_percent,
+                    self.cookie_dead_min_rooms,
+                );
 
-            if checked_count > 0 && auth_fail_count > 0 && auth_fail_count * 2 >= checked_count {
+            if is_dead {
                 if !cookie_dead {
                     cookie_dead = true;
                     cookie_dead_alerted = false;
@@ -276,10 +831,17 @@ impl RoomMonitor {
                         "🍪 COOKIE DEATH DETECTED — {}/{} rooms returning private/cloudflare. All checks paused with backoff.",
                         auth_fail_count, checked_count
                     ));
+                    self.observe(|o| o.on_cookie_death(auth_fail_count, checked_count));
+                    events::emit(&Event::CookieDeath {
+                        failing_rooms: auth_fail_count,
+                        checked_rooms: checked_count,
+                    });
 
-                    // Set all non-recording rooms to CookieDead
+                    // Set all non-recording rooms on the shared cookies to CookieDead
                     for room in &self.rooms {
-                        if !active_recordings.contains_key(room) {
+                        if !active_recordings.contains_key(room)
+                            && !self.room_clients.contains_key(room)
+                        {
                             self.set_status(room, RoomStatus::CookieDead).await;
                         }
                     }
@@ -287,21 +849,82 @@ impl RoomMonitor {
 
                 // Send webhook alert (once per cookie death event)
                 if !cookie_dead_alerted {
-                    self.send_webhook("🍪 Cookie died! All rooms returning private/cloudflare. Fix: solve CAPTCHA and update cf_clearance cookie.").await;
+                    cookie_warned = true;
+                    let message =
+                        cookie_death_message(private_count, cloudflare_count, captcha_count);
+                    self.webhook
+                        .send_with_severity(WebhookEvent::Cookie, Severity::Critical, &message)
+                        .await;
                     cookie_dead_alerted = true;
                 }
             } else if cookie_dead && auth_fail_count == 0 && checked_count > 0 {
                 // Cookie is working again!
                 cookie_dead = false;
                 cookie_dead_alerted = false;
+                cookie_warned = false;
 
                 console::print_success("🍪 Cookie recovered! Rooms responding normally again.");
-                self.send_webhook("🍪 Cookie recovered! Recorder is back to normal.").await;
+                self.observe(|o| o.on_cookie_recovery());
+                events::emit(&Event::CookieRecovery);
+                self.webhook
+                    .send_with_severity(
+                        WebhookEvent::Cookie,
+                        Severity::Good,
+                        "🍪 Cookie recovered! Recorder is back to normal.",
+                    )
+                    .await;
 
                 // Reset all backoff states so rooms get checked immediately
                 for state in check_states.values_mut() {
-                    state.record_success();
+                    state.reset();
                 }
+            } else if !cookie_dead && is_warning {
+                // Send the early warning once per degradation episode
+                if !cookie_warned {
+                    cookie_warned = true;
+
+                    console::print_warning(&format!(
+                        "🍪 Cookie may be expiring — {}/{} rooms returning private/cloudflare.",
+                        auth_fail_count, checked_count
+                    ));
+                    self.webhook.send_with_severity(WebhookEvent::Cookie, Severity::Warning, &format!(
+                        "🍪 Cookie may be expiring: {}/{} rooms returning private/cloudflare. Consider refreshing cf_clearance now.",
+                        auth_fail_count, checked_count
+                    )).await;
+                }
+            } else if !cookie_dead && auth_fail_count == 0 && checked_count > 0 {
+                // Fully healthy again; allow a future warning
+                cookie_warned = false;
+            }
+
+            // --- External cookie refresh ---
+            // Any Cloudflare block (or dead cookies) runs the configured
+            // refresh command, at most once per its cooldown
+            if cookie_refresh
+                .as_ref()
+                .is_some_and(|task| task.is_finished())
+            {
+                if let Some(task) = cookie_refresh.take() {
+                    match task.await {
+                        Ok(Ok(true)) => {
+                            console::print_success(
+                                "🍪 Cookies refreshed by cookie_refresh_command; rechecking rooms.",
+                            );
+                            for state in check_states.values_mut() {
+                                state.reset();
+                            }
+                        }
+                        Ok(Ok(false)) => {}
+                        Ok(Err(e)) => console::print_error(&format!("🍪 {}", e)),
+                        Err(e) => {
+                            console::print_error(&format!("🍪 Cookie refresh task failed: {}", e))
+                        }
+                    }
+                }
+            }
+            if cookie_refresh.is_none() && (is_dead || cloudflare_count + captcha_count > 0) {
+                let client = self.client.clone();
+                cookie_refresh = Some(tokio::spawn(async move { client.refresh_cookies().await }));
             }
 
             // --- Clean up finished recordings ---
@@ -314,64 +937,369 @@ impl RoomMonitor {
 
             for room in finished {
                 if let Some(recording) = active_recordings.remove(&room) {
-                    match recording.handle.await {
+                    let result = recording.handle.await;
+                    if let Some(state) = check_states.get_mut(&room) {
+                        match result {
+                            // on_existing_file = "skip": not a failure, try again later
+                            Ok(Err(Error::OutputExists(ref path))) => {
+                                if state.skip_existing(self.interval_for(&room)) {
+                                    console::print_info(&format!(
+                                        "Skipping {} - {} already exists",
+                                        room,
+                                        path.display()
+                                    ));
+                                }
+                            }
+                            _ => state.cool_down(self.post_recording_cooldown),
+                        }
+                    }
+                    match result {
+                        Ok(Err(Error::OutputExists(_))) => {}
+                        Ok(Ok(stats)) if stats.went_private => {
+                            successful_recordings += 1;
+                            self.room_totals
+                                .entry(room.clone())
+                                .or_default()
+                                .add(&stats);
+                            self.observe(|o| o.on_recording_finish(&room, &stats));
+                            print_finished(&room, &stats, || {
+                                console::print_warning(&format!(
+                                    "{}: Show went private - recording stopped after {}, {:.2} MB",
+                                    room,
+                                    segment_counts(&stats),
+                                    stats.bytes_written as f64 / 1024.0 / 1024.0
+                                ))
+                            });
+                            if self.notify_on_stop {
+                                self.webhook
+                                    .send(
+                                        WebhookEvent::RecordingPrivate,
+                                        &format!(
+                                            "🔒 {} went private, recording stopped: {}",
+                                            room,
+                                            stats_summary(&stats)
+                                        ),
+                                    )
+                                    .await;
+                            }
+                            if self.rooms.contains(&room) {
+                                self.set_status(&room, RoomStatus::Private).await;
+                                continue;
+                            }
+                        }
                         Ok(Ok(stats)) => {
-                            console::print_success(&format!(
-                                "{}: Recording finished - {} segments, {:.2} MB",
-                                room,
-                                stats.segments_downloaded,
-                                stats.bytes_written as f64 / 1024.0 / 1024.0
-                            ));
+                            successful_recordings += 1;
+                            self.room_totals
+                                .entry(room.clone())
+                                .or_default()
+                                .add(&stats);
+                            self.observe(|o| o.on_recording_finish(&room, &stats));
+                            print_finished(&room, &stats, || {
+                                console::print_success(&format!(
+                                    "{}: Recording finished - {}, {:.2} MB",
+                                    room,
+                                    segment_counts(&stats),
+                                    stats.bytes_written as f64 / 1024.0 / 1024.0
+                                ))
+                            });
+                            self.notify_recording_stopped(&room, &stats_summary(&stats))
+                                .await;
                         }
                         Ok(Err(e)) => {
+                            failed_recordings += 1;
+                            self.observe(|o| o.on_recording_error(&room, &e));
                             console::print_error(&format!("{}: Recording error: {}", room, e));
+                            self.notify_recording_stopped(&room, &format!("error: {}", e))
+                                .await;
                         }
                         Err(e) => {
+                            failed_recordings += 1;
+                            self.observe(|o| o.on_recording_error(&room, &e));
                             console::print_error(&format!("{}: Task error: {}", room, e));
+                            self.notify_recording_stopped(&room, &format!("task error: {}", e))
+                                .await;
                         }
                     }
-                    self.set_status(&room, RoomStatus::Unknown).await;
+                    if self.rooms.contains(&room) {
+                        self.set_status(&room, RoomStatus::Unknown).await;
+                    } else {
+                        // Dropped from the room list by a reload while recording
+                        self.room_status.write().await.remove(&room);
+                    }
                 }
             }
 
-            // Wait before next check
-            tokio::select! {
-                _ = tokio::time::sleep(self.check_interval) => {}
-                _ = cancel_token.cancelled() => {}
+            if self.status_table && cycle_checked {
+                self.print_status_table(&check_states).await;
             }
+
+            if self.once && active_recordings.is_empty() && !recheck_now {
+                break;
+            }
+
+            // Wait before next check; in once mode just poll for recordings to finish.
+            // Rooms not yet due are skipped via their check state.
+            let wait = if recheck_now {
+                Duration::ZERO
+            } else if self.once {
+                ONCE_POLL_INTERVAL
+            } else {
+                self.tick_interval()
+            };
+            // Don't oversleep the session limit
+            let wait = session_deadline
+                .map(|deadline| wait.min(deadline.saturating_duration_since(Instant::now())))
+                .unwrap_or(wait);
+            let reload = tokio::select! {
+                _ = tokio::time::sleep(wait) => None,
+                _ = cancel_token.cancelled() => None,
+                config = next_reload(&mut self.reload_rx) => Some(config),
+            };
+
+            if let Some(config) = reload {
+                self.apply_reload(config, &mut check_states).await;
+                // A threshold lowered to 0 releases the held-back rooms now
+                if !self.holds_back(armed) {
+                    for room in held_back.drain() {
+                        if let Some(state) = check_states.get_mut(&room) {
+                            state.reset();
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(task) = self.heartbeat_task.take() {
+            task.abort();
+        }
+        // Dropping the command's future kills it
+        if let Some(task) = cookie_refresh.take() {
+            task.abort();
+        }
+        print_room_summary(&self.room_totals, self.peak_recordings);
+
+        // Recordings stopped by Ctrl+C finish with Ok, so an interrupt alone
+        // never turns into a failure here.
+        let total = successful_recordings + failed_recordings;
+        if failed_recordings > 0 && (self.exit_on_error || successful_recordings == 0) {
+            return Err(Error::RecordingsFailed(failed_recordings, total));
         }
 
         Ok(())
     }
 
-    async fn check_room(
-        &self,
-        room: &str,
-    ) -> Result<crate::stream::StreamInfo> {
-        get_stream_info(
-            &self.client,
-            room,
-            self.recording_config.resolution,
-            self.recording_config.framerate,
-        )
-        .await
+    /// Switch to reloaded settings. Running recordings are untouched; a room
+    /// removed from the list keeps recording until its stream ends.
+    async fn apply_reload(
+        &mut self,
+        config: MonitorConfig,
+        check_states: &mut HashMap<String, RoomCheckState>,
+    ) {
+        let added: Vec<String> = config
+            .rooms
+            .iter()
+            .filter(|r| !self.rooms.contains(r))
+            .cloned()
+            .collect();
+        let removed: Vec<String> = self
+            .rooms
+            .iter()
+            .filter(|r| !config.rooms.contains(r))
+            .cloned()
+            .collect();
+
+        for room in &added {
+            check_states.insert(room.clone(), RoomCheckState::new());
+            self.set_status(room, RoomStatus::Unknown).await;
+        }
+
+        {
+            let mut status = self.room_status.write().await;
+            for room in &removed {
+                check_states.remove(room);
+                if status.get(room) != Some(&RoomStatus::Recording) {
+                    status.remove(room);
+                }
+            }
+        }
+
+        let settings = RoomSettings::new(config.rooms.clone(), &config);
+        self.rooms = settings.rooms;
+        self.room_intervals = settings.intervals;
+        self.room_priorities = settings.priorities;
+        self.room_clients = build_room_clients(&self.client, &settings.cookies);
+        self.check_interval = Duration::from_secs(config.check_interval_seconds);
+        self.webhook = WebhookNotifier::new(&config, self.client.webhook_client());
+        self.max_concurrent_recordings = config.max_concurrent_recordings as usize;
+        self.preempt_lower_priority = config.preempt_lower_priority;
+        self.min_online_to_record = config.min_online_to_record as usize;
+        self.title_filter = title_filter(&config);
+        self.exit_on_error = config.exit_on_error;
+        self.shutdown_timeout = Duration::from_secs(config.shutdown_timeout_seconds);
+        self.post_recording_cooldown = Duration::from_secs(config.post_recording_cooldown_seconds);
+        self.status_table = config.status_table;
+        self.notify_on_start = config.notify_on_start;
+        self.notify_on_stop = config.notify_on_stop;
+        self.heartbeat_interval = Duration::from_secs(config.heartbeat_webhook_interval_seconds);
+        self.restart_heartbeat();
+        self.cookie_warn_threshold_percent = config.cookie_warn_threshold_percent;
+        self.cookie_dead_threshold_percent = config.cookie_dead_threshold_percent;
+        self.cookie_dead_min_rooms = config.cookie_dead_min_rooms;
+
+        let session_limit = Duration::from_secs(config.max_session_duration_minutes * 60);
+        let event_log_path = self.event_log.as_ref().map(|log| log.path());
+        if config.once != self.once
+            || session_limit != self.max_session_duration
+            || config.event_log.as_deref().map(Path::new) != event_log_path
+        {
+            console::print_warning(
+                "Changes to once / max_session_duration_minutes / event_log need a restart to take effect",
+            );
+        }
+
+        console::print_success(&format!(
+            "Config reloaded: {} room(s) ({} added, {} removed), checking every {}s, {} webhook endpoint(s)",
+            self.rooms.len(),
+            added.len(),
+            removed.len(),
+            self.check_interval.as_secs(),
+            self.webhook.endpoint_count()
+        ));
+    }
+
+    /// (Re)start the heartbeat task with the current webhooks and interval.
+    /// It runs on its own so a slow endpoint never holds up room checks.
+    fn restart_heartbeat(&mut self) {
+        if let Some(task) = self.heartbeat_task.take() {
+            task.abort();
+        }
+        if self.heartbeat_interval.is_zero() {
+            return;
+        }
+
+        let webhook = self.webhook.clone();
+        let room_status = Arc::clone(&self.room_status);
+        let interval = self.heartbeat_interval;
+        let started_at = self.started_at;
+        let last_cycle = Arc::clone(&self.last_cycle);
+        let stall_after = self.tick_interval() * STALL_AFTER_INTERVALS;
+        self.heartbeat_task = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let last_cycle = *last_cycle.lock().unwrap();
+                if let Some(stalled) = stalled_for(last_cycle, stall_after) {
+                    webhook.send_stalled(stalled).await;
+                    continue;
+                }
+                let recording = room_status
+                    .read()
+                    .await
+                    .values()
+                    .filter(|s| **s == RoomStatus::Recording)
+                    .count();
+                webhook
+                    .send_heartbeat(recording, started_at.elapsed())
+                    .await;
+            }
+        }));
+    }
+
+    fn is_of_interest(&self, live: &LiveRoom) -> bool {
+        self.title_filter
+            .as_ref()
+            .is_none_or(|filter| title_matches(filter, live.title.as_deref(), &live.tags))
+    }
+
+    fn client_for(&self, room: &str) -> &Arc<ChaturbateClient> {
+        self.room_clients.get(room).unwrap_or(&self.client)
+    }
+
+    /// Whether online rooms wait for `min_online_to_record`. Derived each
+    /// cycle so a reload to 0 takes effect without waiting to be re-armed.
+    fn holds_back(&self, armed: bool) -> bool {
+        self.min_online_to_record > 0 && !armed
+    }
+
+    fn priority_of(&self, room: &str) -> i32 {
+        self.room_priorities.get(room).copied().unwrap_or(0)
+    }
+
+    /// Whether another recording may start. Recordings already told to stop
+    /// don't hold a slot.
+    fn has_free_slot(&self, active_recordings: &HashMap<String, ActiveRecording>) -> bool {
+        if self.max_concurrent_recordings == 0 {
+            return true;
+        }
+        let running = active_recordings
+            .values()
+            .filter(|r| !r.cancel_token.is_cancelled())
+            .count();
+        running < self.max_concurrent_recordings
+    }
+
+    /// Base check interval for a room, before any backoff
+    fn interval_for(&self, room: &str) -> Duration {
+        self.room_intervals
+            .get(room)
+            .copied()
+            .unwrap_or(self.check_interval)
+    }
+
+    /// How long the loop sleeps between cycles: the shortest room interval
+    fn tick_interval(&self) -> Duration {
+        self.room_intervals
+            .values()
+            .copied()
+            .fold(self.check_interval, Duration::min)
+    }
+
+    /// Room page only; the master playlist is left for `select_stream`
+    async fn check_room(&self, room: &str) -> Result<LiveRoom> {
+        is_room_online(self.client_for(room), room)
+            .instrument(tracing::info_span!("check", room = %room))
+            .await
+    }
+
+    async fn select_stream(&self, live: &LiveRoom) -> Result<crate::stream::StreamInfo> {
+        select_stream(self.client_for(&live.room), live, &self.recording_config)
+            .instrument(tracing::info_span!("check", room = %live.room))
+            .await
     }
 
     fn spawn_recording(
         &self,
-        _room: String,
+        room: String,
         stream_info: crate::stream::StreamInfo,
         cancel_token: CancellationToken,
     ) -> JoinHandle<Result<RecordingStats>> {
-        let client = Arc::clone(&self.client);
+        let client = Arc::clone(self.client_for(&room));
         let config = self.recording_config.clone();
+        // Tags every log line of the recording, across awaits, with its room
+        let span = tracing::info_span!("recording", room = %room);
 
-        tokio::spawn(async move {
-            record_stream(&client, &stream_info, &config, cancel_token).await
-        })
+        tokio::spawn(
+            async move { record_stream(&client, &stream_info, &config, cancel_token).await }
+                .instrument(span),
+        )
     }
 
-    async fn get_status(&self, room: &str) -> RoomStatus {
+    /// Current status of every monitored room
+    pub async fn status_snapshot(&self) -> HashMap<String, RoomStatus> {
+        self.room_status.read().await.clone()
+    }
+
+    /// Handle for polling statuses from another task. `run` borrows the
+    /// monitor mutably, so take the handle before starting it.
+    pub fn status_handle(&self) -> RoomStatusHandle {
+        RoomStatusHandle {
+            room_status: Arc::clone(&self.room_status),
+        }
+    }
+
+    /// Current status of one room; `Unknown` for rooms not monitored
+    pub async fn get_status(&self, room: &str) -> RoomStatus {
         self.room_status
             .read()
             .await
@@ -381,43 +1309,553 @@ impl RoomMonitor {
     }
 
     async fn set_status(&self, room: &str, status: RoomStatus) {
-        self.room_status
+        let previous = self
+            .room_status
             .write()
             .await
-            .insert(room.to_string(), status);
+            .insert(room.to_string(), status.clone())
+            .unwrap_or(RoomStatus::Unknown);
+
+        if previous != status {
+            if let Some(ref log) = self.event_log {
+                log.record(room, previous.as_str(), status.as_str());
+            }
+            self.observe(|o| o.on_status_change(room, &previous, &status));
+        }
     }
 
-    /// Send a webhook notification (fire-and-forget)
-    async fn send_webhook(&self, message: &str) {
-        let url = match &self.webhook_url {
-            Some(url) => url.clone(),
-            None => return,
-        };
+    /// One line per room: status, then live counts or backoff state
+    async fn print_status_table(&self, check_states: &HashMap<String, RoomCheckState>) {
+        let statuses = self.room_status.read().await.clone();
+
+        let lines: Vec<String> = self
+            .rooms
+            .iter()
+            .map(|room| {
+                let status = statuses.get(room).cloned().unwrap_or(RoomStatus::Unknown);
+                let detail = if status == RoomStatus::Recording {
+                    live_progress(room)
+                        .map(|p| {
+                            format!(
+                                "{} segments, {:.2} MB",
+                                p.segments,
+                                p.bytes as f64 / 1024.0 / 1024.0
+                            )
+                        })
+                        .unwrap_or_default()
+                } else {
+                    check_states.get(room).map(check_detail).unwrap_or_default()
+                };
+                format!(
+                    "  {:<20} {:<11} {}",
+                    style(room).cyan(),
+                    status_style(&status),
+                    detail
+                )
+            })
+            .collect();
+
+        console::print_plain(&format!(
+            "{}\n{}",
+            style(format!("── Rooms ({}) ──", console::timestamp())).dim(),
+            lines.join("\n")
+        ));
+    }
+
+    fn observe(&self, event: impl FnOnce(&dyn MonitorObserver)) {
+        if let Some(ref observer) = self.observer {
+            event(observer.as_ref());
+        }
+    }
+
+    /// Send the recording-stopped webhook, if enabled
+    async fn notify_recording_stopped(&self, room: &str, summary: &str) {
+        if self.notify_on_stop {
+            self.webhook
+                .send(
+                    WebhookEvent::RecordingStop,
+                    &format!("⏹️ {} recording stopped: {}", room, summary),
+                )
+                .await;
+        }
+    }
+}
+
+/// How long the run loop has gone without a new cycle, once that exceeds
+/// `stall_after`
+fn stalled_for(last_cycle: Instant, stall_after: Duration) -> Option<Duration> {
+    Some(last_cycle.elapsed()).filter(|since| *since > stall_after)
+}
+
+/// Clients for rooms with their own cookies; failures fall back to the shared ones
+fn build_room_clients(
+    client: &ChaturbateClient,
+    cookies: &HashMap<String, String>,
+) -> HashMap<String, Arc<ChaturbateClient>> {
+    cookies
+        .iter()
+        .filter_map(
+            |(room, room_cookies)| match client.with_cookies(room_cookies) {
+                Ok(room_client) => Some((room.clone(), Arc::new(room_client))),
+                Err(e) => {
+                    console::print_error(&format!(
+                        "{}: can't use its own cookies ({}), using the shared ones",
+                        room, e
+                    ));
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// `record_if_title_matches`, compiled. main validates it up front, so an
+/// invalid pattern here is only possible from a config reload.
+fn title_filter(config: &MonitorConfig) -> Option<Regex> {
+    let pattern = config.record_if_title_matches.as_deref()?;
+    match compile_title_filter(pattern) {
+        Ok(filter) => Some(filter),
+        Err(e) => {
+            console::print_error(&format!("{}; recording every room", e));
+            None
+        }
+    }
+}
+
+/// Whether `filter` matches the room's title or any of its tags
+fn title_matches(filter: &Regex, title: Option<&str>, tags: &[String]) -> bool {
+    title.is_some_and(|title| filter.is_match(title)) || tags.iter().any(|tag| filter.is_match(tag))
+}
+
+/// Recording-start notification, with the room's title and tags when known
+fn start_message(stream_info: &crate::stream::StreamInfo) -> String {
+    let mut message = format!(
+        "🔴 {} started recording at {}p{}fps",
+        stream_info.room, stream_info.resolution, stream_info.framerate
+    );
+    if let Some(ref title) = stream_info.title {
+        message.push_str(&format!("\n{}", title));
+    }
+    if !stream_info.tags.is_empty() {
+        let tags: Vec<String> = stream_info.tags.iter().map(|t| format!("#{}", t)).collect();
+        message.push_str(&format!("\n{}", tags.join(" ")));
+    }
+    message
+}
+
+/// Whether `failures` out of `checked` rooms reaches `percent`. Cycles that
+/// checked fewer than `min_rooms` rooms are too small to judge.
+fn exceeds_threshold(failures: u32, checked: u32, percent: u32, min_rooms: u32) -> bool {
+    checked > 0 && checked >= min_rooms && failures > 0 && failures * 100 >= checked * percent
+}
+
+/// Cookie-death alert naming what the failing rooms actually returned, with
+/// the fix for the most demanding kind seen
+fn cookie_death_message(private: u32, cloudflare: u32, captcha: u32) -> String {
+    let mut seen = Vec::new();
+    if captcha > 0 {
+        seen.push(format!("{} CAPTCHA", captcha));
+    }
+    if cloudflare > 0 {
+        seen.push(format!("{} Cloudflare challenge", cloudflare));
+    }
+    if private > 0 {
+        seen.push(format!("{} private", private));
+    }
+
+    let fix = if captcha > 0 {
+        "Fix: solve the CAPTCHA in a browser (same IP and User-Agent) and update cf_clearance cookie."
+    } else if cloudflare > 0 {
+        "Fix: refresh the cf_clearance cookie (a JS challenge may also clear on its own)."
+    } else {
+        "Fix: update the sessionid cookie."
+    };
+
+    format!(
+        "🍪 Cookie died! Rooms returning {}. {}",
+        seen.join(", "),
+        fix
+    )
+}
+
+fn open_event_log(config: &MonitorConfig) -> Option<EventLog> {
+    let path = config.event_log.as_ref()?;
+    match EventLog::open(Path::new(path)) {
+        Ok(log) => Some(log),
+        Err(e) => {
+            console::print_warning(&format!("Event log disabled, can't open {}: {}", path, e));
+            None
+        }
+    }
+}
+
+/// Wait for the next reloaded config; never resolves without a reload channel
+async fn next_reload(
+    reload_rx: &mut Option<mpsc::UnboundedReceiver<MonitorConfig>>,
+) -> MonitorConfig {
+    match reload_rx {
+        Some(rx) => match rx.recv().await {
+            Some(config) => config,
+            None => std::future::pending().await,
+        },
+        None => std::future::pending().await,
+    }
+}
+
+/// The lowest-priority recording strictly below `priority`, if any
+fn pick_preemption_victim<'a>(
+    active: impl Iterator<Item = (&'a str, i32)>,
+    priority: i32,
+) -> Option<&'a str> {
+    active
+        .filter(|&(_, p)| p < priority)
+        .min_by_key(|&(_, p)| p)
+        .map(|(room, _)| room)
+}
+
+/// Wait for a recording task until `deadline`. On timeout the task is
+/// aborted and `None` is returned.
+async fn join_recording(
+    handle: &mut JoinHandle<Result<RecordingStats>>,
+    deadline: Option<Instant>,
+) -> Option<std::result::Result<Result<RecordingStats>, tokio::task::JoinError>> {
+    let Some(deadline) = deadline else {
+        return Some(handle.await);
+    };
 
-        let payload = serde_json::json!({
-            "text": message,
-            "source": "chaturbate-recorder",
-            "timestamp": chrono::Utc::now().to_rfc3339(),
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    match tokio::time::timeout(remaining, &mut *handle).await {
+        Ok(result) => Some(result),
+        Err(_) => {
+            handle.abort();
+            None
+        }
+    }
+}
+
+fn status_style(status: &RoomStatus) -> StyledObject<&'static str> {
+    let name = style(status.as_str());
+    match status {
+        RoomStatus::Recording => name.red().bold(),
+        RoomStatus::Offline | RoomStatus::Unknown => name.dim(),
+        RoomStatus::Private => name.yellow(),
+        RoomStatus::CookieDead => name.red(),
+    }
+}
+
+/// Backoff part of a status line, e.g. "private x3, next check in 240s"
+fn check_detail(state: &RoomCheckState) -> String {
+    let next_check = state
+        .next_check_at
+        .map(|at| at.saturating_duration_since(Instant::now()).as_secs())
+        .map(|secs| format!("next check in {}s", secs))
+        .unwrap_or_else(|| "next check now".to_string());
+
+    match state.last_error_kind {
+        Some(ref kind) if state.consecutive_same_error > 1 => format!(
+            "{} x{}, {}",
+            kind.as_str(),
+            state.consecutive_same_error,
+            next_check
+        ),
+        _ => next_check,
+    }
+}
+
+/// Report a finished recording: `text` prints the console message, while
+/// JSON output prints the structured stats instead
+fn print_finished(room: &str, stats: &RecordingStats, text: impl FnOnce()) {
+    if crate::output::stats::format() == OutputFormat::Json {
+        print_recording_stats(room, stats);
+    } else {
+        text();
+    }
+}
+
+/// Segment count for messages, flagging holes left by failed downloads
+fn segment_counts(stats: &RecordingStats) -> String {
+    if stats.segments_failed > 0 {
+        format!(
+            "{} segments ({} failed)",
+            stats.segments_downloaded, stats.segments_failed
+        )
+    } else {
+        format!("{} segments", stats.segments_downloaded)
+    }
+}
+
+/// One-line description of a finished recording for notifications
+fn stats_summary(stats: &RecordingStats) -> String {
+    format!(
+        "{}, {:.2} MB, {}",
+        segment_counts(stats),
+        stats.bytes_written as f64 / 1024.0 / 1024.0,
+        format_duration(stats.duration_seconds)
+    )
+}
+
+/// `result` field of a check event
+fn check_result_name<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "online",
+        Err(Error::BroadcasterOffline(_)) => "offline",
+        Err(Error::PrivateStream) => "private",
+        Err(Error::CloudflareBlocked) => "cloudflare",
+        Err(Error::CaptchaRequired) => "captcha",
+        Err(Error::ServerError(..)) => "server_error",
+        Err(_) => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_matches() {
+        let tags = |tags: &[&str]| -> Vec<String> { tags.iter().map(|t| t.to_string()).collect() };
+        let filter = Regex::new("(?i)cosplay").unwrap();
+
+        assert!(title_matches(&filter, Some("Friday COSPLAY night"), &[]));
+        assert!(title_matches(
+            &filter,
+            Some("Friday night"),
+            &tags(&["music", "cosplay"])
+        ));
+        assert!(!title_matches(
+            &filter,
+            Some("Friday night"),
+            &tags(&["music"])
+        ));
+        assert!(!title_matches(&filter, None, &[]));
+    }
+
+    #[test]
+    fn test_exceeds_threshold_default_half() {
+        assert!(exceeds_threshold(1, 2, 50, 1));
+        assert!(exceeds_threshold(5, 10, 50, 1));
+        assert!(!exceeds_threshold(4, 10, 50, 1));
+        assert!(!exceeds_threshold(0, 10, 50, 1));
+        assert!(!exceeds_threshold(0, 0, 50, 1));
+    }
+
+    #[test]
+    fn test_exceeds_threshold_custom_percent() {
+        assert!(exceeds_threshold(3, 4, 75, 1));
+        assert!(!exceeds_threshold(2, 4, 75, 1));
+        assert!(exceeds_threshold(1, 4, 25, 1));
+    }
+
+    #[test]
+    fn test_exceeds_threshold_min_rooms() {
+        // One genuinely private room out of two shouldn't count as cookie death
+        assert!(!exceeds_threshold(1, 2, 50, 3));
+        assert!(exceeds_threshold(2, 3, 50, 3));
+    }
+
+    #[test]
+    fn test_cookie_death_message_names_kind() {
+        let captcha = cookie_death_message(1, 2, 3);
+        assert!(captcha.contains("3 CAPTCHA, 2 Cloudflare challenge, 1 private"));
+        assert!(captcha.contains("solve the CAPTCHA"));
+
+        let js = cookie_death_message(0, 4, 0);
+        assert!(js.contains("4 Cloudflare challenge."));
+        assert!(!js.contains("solve the CAPTCHA"));
+
+        assert!(cookie_death_message(5, 0, 0).contains("sessionid"));
+    }
+
+    #[test]
+    fn test_pick_preemption_victim() {
+        let active = [("low", -1), ("mid", 5), ("lowest", -3)];
+        assert_eq!(
+            pick_preemption_victim(active.into_iter(), 10),
+            Some("lowest")
+        );
+        assert_eq!(
+            pick_preemption_victim(active.into_iter(), 0),
+            Some("lowest")
+        );
+        // Equal priority never preempts
+        assert_eq!(pick_preemption_victim(active.into_iter(), -3), None);
+        assert_eq!(pick_preemption_victim(std::iter::empty(), 10), None);
+    }
+
+    #[tokio::test]
+    async fn test_join_recording_aborts_after_deadline() {
+        // A recording that ignores cancellation
+        let mut handle = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(RecordingStats::default())
+        });
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert!(join_recording(&mut handle, Some(deadline)).await.is_none());
+        assert!(handle.await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_join_recording_within_deadline() {
+        let mut handle = tokio::spawn(async {
+            Ok(RecordingStats {
+                segments_downloaded: 3,
+                ..Default::default()
+            })
         });
 
-        let client = reqwest::Client::new();
-        let body = serde_json::to_string(&payload).unwrap_or_default();
-        match client.post(&url)
-            .header("Content-Type", "application/json")
-            .body(body)
-            .timeout(Duration::from_secs(10))
-            .send()
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let stats = join_recording(&mut handle, Some(deadline))
             .await
-        {
-            Ok(resp) if resp.status().is_success() => {
-                tracing::debug!("Webhook sent successfully");
-            }
-            Ok(resp) => {
-                tracing::warn!("Webhook returned {}: {}", resp.status(), url);
-            }
-            Err(e) => {
-                tracing::warn!("Webhook failed: {}", e);
-            }
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(stats.segments_downloaded, 3);
+    }
+
+    #[test]
+    fn test_room_status_serializes_as_str() {
+        for status in [
+            RoomStatus::Unknown,
+            RoomStatus::Recording,
+            RoomStatus::CookieDead,
+        ] {
+            assert_eq!(
+                serde_json::to_value(&status).unwrap(),
+                serde_json::Value::from(status.as_str())
+            );
         }
     }
+
+    #[tokio::test]
+    async fn test_status_handle_sees_updates() {
+        let client = ChaturbateClient::new(&Default::default()).unwrap();
+        let monitor = RoomMonitor::new(
+            client,
+            vec!["alice".to_string()],
+            &MonitorConfig::default(),
+            RecordingConfig::default(),
+        );
+        let handle = monitor.status_handle();
+
+        assert_eq!(handle.snapshot().await["alice"], RoomStatus::Unknown);
+        monitor.set_status("alice", RoomStatus::Offline).await;
+        assert_eq!(handle.snapshot().await["alice"], RoomStatus::Offline);
+        assert_eq!(monitor.status_snapshot().await, handle.snapshot().await);
+    }
+
+    #[derive(Default)]
+    struct StatusRecorder {
+        changes: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl MonitorObserver for StatusRecorder {
+        fn on_status_change(&self, room: &str, from: &RoomStatus, to: &RoomStatus) {
+            self.changes.lock().unwrap().push(format!(
+                "{}: {} -> {}",
+                room,
+                from.as_str(),
+                to.as_str()
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observer_receives_status_changes() {
+        let observer = Arc::new(StatusRecorder::default());
+        let client = ChaturbateClient::new(&Default::default()).unwrap();
+        let monitor = RoomMonitor::new(
+            client,
+            vec!["alice".to_string()],
+            &MonitorConfig::default(),
+            RecordingConfig::default(),
+        )
+        .with_observer(observer.clone());
+
+        monitor.set_status("alice", RoomStatus::Offline).await;
+        // Unchanged status is not an event
+        monitor.set_status("alice", RoomStatus::Offline).await;
+        monitor.set_status("alice", RoomStatus::Recording).await;
+
+        assert_eq!(
+            *observer.changes.lock().unwrap(),
+            vec!["alice: unknown -> offline", "alice: offline -> recording"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_to_zero_min_online_stops_holding_back() {
+        let client = ChaturbateClient::new(&Default::default()).unwrap();
+        let config = MonitorConfig {
+            rooms: vec!["alice".to_string()],
+            min_online_to_record: 2,
+            ..Default::default()
+        };
+        let mut monitor = RoomMonitor::new(
+            client,
+            config.rooms.clone(),
+            &config,
+            RecordingConfig::default(),
+        );
+        assert!(monitor.holds_back(false));
+
+        let mut check_states = HashMap::new();
+        let reloaded = MonitorConfig {
+            min_online_to_record: 0,
+            ..config
+        };
+        monitor.apply_reload(reloaded, &mut check_states).await;
+        assert!(!monitor.holds_back(false));
+    }
+
+    #[test]
+    fn test_stalled_for() {
+        let stall_after = Duration::from_secs(180);
+        assert_eq!(stalled_for(Instant::now(), stall_after), None);
+
+        let stalled = stalled_for(Instant::now() - Duration::from_secs(600), stall_after);
+        assert!(stalled.is_some_and(|since| since >= Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn test_check_detail_shows_backoff() {
+        let mut state = RoomCheckState::new();
+        assert_eq!(check_detail(&state), "next check now");
+
+        let interval = Duration::from_secs(60);
+        state.record_error(RoomErrorKind::Private, interval);
+        assert!(check_detail(&state).starts_with("next check in "));
+
+        state.record_error(RoomErrorKind::Private, interval);
+        assert!(check_detail(&state).starts_with("private x2, next check in "));
+    }
+
+    #[test]
+    fn test_skip_existing_backs_off_quietly() {
+        let interval = Duration::from_secs(60);
+        let mut state = RoomCheckState::new();
+
+        assert!(state.skip_existing(interval));
+        // Starting the next attempt resets the check state, not the count
+        state.record_success(interval);
+        assert!(!state.skip_existing(interval));
+        assert!(state.next_check_at.unwrap() > Instant::now() + interval);
+
+        // A recording that ran starts over
+        state.cool_down(Duration::ZERO);
+        assert!(state.skip_existing(interval));
+    }
+
+    #[test]
+    fn test_cool_down_after_recording() {
+        let mut state = RoomCheckState::new();
+        state.record_error(RoomErrorKind::Offline, Duration::from_secs(60));
+
+        state.cool_down(Duration::from_secs(20));
+        assert!(state.should_skip());
+        assert_eq!(state.last_error_kind, None);
+
+        state.cool_down(Duration::ZERO);
+        assert!(!state.should_skip());
+    }
 }
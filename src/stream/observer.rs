@@ -0,0 +1,33 @@
+use crate::stream::monitor::RoomStatus;
+use crate::stream::{RecordingStats, StreamInfo};
+
+/// Receives [`RoomMonitor`](crate::stream::RoomMonitor) events as they
+/// happen, for applications embedding the monitor.
+/// [`record_sources`](crate::stream::record_sources) only reports
+/// `on_recording_start`.
+///
+/// Methods are called synchronously from the monitor task, at the same points
+/// the monitor logs to the console, so events for one monitor arrive in
+/// order. Blocking or slow work delays room checks; hand it off to another
+/// task or thread instead. Every method defaults to doing nothing.
+pub trait MonitorObserver: Send + Sync {
+    /// A room moved from one status to another
+    fn on_status_change(&self, _room: &str, _from: &RoomStatus, _to: &RoomStatus) {}
+
+    /// A room came online and its recording was started
+    fn on_recording_start(&self, _room: &str, _stream_info: &StreamInfo) {}
+
+    /// A recording ended normally (stream end, private show, or shutdown)
+    fn on_recording_finish(&self, _room: &str, _stats: &RecordingStats) {}
+
+    /// A recording failed, or was aborted at shutdown
+    /// ([`Error::Interrupted`](crate::Error::Interrupted)). Task panics arrive
+    /// as a tokio `JoinError`.
+    fn on_recording_error(&self, _room: &str, _error: &(dyn std::error::Error + 'static)) {}
+
+    /// Enough rooms fail authentication that the cookies look dead
+    fn on_cookie_death(&self, _failing_rooms: u32, _checked_rooms: u32) {}
+
+    /// Rooms respond normally again after a cookie death
+    fn on_cookie_recovery(&self) {}
+}
@@ -0,0 +1,94 @@
+/// A segment written to disk, as listed in a VOD playlist
+#[derive(Debug, Clone)]
+pub struct PlaylistEntry {
+    /// Path of the segment relative to the playlist
+    pub uri: String,
+    pub duration: f64,
+}
+
+/// Render a complete VOD media playlist for already-written segments.
+/// `init_uri` is the fMP4 init segment every entry depends on, if any.
+pub fn build_vod_playlist(entries: &[PlaylistEntry], init_uri: Option<&str>) -> String {
+    // Target duration must be an integer at least as long as every segment
+    let target_duration = entries
+        .iter()
+        .map(|e| e.duration.ceil() as u64)
+        .max()
+        .unwrap_or(0);
+
+    let mut playlist = String::new();
+    playlist.push_str("#EXTM3U\n");
+    // EXT-X-MAP in a regular media playlist needs protocol version 6+
+    let version = if init_uri.is_some() { 7 } else { 3 };
+    playlist.push_str(&format!("#EXT-X-VERSION:{}\n", version));
+    playlist.push_str("#EXT-X-PLAYLIST-TYPE:VOD\n");
+    playlist.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", target_duration));
+    playlist.push_str("#EXT-X-MEDIA-SEQUENCE:0\n");
+    if let Some(uri) = init_uri {
+        playlist.push_str(&format!("#EXT-X-MAP:URI=\"{}\"\n", uri));
+    }
+
+    for entry in entries {
+        playlist.push_str(&format!("#EXTINF:{:.3},\n", entry.duration));
+        playlist.push_str(&entry.uri);
+        playlist.push('\n');
+    }
+
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_vod_playlist() {
+        let entries = vec![
+            PlaylistEntry {
+                uri: "room/room_00000.ts".to_string(),
+                duration: 2.0,
+            },
+            PlaylistEntry {
+                uri: "room/room_00001.ts".to_string(),
+                duration: 2.5,
+            },
+        ];
+
+        let playlist = build_vod_playlist(&entries, None);
+
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n\
+             #EXT-X-VERSION:3\n\
+             #EXT-X-PLAYLIST-TYPE:VOD\n\
+             #EXT-X-TARGETDURATION:3\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n\
+             #EXTINF:2.000,\n\
+             room/room_00000.ts\n\
+             #EXTINF:2.500,\n\
+             room/room_00001.ts\n\
+             #EXT-X-ENDLIST\n"
+        );
+
+        // Must round-trip through the same parser used for live playlists
+        let parsed = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+        assert_eq!(parsed.segments.len(), 2);
+        assert!(parsed.end_list);
+    }
+
+    #[test]
+    fn test_build_vod_playlist_with_init() {
+        let entries = vec![PlaylistEntry {
+            uri: "room/room_00000.m4s".to_string(),
+            duration: 2.0,
+        }];
+
+        let playlist = build_vod_playlist(&entries, Some("room/init.mp4"));
+
+        let parsed = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+        assert_eq!(parsed.version, Some(7));
+        let map = parsed.segments[0].map.as_ref().unwrap();
+        assert_eq!(map.uri, "room/init.mp4");
+    }
+}
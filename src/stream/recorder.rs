@@ -1,58 +1,536 @@
+use futures::Stream;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
-use tokio::fs::{File, OpenOptions};
-use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 use crate::api::ChaturbateClient;
-use crate::config::RecordingConfig;
-use crate::error::Result;
-use crate::fs::generate_output_path;
-use crate::stream::discovery::resolve_segment_url;
-use crate::stream::segment::{download_segment_with_retry, SegmentTracker};
+use crate::config::{RecordingConfig, StallAction};
+use crate::error::{Error, Result};
+use crate::fs::dump;
+use crate::output::events::{self, Event};
+use crate::output::progress;
+use crate::output::stats::format_duration;
+use crate::stream::discovery::{
+    get_lower_stream_info, get_stream_info, refresh_stream, resolve_segment_url, url_stream_info,
+    UrlSource,
+};
+use crate::stream::observer::MonitorObserver;
+use crate::stream::segment::{
+    fetch_init_segment, send, stream_segment_with_retry, AdBreakDetector, SegmentFormat,
+    SegmentMessage, SegmentTracker,
+};
+use crate::stream::writer::RecordingOutput;
 use crate::stream::StreamInfo;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct RecordingStats {
     pub segments_downloaded: u64,
+    /// Segments skipped after exhausting their retries, leaving holes
+    pub segments_failed: u64,
+    /// Segment downloads that were retried after a failed attempt
+    pub segment_retries: u64,
     pub bytes_written: u64,
     pub duration_seconds: f64,
     pub files_created: u32,
+    /// Output files in recording order
+    pub files: Vec<PathBuf>,
+    /// Recording stopped because the show turned private
+    pub went_private: bool,
+    /// Wall-clock time from start to finish of the recording
+    pub elapsed_seconds: f64,
+    /// Average media bitrate: bytes written over media duration, in Mbps
+    pub average_bitrate_mbps: f64,
+    /// Average download throughput: bytes written over wall-clock time
+    pub throughput_bytes_per_second: f64,
+    /// Media playlist polled last, following any re-resolution
+    pub playlist_url: String,
+}
+
+impl RecordingStats {
+    /// Fill in the derived rates once the recording has finished. Rates stay
+    /// zero when nothing measurable was recorded.
+    fn compute_rates(&mut self, elapsed: Duration) {
+        self.elapsed_seconds = elapsed.as_secs_f64();
+
+        self.average_bitrate_mbps = if self.duration_seconds > 0.0 {
+            self.bytes_written as f64 * 8.0 / self.duration_seconds / 1_000_000.0
+        } else {
+            0.0
+        };
+
+        self.throughput_bytes_per_second = if self.elapsed_seconds > 0.0 {
+            self.bytes_written as f64 / self.elapsed_seconds
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Counts of a recording in progress
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LiveProgress {
+    pub segments: u64,
+    pub bytes: u64,
+    /// Media playlist being polled, following any re-resolution; may carry
+    /// access tokens, see [`redact_url`](crate::stream::redact_url)
+    pub playlist_url: String,
 }
 
+/// Recordings in progress by room, for status displays
+static LIVE_PROGRESS: OnceLock<Mutex<HashMap<String, LiveProgress>>> = OnceLock::new();
+
+fn live_recordings() -> &'static Mutex<HashMap<String, LiveProgress>> {
+    LIVE_PROGRESS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Segments and bytes written so far by the recording of `room`, and the
+/// playlist it polls, if one is in progress
+pub fn live_progress(room: &str) -> Option<LiveProgress> {
+    live_recordings().lock().unwrap().get(room).cloned()
+}
+
+/// Publishes a recording's counts until dropped
+struct LiveProgressGuard {
+    room: String,
+}
+
+impl LiveProgressGuard {
+    fn new(room: &str) -> Self {
+        live_recordings()
+            .lock()
+            .unwrap()
+            .insert(room.to_string(), LiveProgress::default());
+        Self {
+            room: room.to_string(),
+        }
+    }
+
+    fn update(&self, stats: &RecordingStats) {
+        let progress = LiveProgress {
+            segments: stats.segments_downloaded,
+            bytes: stats.bytes_written,
+            playlist_url: stats.playlist_url.clone(),
+        };
+        live_recordings()
+            .lock()
+            .unwrap()
+            .insert(self.room.clone(), progress);
+    }
+}
+
+impl Drop for LiveProgressGuard {
+    fn drop(&mut self) {
+        live_recordings().lock().unwrap().remove(&self.room);
+    }
+}
+
+/// A snapshot of a recording, as yielded by [`record_stream_progress`]
+#[derive(Debug)]
+pub struct RecordingProgress {
+    pub room: String,
+    /// The file being written, or the last one once finished
+    pub file: Option<PathBuf>,
+    /// Bytes written so far, across every file of the recording
+    pub bytes: u64,
+    /// Segments written so far, across every file of the recording
+    pub segments: u64,
+    /// Media playlist currently polled, following any re-resolution
+    pub playlist_url: String,
+    /// Set on the last item only, with the recording's result
+    pub finished: Option<Result<RecordingStats>>,
+}
+
+/// Progress updates are dropped rather than slowing the recording down when
+/// the consumer falls this far behind; the final update is always delivered
+const PROGRESS_CHANNEL_CAPACITY: usize = 64;
+
+/// Characters of an unparseable playlist logged at debug level
+const PLAYLIST_SAMPLE_CHARS: usize = 200;
+
+/// Unparseable playlists in a row that end a recording when
+/// `max_playlist_parse_failures` is 0, so it can't poll forever
+const PARSE_FAILURE_HARD_CAP: u32 = 100;
+
+/// How often the room page is re-checked while verifying an endlist
+const ENDLIST_RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Segments averaged when judging whether downloads keep up
+const LAG_WINDOW: usize = 5;
+
+/// Fetch time over media duration at which downloads count as falling behind
+const LAG_THRESHOLD: f64 = 0.9;
+
+/// Rolling ratio of segment fetch time to segment duration
+#[derive(Default)]
+struct LagMonitor {
+    ratios: VecDeque<f64>,
+}
+
+impl LagMonitor {
+    fn record(&mut self, fetch_time: Duration, duration: f64) {
+        if duration <= 0.0 {
+            return;
+        }
+        self.ratios.push_back(fetch_time.as_secs_f64() / duration);
+        if self.ratios.len() > LAG_WINDOW {
+            self.ratios.pop_front();
+        }
+    }
+
+    /// The average ratio over a full window, if it's at the threshold
+    fn sustained_lag(&self) -> Option<f64> {
+        if self.ratios.len() < LAG_WINDOW {
+            return None;
+        }
+        let average = self.ratios.iter().sum::<f64>() / self.ratios.len() as f64;
+        (average >= LAG_THRESHOLD).then_some(average)
+    }
+
+    fn reset(&mut self) {
+        self.ratios.clear();
+    }
+}
+
+/// Notices an output file that stops growing on disk while segments keep
+/// being written to it
+struct GrowthCheck {
+    interval: Duration,
+    /// Bytes that may still sit in the write buffer rather than on disk
+    slack: u64,
+    next_check: tokio::time::Instant,
+    /// File, its size on disk, and the bytes written to it at the last check
+    last: Option<(PathBuf, u64, u64)>,
+}
+
+impl GrowthCheck {
+    fn new(config: &RecordingConfig) -> Self {
+        let interval = Duration::from_secs(config.output_growth_check_seconds);
+        Self {
+            interval,
+            slack: config.write_buffer_kb as u64 * 1024,
+            next_check: tokio::time::Instant::now() + interval,
+            last: None,
+        }
+    }
+
+    /// Once per interval, compare `path` on disk with the `written` bytes of
+    /// its part. Returns a description of the stall when it hasn't grown.
+    async fn check(&mut self, path: &std::path::Path, written: u64) -> Option<String> {
+        let now = tokio::time::Instant::now();
+        if self.interval.is_zero() || now < self.next_check {
+            return None;
+        }
+        self.next_check = now + self.interval;
+
+        let on_disk = match tokio::fs::metadata(path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                tracing::warn!("Failed to check the size of {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        // A new part starts a new baseline
+        let stall = match self.last {
+            Some((ref last_path, last_on_disk, last_written)) if last_path == path => {
+                has_stalled(last_on_disk, on_disk, last_written, written, self.slack).then(|| {
+                    format!(
+                        "{} is {} bytes on disk after {}s, but {} more bytes were written to it",
+                        path.display(),
+                        on_disk,
+                        self.interval.as_secs(),
+                        written - last_written
+                    )
+                })
+            }
+            _ => None,
+        };
+        self.last = Some((path.to_path_buf(), on_disk, written));
+        stall
+    }
+}
+
+/// The file hasn't grown on disk although more was written to it than the
+/// write buffer can hold
+fn has_stalled(
+    last_on_disk: u64,
+    on_disk: u64,
+    last_written: u64,
+    written: u64,
+    slack: u64,
+) -> bool {
+    on_disk <= last_on_disk && written.saturating_sub(last_written) > slack
+}
+
+/// Number of in-flight messages between the downloader and the writer. Each
+/// message is at most one HTTP body chunk, so this bounds memory per recording.
+const SEGMENT_CHANNEL_CAPACITY: usize = 256;
+
 pub async fn record_stream(
     client: &ChaturbateClient,
     stream_info: &StreamInfo,
     config: &RecordingConfig,
     cancel_token: CancellationToken,
 ) -> Result<RecordingStats> {
-    let mut stats = RecordingStats::default();
-    let mut tracker = SegmentTracker::new()?;
+    record(client, stream_info, config, cancel_token, None).await
+}
 
-    // Create initial output file
-    let (mut output_file, mut current_path) =
-        create_output_file(&stream_info.room, config, 0).await?;
-    stats.files_created = 1;
+/// Record in a background task, like [`record_stream`], and yield its
+/// progress after every segment. The last item has `finished` set, then the
+/// stream ends. The stream is `Unpin`, so `next()` works without pinning.
+/// Must be called from within a tokio runtime.
+pub fn record_stream_progress(
+    client: ChaturbateClient,
+    stream_info: StreamInfo,
+    config: RecordingConfig,
+    cancel_token: CancellationToken,
+) -> impl Stream<Item = RecordingProgress> + Unpin {
+    let (tx, mut rx) = mpsc::channel(PROGRESS_CHANNEL_CAPACITY);
 
-    let mut file_duration: f64 = 0.0;
-    let mut file_size: u64 = 0;
-    let mut file_sequence: u32 = 0;
+    tokio::spawn(async move {
+        let result = record(&client, &stream_info, &config, cancel_token, Some(&tx)).await;
+        let (file, bytes, segments, playlist_url) = match &result {
+            Ok(stats) => (
+                stats.files.last().cloned(),
+                stats.bytes_written,
+                stats.segments_downloaded,
+                stats.playlist_url.clone(),
+            ),
+            Err(_) => (None, 0, 0, stream_info.hls_source.clone()),
+        };
+        let _ = tx
+            .send(RecordingProgress {
+                room: stream_info.room,
+                file,
+                bytes,
+                segments,
+                playlist_url,
+                finished: Some(result),
+            })
+            .await;
+    });
 
-    let poll_interval = Duration::from_millis(config.poll_interval_ms());
-    let max_duration_secs = (config.max_duration_minutes as f64) * 60.0;
-    let max_filesize_bytes = (config.max_filesize_mb as u64) * 1024 * 1024;
+    futures::stream::poll_fn(move |cx| rx.poll_recv(cx))
+}
 
-    // Track consecutive failures to detect stream becoming unavailable
-    let mut consecutive_failures: u32 = 0;
-    const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+async fn record(
+    client: &ChaturbateClient,
+    stream_info: &StreamInfo,
+    config: &RecordingConfig,
+    cancel_token: CancellationToken,
+    progress: Option<&mpsc::Sender<RecordingProgress>>,
+) -> Result<RecordingStats> {
+    let (tx, rx) = mpsc::channel(SEGMENT_CHANNEL_CAPACITY);
+    let started = std::time::Instant::now();
+    #[cfg(feature = "database")]
+    let started_at = chrono::Utc::now();
+
+    events::emit(&Event::RecordingStart {
+        room: &stream_info.room,
+        resolution: stream_info.resolution,
+        framerate: stream_info.framerate,
+    });
+
+    // The downloader and writer run concurrently: a slow disk applies
+    // backpressure through the bounded channel instead of stalling fetches
+    // mid-request, and a slow network no longer blocks pending writes.
+    let joined = tokio::try_join!(
+        download_segments(client, stream_info, config, tx, cancel_token),
+        write_segments(stream_info, config, rx, progress),
+    );
+    let (outcome, mut stats) = match joined {
+        Ok(results) => results,
+        Err(e) => {
+            events::emit(&Event::RecordingError {
+                room: &stream_info.room,
+                error: e.to_string(),
+            });
+            return Err(e);
+        }
+    };
+    stats.went_private = outcome.went_private;
+    stats.segments_failed = outcome.segments_failed;
+    stats.segment_retries = outcome.segment_retries;
+    stats.compute_rates(started.elapsed());
+
+    #[cfg(feature = "database")]
+    if let Some(ref database) = config.database {
+        crate::database::log_recording(
+            database,
+            stream_info,
+            &stats,
+            started_at,
+            chrono::Utc::now(),
+        )
+        .await;
+    }
 
     tracing::info!(
-        "Recording {} at {}p{}fps to {}",
+        "Recording complete for {}: {} segments, {:.2} MB, {:.0}s, {:.2} Mbps",
         stream_info.room,
-        stream_info.resolution,
-        stream_info.framerate,
-        current_path.display()
+        stats.segments_downloaded,
+        stats.bytes_written as f64 / 1024.0 / 1024.0,
+        stats.duration_seconds,
+        stats.average_bitrate_mbps
     );
+    events::emit(&Event::RecordingStop {
+        room: &stream_info.room,
+        segments: stats.segments_downloaded,
+        bytes: stats.bytes_written,
+        duration_seconds: stats.duration_seconds,
+        went_private: stats.went_private,
+        files: &stats.files,
+    });
+
+    Ok(stats)
+}
+
+/// Record several rooms concurrently until each stream ends or
+/// `cancel_token` fires. Returns one result per room, in the order the
+/// recordings finished; a room that is offline fails with its discovery error.
+pub async fn record_rooms(
+    client: ChaturbateClient,
+    rooms: Vec<String>,
+    config: &RecordingConfig,
+    cancel_token: CancellationToken,
+) -> Vec<(String, Result<RecordingStats>)> {
+    record_sources(client, rooms, Vec::new(), config, None, cancel_token).await
+}
+
+/// [`record_rooms`], plus plain playlist URLs recorded alongside the rooms
+/// and reported under their labels. `observer` hears of each recording as it
+/// starts, e.g. to report it before the results are in.
+pub async fn record_sources(
+    client: ChaturbateClient,
+    rooms: Vec<String>,
+    urls: Vec<UrlSource>,
+    config: &RecordingConfig,
+    observer: Option<Arc<dyn MonitorObserver>>,
+    cancel_token: CancellationToken,
+) -> Vec<(String, Result<RecordingStats>)> {
+    // Playlist URLs point at third-party hosts: they get none of the site's
+    // cookies or headers
+    let url_client = (!urls.is_empty()).then(|| client.plain().map(Arc::new));
+    let client = Arc::new(client);
+    let mut tasks = JoinSet::new();
+    // Lets a panicked task still be reported against its room
+    let mut task_rooms = HashMap::new();
+    let mut results = Vec::new();
+
+    let sources = rooms.into_iter().map(|room| (room, None)).chain(
+        urls.into_iter()
+            .map(|source| (source.label.clone(), Some(source))),
+    );
+
+    for (room, url) in sources {
+        let client = match (&url, &url_client) {
+            (Some(_), Some(Ok(url_client))) => Arc::clone(url_client),
+            (Some(_), Some(Err(e))) => {
+                let error = Error::Config(format!("Can't create a client for {}: {}", room, e));
+                results.push((room, Err(error)));
+                continue;
+            }
+            _ => Arc::clone(&client),
+        };
+        let config = config.clone();
+        let cancel_token = cancel_token.clone();
+        let observer = observer.clone();
+        let task_room = room.clone();
+        let span = tracing::info_span!("recording", room = %room);
+
+        let handle = tasks.spawn(
+            async move {
+                let stream_info = match url {
+                    Some(ref source) => url_stream_info(&client, source, &config).await?,
+                    None => get_stream_info(&client, &room, &config).await?,
+                };
+
+                if let Some(observer) = observer {
+                    observer.on_recording_start(&room, &stream_info);
+                }
+
+                record_stream(&client, &stream_info, &config, cancel_token).await
+            }
+            .instrument(span),
+        );
+        task_rooms.insert(handle.id(), task_room);
+    }
+
+    while let Some(joined) = tasks.join_next_with_id().await {
+        let (id, result) = match joined {
+            Ok((id, result)) => (id, result),
+            Err(e) => (e.id(), Err(Error::TaskFailed(e.to_string()))),
+        };
+        let room = task_rooms.remove(&id).unwrap_or_default();
+        results.push((room, result));
+    }
+
+    results
+}
+
+/// What the downloader knows about a recording that the writer doesn't
+#[derive(Debug, Default)]
+struct DownloadOutcome {
+    went_private: bool,
+    segments_failed: u64,
+    segment_retries: u64,
+}
+
+/// Producer: poll the media playlist and stream new segments to the writer.
+/// Returning drops `tx`, which tells the writer to finish up.
+async fn download_segments(
+    client: &ChaturbateClient,
+    stream_info: &StreamInfo,
+    config: &RecordingConfig,
+    tx: mpsc::Sender<SegmentMessage>,
+    cancel_token: CancellationToken,
+) -> Result<DownloadOutcome> {
+    let mut outcome = DownloadOutcome::default();
+    let mut tracker = match config.segment_pattern {
+        Some(ref pattern) => SegmentTracker::with_pattern(pattern)?,
+        None => SegmentTracker::new()?,
+    };
+    let poll_interval = Duration::from_millis(config.poll_interval_ms());
+
+    // Re-resolution can move us to a different media playlist mid-recording
+    let mut stream_info = stream_info.clone();
+
+    // Track consecutive failures to detect stream becoming unavailable
+    let mut consecutive_failures: u32 = 0;
+    const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+    // Only re-resolve once per run of failures so a dead URL can't loop forever
+    let mut refreshed = false;
+    // Playlists that came back but didn't parse, e.g. an error page with a 200
+    let mut parse_failures: u32 = 0;
+    let mut parse_refreshed = false;
+    let mut wrote_segment = false;
+    // Consecutive 403s from the playlist or segments: a public show that
+    // turned private (or ticketed) looks like this
+    let mut private_run: u32 = 0;
+    let mut waiting_private = false;
+    // Container of the segments sent so far, with the fMP4 init segment URI
+    let mut current_format: Option<(SegmentFormat, Option<String>)> = None;
+    // --debug-dump keeps the first media playlist of each stream URL
+    let mut dumped_playlist: Option<String> = None;
+    // How well segment downloads keep up, for adaptive_quality
+    let mut lag = LagMonitor::default();
+    let mut at_lowest_quality = false;
+    // Cue-out/cue-in state, for skip_ads
+    let mut ad_breaks = config
+        .skip_ads
+        .then(|| AdBreakDetector::new(&config.ad_cue_out_tags, &config.ad_cue_in_tags));
+    let mut in_ad_break = false;
+    // Wall-clock stop time, independent of max_duration_minutes splits
+    let deadline = (config.total_duration_minutes > 0).then(|| {
+        tokio::time::Instant::now()
+            + Duration::from_secs(u64::from(config.total_duration_minutes) * 60)
+    });
 
     loop {
         // Check for cancellation
@@ -61,15 +539,89 @@ pub async fn record_stream(
             break;
         }
 
+        if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+            tracing::info!(
+                "Total duration limit of {} minutes reached for {}, stopping recording",
+                config.total_duration_minutes,
+                stream_info.room
+            );
+            break;
+        }
+
+        if private_run >= MAX_CONSECUTIVE_FAILURES && !waiting_private {
+            // Our cookies may still grant access (e.g. a bought ticket)
+            if !refreshed {
+                refreshed = true;
+                if let Some(info) = refresh_stream_info(client, &stream_info, config).await {
+                    if send(&tx, SegmentMessage::StreamChanged(info.clone()))
+                        .await
+                        .is_err()
+                    {
+                        return Ok(outcome);
+                    }
+                    stream_info = info;
+                    private_run = 0;
+                    continue;
+                }
+            }
+
+            if config.stop_on_private {
+                tracing::info!(
+                    "Show went private for {}, stopping recording",
+                    stream_info.room
+                );
+                outcome.went_private = true;
+                return Ok(outcome);
+            }
+
+            tracing::info!(
+                "Show went private for {}, waiting for it to become public again",
+                stream_info.room
+            );
+            waiting_private = true;
+        }
+
         // Fetch media playlist
         let playlist_content = match client.get(&stream_info.hls_source).await {
             Ok(content) => {
                 consecutive_failures = 0; // Reset on success
+                refreshed = false;
+                if dump::enabled()
+                    && dumped_playlist.as_deref() != Some(stream_info.hls_source.as_str())
+                {
+                    dump::save(&stream_info.room, "media", "m3u8", &content).await;
+                    dumped_playlist = Some(stream_info.hls_source.clone());
+                }
                 content
             }
             Err(e) => {
+                if matches!(e, Error::PrivateStream) {
+                    private_run += 1;
+                } else {
+                    private_run = 0;
+                }
+
                 consecutive_failures += 1;
-                if consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                if consecutive_failures >= MAX_CONSECUTIVE_FAILURES && !refreshed {
+                    // The playlist URL may have rotated; look the stream up again
+                    refreshed = true;
+                    if let Some(info) = refresh_stream_info(client, &stream_info, config).await {
+                        if send(&tx, SegmentMessage::StreamChanged(info.clone()))
+                            .await
+                            .is_err()
+                        {
+                            return Ok(outcome);
+                        }
+                        stream_info = info;
+                        consecutive_failures = 0;
+                        private_run = 0;
+                        continue;
+                    }
+                }
+                // A private show is handled at the top of the loop
+                if consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+                    && private_run < MAX_CONSECUTIVE_FAILURES
+                {
                     tracing::info!(
                         "Stream unavailable for {} after {} consecutive failures, stopping recording",
                         stream_info.room,
@@ -92,95 +644,290 @@ pub async fn record_stream(
 
         // Parse media playlist
         let playlist = match m3u8_rs::parse_media_playlist_res(playlist_content.as_bytes()) {
-            Ok(pl) => pl,
+            Ok(pl) => {
+                parse_failures = 0;
+                parse_refreshed = false;
+                pl
+            }
             Err(e) => {
-                consecutive_failures += 1;
-                if consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
-                    tracing::info!(
-                        "Stream unavailable for {} after {} consecutive failures, stopping recording",
-                        stream_info.room,
-                        consecutive_failures
-                    );
-                    break;
-                }
-                if consecutive_failures == 1 {
+                parse_failures += 1;
+                if parse_failures == 1 {
                     tracing::warn!(
                         "Failed to parse media playlist for {}: {:?}",
                         stream_info.room,
                         e
                     );
+                    tracing::debug!(
+                        "Unparseable playlist for {} starts with: {:?}",
+                        stream_info.room,
+                        playlist_content
+                            .chars()
+                            .take(PLAYLIST_SAMPLE_CHARS)
+                            .collect::<String>()
+                    );
+                }
+
+                if parse_failures >= parse_failure_limit(config.max_playlist_parse_failures) {
+                    if !parse_refreshed {
+                        parse_refreshed = true;
+                        if let Some(info) = refresh_stream_info(client, &stream_info, config).await
+                        {
+                            if send(&tx, SegmentMessage::StreamChanged(info.clone()))
+                                .await
+                                .is_err()
+                            {
+                                return Ok(outcome);
+                            }
+                            stream_info = info;
+                            parse_failures = 0;
+                            continue;
+                        }
+                    }
+
+                    let message = format!(
+                        "media playlist for {} couldn't be parsed {} times in a row and the stream \
+                         couldn't be found again: {}",
+                        stream_info.room, parse_failures, stream_info.hls_source
+                    );
+                    // A recording that already wrote segments ends normally so
+                    // the writer finishes its files and the stats are reported;
+                    // an error here would abandon them as `.part` files. One
+                    // that never got going fails with the reason.
+                    if wrote_segment {
+                        tracing::warn!("Stopping recording: {}", message);
+                        break;
+                    }
+                    return Err(Error::M3u8(message));
                 }
+
                 tokio::time::sleep(poll_interval).await;
                 continue;
             }
         };
 
-        // Check for stream end
-        if playlist.end_list {
-            tracing::info!("Stream ended for {}", stream_info.room);
-            break;
+        // A VOD playlist lists every segment up front: download them all once
+        // instead of polling, and don't treat its endlist as the show ending
+        let is_vod = matches!(
+            playlist.playlist_type,
+            Some(m3u8_rs::MediaPlaylistType::Vod)
+        );
+
+        // Check for stream end. Encoder hiccups can briefly publish an
+        // endlist too, so with a grace period the room is checked first.
+        if playlist.end_list && !is_vod {
+            if config.reconnect_grace_seconds == 0 {
+                tracing::info!("Stream ended for {}", stream_info.room);
+                break;
+            }
+
+            tracing::info!(
+                "Endlist seen for {}, verifying the stream has ended",
+                stream_info.room
+            );
+            match verify_endlist(client, &stream_info, config, &cancel_token).await {
+                EndlistOutcome::Resumed => {
+                    tracing::info!("Stream for {} resumed after endlist", stream_info.room);
+                    continue;
+                }
+                EndlistOutcome::Moved(info) => {
+                    tracing::info!("Stream for {} resumed on a new playlist", info.room);
+                    if send(&tx, SegmentMessage::StreamChanged(info.clone()))
+                        .await
+                        .is_err()
+                    {
+                        return Ok(outcome);
+                    }
+                    stream_info = info;
+                    continue;
+                }
+                // The top of the loop reports the cancellation
+                EndlistOutcome::Ended if cancel_token.is_cancelled() => continue,
+                EndlistOutcome::Ended => {
+                    tracing::info!("Stream ended for {}", stream_info.room);
+                    break;
+                }
+            }
         }
 
         // Process segments
-        for segment in &playlist.segments {
-            if let Some(seq) = tracker.extract_sequence(&segment.uri) {
-                if tracker.is_new_segment(seq) {
-                    // Download segment
-                    let segment_url = resolve_segment_url(&stream_info.hls_source, &segment.uri)?;
-
-                    match download_segment_with_retry(client, &segment_url, 3).await {
-                        Ok(data) => {
-                            // Write to output file
-                            output_file.write_all(&data).await?;
-
-                            let bytes = data.len() as u64;
-                            let duration = segment.duration as f64;
-                            file_size += bytes;
-                            file_duration += duration;
-                            stats.bytes_written += bytes;
-                            stats.duration_seconds += duration;
-                            stats.segments_downloaded += 1;
-
-                            tracker.update_sequence(seq);
-
-                            // Check if we need to split file
-                            if should_split_file(
-                                file_duration,
-                                file_size,
-                                max_duration_secs,
-                                max_filesize_bytes,
-                            ) {
-                                output_file.flush().await?;
-                                drop(output_file);
-
-                                file_sequence += 1;
-                                let (new_file, new_path) = create_output_file(
-                                    &stream_info.room,
-                                    config,
-                                    file_sequence,
-                                )
-                                .await?;
-
-                                output_file = new_file;
-                                current_path = new_path;
-                                file_duration = 0.0;
-                                file_size = 0;
-                                stats.files_created += 1;
-
-                                tracing::info!(
-                                    "Split recording, new file: {}",
-                                    current_path.display()
+        let mut stopped_early = false;
+        let mut interrupted = false;
+        for (seq, segment, map) in tracker.new_segments(&playlist) {
+            // A VOD lists its whole length at once, so stop between segments
+            if cancel_token.is_cancelled()
+                || deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline)
+            {
+                interrupted = true;
+                break;
+            }
+
+            if let Some(ads) = ad_breaks.as_mut() {
+                let is_ad = ads.is_ad(segment);
+                if is_ad != in_ad_break {
+                    in_ad_break = is_ad;
+                    if is_ad {
+                        tracing::info!(
+                            "Ad break started for {}, skipping its segments",
+                            stream_info.room
+                        );
+                    } else {
+                        tracing::info!("Ad break ended for {}", stream_info.room);
+                    }
+                }
+                if is_ad {
+                    tracing::debug!("Skipping ad segment {} for {}", seq, stream_info.room);
+                    tracker.update_sequence(seq);
+                    continue;
+                }
+            }
+
+            let format = SegmentFormat::detect(&segment.uri, map);
+            let format_key = (format, map.map(|m| m.uri.clone()));
+
+            if current_format.as_ref() != Some(&format_key) {
+                let init = match map {
+                    Some(map) => {
+                        let init_url = resolve_segment_url(&stream_info.hls_source, &map.uri)?;
+                        match fetch_init_segment(client, &init_url, map.byte_range.as_ref()).await {
+                            Ok(data) => Some(data),
+                            Err(e) => {
+                                // Media segments are useless without it; retry next poll
+                                tracing::warn!(
+                                    "Failed to download init segment for {}: {}",
+                                    stream_info.room,
+                                    e
                                 );
+                                stopped_early = true;
+                                break;
                             }
                         }
-                        Err(e) => {
-                            tracing::warn!(
-                                "Failed to download segment {} for {}: {}",
-                                seq,
-                                stream_info.room,
-                                e
-                            );
+                    }
+                    None => None,
+                };
+
+                if send(&tx, SegmentMessage::Format { format, init })
+                    .await
+                    .is_err()
+                {
+                    return Ok(outcome);
+                }
+                current_format = Some(format_key);
+            }
+
+            let segment_url = resolve_segment_url(&stream_info.hls_source, &segment.uri)?;
+
+            match stream_segment_with_retry(
+                client,
+                &segment_url,
+                &tx,
+                3,
+                // fMP4 fragments have no TS sync bytes to check
+                config.verify_ts_sync && format == SegmentFormat::Ts,
+                &mut outcome.segment_retries,
+            )
+            .await
+            {
+                Ok(network_time) => {
+                    lag.record(network_time, segment.duration as f64);
+                    let complete = SegmentMessage::Complete {
+                        sequence: seq,
+                        duration: segment.duration as f64,
+                    };
+                    if send(&tx, complete).await.is_err() {
+                        return Ok(outcome);
+                    }
+                    tracker.update_sequence(seq);
+                    wrote_segment = true;
+
+                    private_run = 0;
+                    if waiting_private {
+                        waiting_private = false;
+                        tracing::info!("Show is public again for {}", stream_info.room);
+                    }
+                }
+                // Writer has stopped; its result carries the reason
+                Err(Error::Interrupted) => return Ok(outcome),
+                Err(Error::PrivateStream) => {
+                    private_run += 1;
+                    tracing::debug!(
+                        "Segment {} for {} is forbidden (private show?)",
+                        seq,
+                        stream_info.room
+                    );
+                    if private_run >= MAX_CONSECUTIVE_FAILURES {
+                        stopped_early = true;
+                        break;
+                    }
+                }
+                Err(e) => {
+                    outcome.segments_failed += 1;
+                    tracing::warn!(
+                        "Failed to download segment {} for {}: {}",
+                        seq,
+                        stream_info.room,
+                        e
+                    );
+                }
+            }
+        }
+
+        // The top of the loop reports why
+        if interrupted {
+            continue;
+        }
+
+        if is_vod && !stopped_early {
+            tracing::info!(
+                "Downloaded every segment of the VOD playlist for {}",
+                stream_info.room
+            );
+            break;
+        }
+
+        if config.adaptive_quality && !at_lowest_quality {
+            if let Some(ratio) = lag.sustained_lag() {
+                lag.reset();
+                // Never step below the resolution floor
+                let lower = get_lower_stream_info(client, &stream_info)
+                    .await
+                    .map(|info| info.filter(|info| info.resolution >= config.min_resolution));
+                match lower {
+                    Ok(Some(info)) => {
+                        tracing::warn!(
+                            "Downloads for {} can't keep up (segments take {:.0}% of their duration to fetch), switching from {}p{}fps to {}p{}fps",
+                            stream_info.room,
+                            ratio * 100.0,
+                            stream_info.resolution,
+                            stream_info.framerate,
+                            info.resolution,
+                            info.framerate
+                        );
+                        if send(&tx, SegmentMessage::StreamChanged(info.clone()))
+                            .await
+                            .is_err()
+                        {
+                            return Ok(outcome);
                         }
+                        stream_info = info;
+                        // Behind already; poll the new playlist right away
+                        continue;
+                    }
+                    Ok(None) => {
+                        at_lowest_quality = true;
+                        tracing::warn!(
+                            "Downloads for {} can't keep up (segments take {:.0}% of their duration to fetch), but {}p{}fps is the lowest quality allowed",
+                            stream_info.room,
+                            ratio * 100.0,
+                            stream_info.resolution,
+                            stream_info.framerate
+                        );
+                    }
+                    Err(e) => {
+                        tracing::debug!(
+                            "Lower quality lookup failed for {}: {}",
+                            stream_info.room,
+                            e
+                        )
                     }
                 }
             }
@@ -190,20 +937,270 @@ pub async fn record_stream(
         tokio::time::sleep(poll_interval).await;
     }
 
-    // Flush and close file
-    output_file.flush().await?;
+    Ok(outcome)
+}
+
+/// Result of re-checking a stream after its playlist carried EXT-X-ENDLIST
+enum EndlistOutcome {
+    /// The room is offline, or the endlist outlasted the grace period
+    Ended,
+    /// The same playlist dropped the endlist and carries on
+    Resumed,
+    /// The room is live on a different playlist
+    Moved(StreamInfo),
+}
+
+/// Watch a stream that announced its end for up to `reconnect_grace_seconds`.
+/// The room page decides: offline means ended; otherwise keep waiting for the
+/// playlist to continue.
+async fn verify_endlist(
+    client: &ChaturbateClient,
+    stream_info: &StreamInfo,
+    config: &RecordingConfig,
+    cancel_token: &CancellationToken,
+) -> EndlistOutcome {
+    let deadline =
+        tokio::time::Instant::now() + Duration::from_secs(config.reconnect_grace_seconds);
+
+    loop {
+        // A resumed stream may come back on different variants
+        client.invalidate_master_playlist(&stream_info.room);
+        match refresh_stream(client, stream_info, config).await {
+            Err(Error::BroadcasterOffline(_)) => return EndlistOutcome::Ended,
+            Ok(info) if info.hls_source != stream_info.hls_source => {
+                return EndlistOutcome::Moved(info)
+            }
+            Ok(_) => {}
+            Err(e) => tracing::debug!("Endlist check failed for {}: {}", stream_info.room, e),
+        }
+
+        // Still listed as live; see whether the playlist itself continues
+        if let Ok(content) = client.get(&stream_info.hls_source).await {
+            if let Ok(playlist) = m3u8_rs::parse_media_playlist_res(content.as_bytes()) {
+                if !playlist.end_list {
+                    return EndlistOutcome::Resumed;
+                }
+            }
+        }
+
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return EndlistOutcome::Ended;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(ENDLIST_RECHECK_INTERVAL.min(deadline - now)) => {}
+            _ = cancel_token.cancelled() => return EndlistOutcome::Ended,
+        }
+    }
+}
+
+/// Re-run discovery for a stream whose media playlist stopped responding.
+/// Returns the fresh stream info if the room is still online.
+async fn refresh_stream_info(
+    client: &ChaturbateClient,
+    stream_info: &StreamInfo,
+    config: &RecordingConfig,
+) -> Option<StreamInfo> {
+    tracing::info!("Re-resolving stream URL for {}", stream_info.room);
+    // The cached variants may be what stopped working
+    client.invalidate_master_playlist(&stream_info.room);
+
+    match refresh_stream(client, stream_info, config).await {
+        // The writer logs the new URL when it takes over
+        Ok(info) => Some(info),
+        Err(e) => {
+            tracing::debug!("Re-resolution failed for {}: {}", stream_info.room, e);
+            None
+        }
+    }
+}
+
+/// Consumer: write segments in arrival order, split files, and own the stats.
+async fn write_segments(
+    stream_info: &StreamInfo,
+    config: &RecordingConfig,
+    mut rx: mpsc::Receiver<SegmentMessage>,
+    progress: Option<&mpsc::Sender<RecordingProgress>>,
+) -> Result<RecordingStats> {
+    let mut stats = RecordingStats::default();
+    let mut stream_info = stream_info.clone();
+    let mut output = RecordingOutput::new(&stream_info.room, config);
+
+    // Kept segments are separate files already, so parts are never split
+    // by duration, size, or resolution
+    let (max_duration_secs, max_filesize_bytes) = if config.keep_segments {
+        (0.0, 0)
+    } else {
+        (
+            (config.max_duration_minutes as f64) * 60.0,
+            (config.max_filesize_mb as u64) * 1024 * 1024,
+        )
+    };
+
+    let bar = progress::create_recording_bar(&stream_info.room);
+    let live = LiveProgressGuard::new(&stream_info.room);
+
+    // Heartbeat log; only reached once segments arrive past the interval
+    let started = tokio::time::Instant::now();
+    let progress_interval = Duration::from_secs(config.progress_log_interval_seconds);
+    let mut next_progress_log = started + progress_interval;
+    let mut growth = GrowthCheck::new(config);
 
     tracing::info!(
-        "Recording complete for {}: {} segments, {:.2} MB, {:.0}s",
+        "Recording {} at {}p{}fps from {}",
         stream_info.room,
-        stats.segments_downloaded,
-        stats.bytes_written as f64 / 1024.0 / 1024.0,
-        stats.duration_seconds
+        stream_info.resolution,
+        stream_info.framerate,
+        stream_info.redacted_source()
+    );
+    tracing::debug!(
+        "Media playlist for {}: {}",
+        stream_info.room,
+        stream_info.hls_source
     );
+    stats.playlist_url = stream_info.hls_source.clone();
+    live.update(&stats);
+
+    // A failed write or a stalled output ends the recording early; the
+    // current part is flushed explicitly instead of relying on Drop
+    let written: Result<()> = async {
+        while let Some(message) = rx.recv().await {
+            match message {
+                SegmentMessage::Chunk(data) => {
+                    output.write_chunk(&data).await?;
+                }
+                SegmentMessage::Abort => {
+                    output.abort_segment().await?;
+                }
+                SegmentMessage::Format { format, init } => {
+                    output.set_format(format, init).await?;
+                }
+                SegmentMessage::StreamChanged(info) => {
+                    let changed = info.resolution != stream_info.resolution
+                        || info.framerate != stream_info.framerate;
+
+                    if changed {
+                        tracing::info!(
+                            "Stream for {} changed from {}p{}fps to {}p{}fps",
+                            stream_info.room,
+                            stream_info.resolution,
+                            stream_info.framerate,
+                            info.resolution,
+                            info.framerate
+                        );
+                    }
+
+                    // Joining differently-encoded TS data breaks most players
+                    if changed && config.split_on_resolution_change && !config.keep_segments {
+                        output.split().await?;
+                    }
+
+                    if info.hls_source != stream_info.hls_source {
+                        tracing::info!("Now polling {} for {}", info.redacted_source(), info.room);
+                        tracing::debug!("Media playlist for {}: {}", info.room, info.hls_source);
+                        stats.playlist_url = info.hls_source.clone();
+                        live.update(&stats);
+                    }
+
+                    stream_info = info;
+                }
+                SegmentMessage::Complete { sequence, duration } => {
+                    tracing::debug!("Wrote segment {} for {}", sequence, stream_info.room);
+
+                    let bytes = output.complete_segment(duration).await?;
+                    stats.bytes_written += bytes;
+                    stats.duration_seconds += duration;
+                    stats.segments_downloaded += 1;
+
+                    bar.set_message(format!(
+                        "{} segments, {:.2} MB",
+                        stats.segments_downloaded,
+                        stats.bytes_written as f64 / 1024.0 / 1024.0
+                    ));
+                    live.update(&stats);
+                    if let Some(progress) = progress {
+                        let _ = progress.try_send(RecordingProgress {
+                            room: stream_info.room.clone(),
+                            file: output.part().map(|part| part.path().to_path_buf()),
+                            bytes: stats.bytes_written,
+                            segments: stats.segments_downloaded,
+                            playlist_url: stream_info.hls_source.clone(),
+                            finished: None,
+                        });
+                    }
+
+                    let now = tokio::time::Instant::now();
+                    if !progress_interval.is_zero() && now >= next_progress_log {
+                        next_progress_log = now + progress_interval;
+                        tracing::info!(
+                            "{}: recording for {}, {} segments, {:.2} MB so far",
+                            stream_info.room,
+                            format_duration(started.elapsed().as_secs_f64()),
+                            stats.segments_downloaded,
+                            stats.bytes_written as f64 / 1024.0 / 1024.0
+                        );
+                    }
+
+                    let disk_file = output
+                        .part()
+                        .and_then(|part| Some((part.disk_file()?.to_path_buf(), part.size())));
+                    if let Some((path, written)) = disk_file {
+                        if let Some(stall) = growth.check(&path, written).await {
+                            match config.output_stall_action {
+                                StallAction::Warn => {
+                                    tracing::warn!(
+                                        "Output for {} stopped growing: {}",
+                                        stream_info.room,
+                                        stall
+                                    )
+                                }
+                                StallAction::Stop => return Err(Error::OutputStalled(stall)),
+                            }
+                        }
+                    }
+
+                    // Check if we need to split file
+                    let split = output.part().is_some_and(|part| {
+                        should_split_file(
+                            part.duration(),
+                            part.size(),
+                            max_duration_secs,
+                            max_filesize_bytes,
+                        )
+                    });
+                    if split {
+                        output.split().await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = written {
+        output.abandon().await;
+        bar.finish_and_clear();
+        return Err(e);
+    }
+
+    // Drops a segment the downloader stopped part-way through, then flushes
+    stats.files_created = output.files_created();
+    stats.files = output.finish().await?;
+    bar.finish_and_clear();
 
     Ok(stats)
 }
 
+/// Unparseable playlists in a row before giving up on the stream
+fn parse_failure_limit(configured: u32) -> u32 {
+    if configured == 0 {
+        PARSE_FAILURE_HARD_CAP
+    } else {
+        configured
+    }
+}
+
 fn should_split_file(
     duration: f64,
     size: u64,
@@ -219,29 +1216,265 @@ fn should_split_file(
     false
 }
 
-async fn create_output_file(
-    room: &str,
-    config: &RecordingConfig,
-    sequence: u32,
-) -> Result<(File, PathBuf)> {
-    let path = generate_output_path(
-        &config.output_directory,
-        &config.filename_pattern,
-        room,
-        sequence,
-    )?;
-
-    // Create parent directories if needed
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent).await?;
-    }
-
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&path)
-        .await?;
-
-    Ok((file, path))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::test_util::{serve, stream_info, test_dir};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_progress_stream_ends_with_finished() {
+        let client = ChaturbateClient::new(&crate::config::NetworkConfig::default()).unwrap();
+        let stream_info = stream_info("http://127.0.0.1:9/playlist.m3u8");
+        let config = RecordingConfig {
+            output_directory: std::env::temp_dir().to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        // Cancelled before the first poll, so nothing is recorded
+        let cancel_token = CancellationToken::new();
+        cancel_token.cancel();
+
+        let updates: Vec<_> = record_stream_progress(client, stream_info, config, cancel_token)
+            .collect()
+            .await;
+
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].room, "alice");
+        let stats = updates[0].finished.as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(stats.segments_downloaded, 0);
+    }
+
+    /// HTTP server for `record_stream`: `/playlist.m3u8` returns `playlist`,
+    /// any other path two TS packets
+    async fn serve_stream(playlist: impl Into<String>) -> String {
+        let playlist = playlist.into();
+        let mut packets = vec![0u8; 376];
+        packets[0] = 0x47;
+        packets[188] = 0x47;
+
+        serve(move |path| {
+            if path == "/playlist.m3u8" {
+                playlist.clone().into_bytes()
+            } else {
+                packets.clone()
+            }
+        })
+        .await
+    }
+
+    async fn record_test_playlist(
+        playlist: &'static str,
+        dir: &std::path::Path,
+    ) -> impl std::future::Future<Output = Result<RecordingStats>> {
+        let base = serve_stream(playlist).await;
+        let client = ChaturbateClient::new(&crate::config::NetworkConfig::default()).unwrap();
+        let stream_info = stream_info(&format!("{}/playlist.m3u8", base));
+        let config = RecordingConfig {
+            output_directory: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+
+        async move { record_stream(&client, &stream_info, &config, CancellationToken::new()).await }
+    }
+
+    #[tokio::test]
+    async fn test_live_progress_shows_polled_playlist() {
+        let dir = test_dir("live-progress");
+
+        let live = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:2\n\
+#EXT-X-MEDIA-SEQUENCE:0\n#EXTINF:2.0,\nseg0.ts\n";
+        let base = serve_stream(live).await;
+        let url = format!("{}/playlist.m3u8", base);
+        let client = ChaturbateClient::new(&crate::config::NetworkConfig::default()).unwrap();
+        // Progress is global; a room no other test records
+        let stream_info = StreamInfo {
+            room: "live_progress_room".to_string(),
+            ..stream_info(&url)
+        };
+        let config = RecordingConfig {
+            output_directory: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let cancel_token = CancellationToken::new();
+        let mut updates = record_stream_progress(client, stream_info, config, cancel_token.clone());
+
+        let first = tokio::time::timeout(Duration::from_secs(10), updates.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(first.finished.is_none());
+        let progress = live_progress("live_progress_room").unwrap();
+        assert_eq!(progress.playlist_url, url);
+        assert_eq!(progress.segments, 1);
+
+        cancel_token.cancel();
+        while updates.next().await.is_some() {}
+        assert!(live_progress("live_progress_room").is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_vod_playlist_downloaded_once_live_polled() {
+        let dir = test_dir("vod");
+
+        let vod = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:2\n\
+#EXT-X-MEDIA-SEQUENCE:0\n#EXTINF:2.0,\nseg0.ts\n#EXTINF:2.0,\nseg1.ts\n#EXT-X-ENDLIST\n";
+        let recording = record_test_playlist(vod, &dir.join("vod")).await;
+        let stats = tokio::time::timeout(Duration::from_secs(10), recording)
+            .await
+            .expect("VOD recording should stop by itself")
+            .unwrap();
+        assert_eq!(stats.segments_downloaded, 2);
+
+        // The same segments as a live window keep being polled for more
+        let live = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:2\n\
+#EXT-X-MEDIA-SEQUENCE:0\n#EXTINF:2.0,\nseg0.ts\n#EXTINF:2.0,\nseg1.ts\n";
+        let recording = record_test_playlist(live, &dir.join("live")).await;
+        assert!(tokio::time::timeout(Duration::from_millis(1500), recording)
+            .await
+            .is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_vod_download_stops_when_cancelled() {
+        let dir = test_dir("vod-cancel");
+
+        const SEGMENTS: u64 = 1000;
+        let mut vod = String::from(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:2\n\
+#EXT-X-MEDIA-SEQUENCE:0\n",
+        );
+        for i in 0..SEGMENTS {
+            vod.push_str(&format!("#EXTINF:2.0,\nseg{}.ts\n", i));
+        }
+        vod.push_str("#EXT-X-ENDLIST\n");
+        let base = serve_stream(vod).await;
+
+        let client = ChaturbateClient::new(&crate::config::NetworkConfig::default()).unwrap();
+        let stream_info = stream_info(&format!("{}/playlist.m3u8", base));
+        let config = RecordingConfig {
+            output_directory: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let cancel_token = CancellationToken::new();
+        let mut updates = record_stream_progress(client, stream_info, config, cancel_token.clone());
+
+        // Cancel as soon as the first segment is written
+        let finished = tokio::time::timeout(Duration::from_secs(10), async {
+            while let Some(update) = updates.next().await {
+                if let Some(result) = update.finished {
+                    return result;
+                }
+                cancel_token.cancel();
+            }
+            unreachable!("the stream ends with a finished update");
+        })
+        .await
+        .expect("cancelled VOD recording should stop")
+        .unwrap();
+        assert!(finished.segments_downloaded > 0);
+        assert!(finished.segments_downloaded < SEGMENTS);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_unparseable_playlist_ends_recording() {
+        let dir = test_dir("unparseable");
+
+        // An error page served with a 200, before and after re-discovery
+        let base = serve_stream("<html><body>Service unavailable</body></html>").await;
+        let url = format!("{}/playlist.m3u8", base);
+        let client = ChaturbateClient::new(&crate::config::NetworkConfig::default()).unwrap();
+        let stream_info = StreamInfo {
+            source: crate::stream::StreamSource::Url(url.clone()),
+            ..stream_info(&url)
+        };
+        let config = RecordingConfig {
+            output_directory: dir.to_string_lossy().into_owned(),
+            max_playlist_parse_failures: 2,
+            ..Default::default()
+        };
+
+        let recording = record_stream(&client, &stream_info, &config, CancellationToken::new());
+        let result = tokio::time::timeout(Duration::from_secs(10), recording)
+            .await
+            .expect("recording should give up");
+        assert!(matches!(result, Err(Error::M3u8(_))));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_failure_limit_has_hard_cap() {
+        assert_eq!(parse_failure_limit(5), 5);
+        assert_eq!(parse_failure_limit(0), PARSE_FAILURE_HARD_CAP);
+    }
+
+    #[test]
+    fn test_has_stalled() {
+        // Grew on disk
+        assert!(!has_stalled(1000, 5000, 1000, 5000, 256));
+        // Nothing written since the last check
+        assert!(!has_stalled(1000, 1000, 5000, 5000, 256));
+        // Written bytes may still be in the buffer
+        assert!(!has_stalled(1000, 1000, 1000, 1200, 256));
+        // More written than the buffer holds, yet no growth
+        assert!(has_stalled(1000, 1000, 1000, 5000, 256));
+        // Shrank
+        assert!(has_stalled(1000, 0, 1000, 5000, 256));
+    }
+
+    #[test]
+    fn test_lag_monitor_needs_sustained_lag() {
+        let mut lag = LagMonitor::default();
+
+        // One slow segment isn't enough
+        lag.record(Duration::from_secs(4), 2.0);
+        for _ in 0..3 {
+            lag.record(Duration::from_millis(500), 2.0);
+        }
+        assert!(lag.sustained_lag().is_none());
+
+        lag.record(Duration::from_millis(500), 2.0);
+        assert!(lag.sustained_lag().is_none());
+
+        for _ in 0..LAG_WINDOW {
+            lag.record(Duration::from_millis(1900), 2.0);
+        }
+        let ratio = lag.sustained_lag().unwrap();
+        assert!((ratio - 0.95).abs() < 1e-9);
+
+        lag.reset();
+        assert!(lag.sustained_lag().is_none());
+    }
+
+    #[test]
+    fn test_compute_rates() {
+        let mut stats = RecordingStats {
+            bytes_written: 10_000_000,
+            duration_seconds: 20.0,
+            ..Default::default()
+        };
+        stats.compute_rates(Duration::from_secs(25));
+
+        assert_eq!(stats.average_bitrate_mbps, 4.0);
+        assert_eq!(stats.throughput_bytes_per_second, 400_000.0);
+        assert_eq!(stats.elapsed_seconds, 25.0);
+    }
+
+    #[test]
+    fn test_compute_rates_zero_duration() {
+        let mut stats = RecordingStats {
+            bytes_written: 1024,
+            ..Default::default()
+        };
+        stats.compute_rates(Duration::ZERO);
+
+        assert_eq!(stats.average_bitrate_mbps, 0.0);
+        assert_eq!(stats.throughput_bytes_per_second, 0.0);
+    }
 }
@@ -1,22 +1,115 @@
+use bytes::Bytes;
+use futures::StreamExt;
+use m3u8_rs::{ByteRange, Map, MediaPlaylist, MediaSegment};
 use regex::Regex;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 
 use crate::api::ChaturbateClient;
+use crate::config::compile_segment_pattern;
 use crate::error::{Error, Result};
+use crate::stream::StreamInfo;
+
+/// Every MPEG-TS packet starts with this byte
+const TS_SYNC_BYTE: u8 = 0x47;
+const TS_PACKET_SIZE: u64 = 188;
+
+/// Sequence number pattern for `..._<n>.ts` URIs. Signed CDN URLs append a
+/// query string after the extension.
+const DEFAULT_SEGMENT_PATTERN: &str = r"_(\d+)\.ts(\?.*)?$";
+
+/// How far behind the last recorded segment a whole playlist may fall before
+/// it's treated as a sequence reset rather than a stale (cached) response
+const ROLLOVER_TOLERANCE: u64 = 2;
 
 pub struct SegmentTracker {
-    last_sequence: u64,
+    /// Sequence of the last segment recorded; `None` until the first one
+    last_sequence: Option<u64>,
     sequence_regex: Regex,
 }
 
 impl SegmentTracker {
     pub fn new() -> Result<Self> {
         Ok(Self {
-            last_sequence: 0,
-            sequence_regex: Regex::new(r"_(\d+)\.ts$")?,
+            last_sequence: None,
+            sequence_regex: Regex::new(DEFAULT_SEGMENT_PATTERN)?,
         })
     }
 
+    /// Tracker that reads sequence numbers with a custom `segment_pattern`
+    pub fn with_pattern(pattern: &str) -> Result<Self> {
+        Ok(Self {
+            last_sequence: None,
+            sequence_regex: compile_segment_pattern(pattern)?,
+        })
+    }
+
+    /// Segments of `playlist` not recorded yet, with their sequence numbers
+    /// and the `EXT-X-MAP` init segment that applies to each (fMP4 only).
+    ///
+    /// Sequence numbers come from `EXT-X-MEDIA-SEQUENCE` plus the segment's
+    /// position. Playlists without that tag (sequence 0) fall back to the
+    /// number in the URI, or to position alone when there is none. If the
+    /// whole playlist is well behind what was already recorded, the encoder
+    /// restarted and numbering starts over.
+    pub fn new_segments<'a>(
+        &mut self,
+        playlist: &'a MediaPlaylist,
+    ) -> Vec<(u64, &'a MediaSegment, Option<&'a Map>)> {
+        let sequences = self.sequences(playlist);
+
+        // A map applies to every segment after it until the next one
+        let mut map = None;
+        let maps: Vec<Option<&Map>> = playlist
+            .segments
+            .iter()
+            .map(|segment| {
+                if segment.map.is_some() {
+                    map = segment.map.as_ref();
+                }
+                map
+            })
+            .collect();
+
+        if let (Some(last), Some(&newest)) = (self.last_sequence, sequences.last()) {
+            if newest + ROLLOVER_TOLERANCE < last {
+                tracing::info!(
+                    "Segment sequence went back from {} to {}, assuming the stream restarted",
+                    last,
+                    newest
+                );
+                self.last_sequence = None;
+            }
+        }
+
+        sequences
+            .into_iter()
+            .zip(&playlist.segments)
+            .zip(maps)
+            .filter(|((seq, _), _)| self.is_new_segment(*seq))
+            .map(|((seq, segment), map)| (seq, segment, map))
+            .collect()
+    }
+
+    fn sequences(&self, playlist: &MediaPlaylist) -> Vec<u64> {
+        let by_position = |offset: u64| -> Vec<u64> {
+            (0..playlist.segments.len() as u64)
+                .map(|i| offset + i)
+                .collect()
+        };
+
+        if playlist.media_sequence > 0 {
+            return by_position(playlist.media_sequence);
+        }
+
+        playlist
+            .segments
+            .iter()
+            .map(|s| self.extract_sequence(&s.uri))
+            .collect::<Option<Vec<u64>>>()
+            .unwrap_or_else(|| by_position(0))
+    }
+
     pub fn extract_sequence(&self, uri: &str) -> Option<u64> {
         self.sequence_regex
             .captures(uri)
@@ -25,17 +118,17 @@ impl SegmentTracker {
     }
 
     pub fn is_new_segment(&self, sequence: u64) -> bool {
-        sequence > self.last_sequence
+        self.last_sequence.is_none_or(|last| sequence > last)
     }
 
     pub fn update_sequence(&mut self, sequence: u64) {
-        if sequence > self.last_sequence {
-            self.last_sequence = sequence;
+        if self.is_new_segment(sequence) {
+            self.last_sequence = Some(sequence);
         }
     }
 
     pub fn last_sequence(&self) -> u64 {
-        self.last_sequence
+        self.last_sequence.unwrap_or(0)
     }
 }
 
@@ -45,22 +138,158 @@ impl Default for SegmentTracker {
     }
 }
 
-pub async fn download_segment_with_retry(
+/// Follows SCTE-35 style cue tags (`#EXT-X-CUE-OUT` / `#EXT-X-CUE-IN`) across
+/// playlist refreshes to tell which segments fall inside an ad break. A tag
+/// applies to the segment it precedes, so the first segment after a cue-in is
+/// content again.
+pub struct AdBreakDetector {
+    cue_out_tags: Vec<String>,
+    cue_in_tags: Vec<String>,
+    in_break: bool,
+}
+
+impl AdBreakDetector {
+    /// Tags may be given with or without the leading `#EXT-`
+    pub fn new(cue_out_tags: &[String], cue_in_tags: &[String]) -> Self {
+        let normalize = |tags: &[String]| -> Vec<String> {
+            tags.iter()
+                .map(|tag| {
+                    let tag = tag.trim().trim_start_matches('#');
+                    tag.strip_prefix("EXT-").unwrap_or(tag).to_string()
+                })
+                .collect()
+        };
+
+        Self {
+            cue_out_tags: normalize(cue_out_tags),
+            cue_in_tags: normalize(cue_in_tags),
+            in_break: false,
+        }
+    }
+
+    /// Update the break state with `segment`'s tags; true if it is an ad
+    pub fn is_ad(&mut self, segment: &MediaSegment) -> bool {
+        for tag in &segment.unknown_tags {
+            if self.cue_in_tags.contains(&tag.tag) {
+                self.in_break = false;
+            } else if self.cue_out_tags.contains(&tag.tag) {
+                self.in_break = true;
+            }
+        }
+        self.in_break
+    }
+}
+
+/// Container format of a stream's media segments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SegmentFormat {
+    /// MPEG-TS `.ts` segments
+    #[default]
+    Ts,
+    /// Fragmented MP4 (CMAF) `.m4s` segments after an `EXT-X-MAP` init segment
+    Fmp4,
+}
+
+impl SegmentFormat {
+    pub fn detect(uri: &str, map: Option<&Map>) -> Self {
+        let path = uri.split('?').next().unwrap_or(uri);
+        if map.is_some() || path.ends_with(".m4s") || path.ends_with(".mp4") {
+            SegmentFormat::Fmp4
+        } else {
+            SegmentFormat::Ts
+        }
+    }
+
+    /// Extension of a recording made of these segments
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            SegmentFormat::Ts => "ts",
+            SegmentFormat::Fmp4 => "mp4",
+        }
+    }
+
+    /// Extension of a single segment file
+    pub fn segment_extension(self) -> &'static str {
+        match self {
+            SegmentFormat::Ts => "ts",
+            SegmentFormat::Fmp4 => "m4s",
+        }
+    }
+}
+
+/// Message sent from the downloader to the writer.
+///
+/// Segment bodies travel as a series of chunks so a whole segment is never
+/// held in memory; the writer commits or discards them as a unit.
+#[derive(Debug)]
+pub enum SegmentMessage {
+    /// Part of the body of the segment currently being downloaded
+    Chunk(Bytes),
+    /// The current segment arrived in full
+    Complete { sequence: u64, duration: f64 },
+    /// The current download attempt failed; discard its chunks
+    Abort,
+    /// Discovery was re-run and the downloader now follows this stream
+    StreamChanged(StreamInfo),
+    /// Segments from here on use this container; fMP4 carries the init
+    /// segment that must start every output file
+    Format {
+        format: SegmentFormat,
+        init: Option<Bytes>,
+    },
+}
+
+/// Stream a segment into the writer channel without buffering it in memory.
+///
+/// A failed attempt is followed by `SegmentMessage::Abort` so the writer can
+/// truncate whatever it already wrote before the retry starts. Returns the
+/// time the successful attempt spent on the network, without retry backoff
+/// or waiting for the writer, or `Error::Interrupted` once the writer has
+/// gone away.
+///
+/// With `verify_ts_sync`, a body that doesn't look like MPEG-TS (e.g. an
+/// HTML error page served with a 200) counts as a failed attempt.
+///
+/// Every retry after a failed attempt is added to `retries`.
+pub async fn stream_segment_with_retry(
     client: &ChaturbateClient,
     url: &str,
+    tx: &mpsc::Sender<SegmentMessage>,
     max_retries: u32,
-) -> Result<Vec<u8>> {
+    verify_ts_sync: bool,
+    retries: &mut u64,
+) -> Result<Duration> {
     let mut last_error = None;
     let delay = Duration::from_millis(600);
 
     for attempt in 0..max_retries {
-        match client.get_bytes(url).await {
-            Ok(data) => return Ok(data),
+        match stream_segment(client, url, tx, verify_ts_sync).await {
+            Ok(network_time) => return Ok(network_time),
+            Err(Error::Interrupted) => return Err(Error::Interrupted),
             Err(e) => {
-                last_error = Some(e);
+                send(tx, SegmentMessage::Abort).await?;
+
                 if attempt + 1 < max_retries {
+                    tracing::debug!(
+                        "Segment attempt {}/{} failed ({}), retrying in {:?}: {}",
+                        attempt + 1,
+                        max_retries,
+                        e,
+                        delay,
+                        url
+                    );
+                    *retries += 1;
                     tokio::time::sleep(delay).await;
+                } else {
+                    tracing::debug!(
+                        "Segment attempt {}/{} failed ({}), giving up: {}",
+                        attempt + 1,
+                        max_retries,
+                        e,
+                        url
+                    );
                 }
+                last_error = Some(e);
             }
         }
     }
@@ -70,23 +299,244 @@ pub async fn download_segment_with_retry(
     }))
 }
 
+/// Download an fMP4 init segment (`EXT-X-MAP`), honoring its byte range.
+/// Init segments are a few KB, so this one is read into memory.
+pub async fn fetch_init_segment(
+    client: &ChaturbateClient,
+    url: &str,
+    byte_range: Option<&ByteRange>,
+) -> Result<Bytes> {
+    let data = Bytes::from(client.get_bytes(url).await?);
+
+    let Some(range) = byte_range else {
+        return Ok(data);
+    };
+
+    let start = range.offset.unwrap_or(0) as usize;
+    let end = start + range.length as usize;
+    if end > data.len() {
+        return Err(Error::InvalidSegment(format!(
+            "init segment byte range {}..{} exceeds {} bytes: {}",
+            start,
+            end,
+            data.len(),
+            url
+        )));
+    }
+
+    Ok(data.slice(start..end))
+}
+
+async fn stream_segment(
+    client: &ChaturbateClient,
+    url: &str,
+    tx: &mpsc::Sender<SegmentMessage>,
+    verify_ts_sync: bool,
+) -> Result<Duration> {
+    let started = Instant::now();
+    let response = client.get_response(url).await?;
+    let mut network_time = started.elapsed();
+    let mut body = response.bytes_stream();
+    let mut sent: u64 = 0;
+
+    loop {
+        // Time spent in `send` is the writer's backpressure, not the network
+        let waiting = Instant::now();
+        let Some(chunk) = body.next().await else {
+            break;
+        };
+        network_time += waiting.elapsed();
+        let chunk = chunk?;
+        if verify_ts_sync && sent == 0 && !chunk.is_empty() {
+            validate_ts_start(&chunk, url)?;
+        }
+        sent += chunk.len() as u64;
+        send(tx, SegmentMessage::Chunk(chunk)).await?;
+    }
+
+    if verify_ts_sync {
+        validate_ts_length(sent, url)?;
+    }
+
+    Ok(network_time)
+}
+
+fn validate_ts_start(data: &[u8], url: &str) -> Result<()> {
+    if data[0] != TS_SYNC_BYTE {
+        return Err(Error::InvalidSegment(format!(
+            "expected MPEG-TS sync byte 0x47, got 0x{:02x}: {}",
+            data[0], url
+        )));
+    }
+    Ok(())
+}
+
+fn validate_ts_length(len: u64, url: &str) -> Result<()> {
+    if len == 0 || !len.is_multiple_of(TS_PACKET_SIZE) {
+        return Err(Error::InvalidSegment(format!(
+            "length {} is not a multiple of the {}-byte TS packet size: {}",
+            len, TS_PACKET_SIZE, url
+        )));
+    }
+    Ok(())
+}
+
+/// Send to the writer, mapping a closed channel to `Error::Interrupted`
+pub async fn send(tx: &mpsc::Sender<SegmentMessage>, message: SegmentMessage) -> Result<()> {
+    tx.send(message).await.map_err(|_| Error::Interrupted)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stream::test_util::serve;
+
+    #[tokio::test]
+    async fn test_stream_segment_with_retry_counts_retries() {
+        // Every request gets an HTML page instead of TS data
+        let base = serve(|_| b"<html>blocked</html>".to_vec()).await;
+        let url = format!("{}/seg_1.ts", base);
+
+        let client = ChaturbateClient::new(&crate::config::NetworkConfig::default()).unwrap();
+        let (tx, _rx) = mpsc::channel(16);
+        let mut retries = 0;
+
+        let result = stream_segment_with_retry(&client, &url, &tx, 3, true, &mut retries).await;
+        assert!(matches!(result, Err(Error::InvalidSegment(_))));
+        assert_eq!(retries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_network_time_excludes_writer_backpressure() {
+        let mut packets = vec![0u8; 376];
+        packets[0] = 0x47;
+        packets[188] = 0x47;
+        let base = serve(move |_| packets.clone()).await;
+        let url = format!("{}/seg_1.ts", base);
+
+        let client = ChaturbateClient::new(&crate::config::NetworkConfig::default()).unwrap();
+        // Full, so the segment waits until the writer catches up
+        let (tx, mut rx) = mpsc::channel(1);
+        tx.send(SegmentMessage::Abort).await.unwrap();
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            while rx.recv().await.is_some() {}
+        });
+
+        let mut retries = 0;
+        let network_time = stream_segment_with_retry(&client, &url, &tx, 3, true, &mut retries)
+            .await
+            .unwrap();
+        assert!(
+            network_time < Duration::from_millis(400),
+            "{:?}",
+            network_time
+        );
+
+        drop(tx);
+        writer.await.unwrap();
+    }
 
     #[test]
     fn test_extract_sequence() {
         let tracker = SegmentTracker::new().unwrap();
 
+        assert_eq!(tracker.extract_sequence("playlist_480p_123.ts"), Some(123));
         assert_eq!(
-            tracker.extract_sequence("playlist_480p_123.ts"),
+            tracker.extract_sequence("chunklist_720p30fps_456.ts"),
+            Some(456)
+        );
+        assert_eq!(tracker.extract_sequence("invalid.m3u8"), None);
+    }
+
+    #[test]
+    fn test_extract_sequence_with_query_string() {
+        let tracker = SegmentTracker::new().unwrap();
+
+        assert_eq!(
+            tracker.extract_sequence("media_123.ts?token=abc"),
             Some(123)
         );
         assert_eq!(
-            tracker.extract_sequence("chunklist_720p30fps_456.ts"),
+            tracker.extract_sequence("chunklist_720p30fps_456.ts?sig=a_9.ts&exp=1700000000"),
             Some(456)
         );
-        assert_eq!(tracker.extract_sequence("invalid.m3u8"), None);
+        assert_eq!(tracker.extract_sequence("media.ts?seq_5"), None);
+    }
+
+    #[test]
+    fn test_extract_sequence_custom_pattern() {
+        let tracker = SegmentTracker::with_pattern(r"/seg-(\d+)\.m4s").unwrap();
+
+        assert_eq!(
+            tracker.extract_sequence("https://cdn/x/seg-17.m4s"),
+            Some(17)
+        );
+        assert_eq!(tracker.extract_sequence("media_123.ts"), None);
+        assert!(SegmentTracker::with_pattern(r"seg-\d+").is_err());
+    }
+
+    #[test]
+    fn test_extract_sequence_path_only() {
+        let tracker = SegmentTracker::new().unwrap();
+
+        assert_eq!(
+            tracker.extract_sequence("https://edge.example.com/live/room/media_789.ts"),
+            Some(789)
+        );
+        // A number elsewhere in the path isn't a segment sequence
+        assert_eq!(tracker.extract_sequence("/live_42/media.ts"), None);
+    }
+
+    #[test]
+    fn test_ad_break_detection() {
+        let playlist = "#EXTM3U\n\
+            #EXT-X-MEDIA-SEQUENCE:10\n\
+            #EXTINF:2.0,\n\
+            media_10.ts\n\
+            #EXT-X-CUE-OUT:DURATION=4\n\
+            #EXTINF:2.0,\n\
+            ad_1.ts\n\
+            #EXT-X-CUE-OUT-CONT:ElapsedTime=2,Duration=4\n\
+            #EXTINF:2.0,\n\
+            ad_2.ts\n\
+            #EXT-X-CUE-IN\n\
+            #EXTINF:2.0,\n\
+            media_13.ts\n";
+        let playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+
+        let mut detector = AdBreakDetector::new(
+            &[
+                "EXT-X-CUE-OUT".to_string(),
+                "#EXT-X-CUE-OUT-CONT".to_string(),
+            ],
+            &["X-CUE-IN".to_string()],
+        );
+        let mut tracker = SegmentTracker::new().unwrap();
+        let mut kept = Vec::new();
+        for (seq, segment, _) in tracker.new_segments(&playlist) {
+            if !detector.is_ad(segment) {
+                kept.push(segment.uri.clone());
+            }
+            tracker.update_sequence(seq);
+        }
+
+        assert_eq!(kept, vec!["media_10.ts", "media_13.ts"]);
+        // Ad segments still count as seen
+        assert_eq!(tracker.last_sequence(), 13);
+    }
+
+    #[test]
+    fn test_validate_ts_start() {
+        assert!(validate_ts_start(&[0x47, 0x40, 0x00], "seg.ts").is_ok());
+        assert!(validate_ts_start(b"<!DOCTYPE html>", "seg.ts").is_err());
+    }
+
+    #[test]
+    fn test_validate_ts_length() {
+        assert!(validate_ts_length(188 * 100, "seg.ts").is_ok());
+        assert!(validate_ts_length(188 * 100 + 1, "seg.ts").is_err());
+        assert!(validate_ts_length(0, "seg.ts").is_err());
     }
 
     #[test]
@@ -98,4 +548,113 @@ mod tests {
         assert!(!tracker.is_new_segment(1));
         assert!(tracker.is_new_segment(2));
     }
+
+    fn media_playlist(media_sequence: u64, uris: &[&str]) -> MediaPlaylist {
+        MediaPlaylist {
+            media_sequence,
+            segments: uris
+                .iter()
+                .map(|uri| MediaSegment {
+                    uri: uri.to_string(),
+                    duration: 2.0,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn record_all(tracker: &mut SegmentTracker, playlist: &MediaPlaylist) -> Vec<u64> {
+        let sequences: Vec<u64> = tracker
+            .new_segments(playlist)
+            .into_iter()
+            .map(|(s, _, _)| s)
+            .collect();
+        for &seq in &sequences {
+            tracker.update_sequence(seq);
+        }
+        sequences
+    }
+
+    #[test]
+    fn test_new_segments_uses_media_sequence() {
+        let mut tracker = SegmentTracker::new().unwrap();
+
+        let playlist = media_playlist(100, &["a_7.ts", "a_8.ts", "a_9.ts"]);
+        assert_eq!(record_all(&mut tracker, &playlist), vec![100, 101, 102]);
+
+        let playlist = media_playlist(101, &["a_8.ts", "a_9.ts", "a_10.ts"]);
+        assert_eq!(record_all(&mut tracker, &playlist), vec![103]);
+    }
+
+    #[test]
+    fn test_new_segments_init_map() {
+        let mut tracker = SegmentTracker::new().unwrap();
+        let mut playlist = media_playlist(10, &["seg_10.m4s", "seg_11.m4s", "seg_12.m4s"]);
+        playlist.segments[0].map = Some(Map {
+            uri: "init_a.mp4".to_string(),
+            ..Default::default()
+        });
+        playlist.segments[2].map = Some(Map {
+            uri: "init_b.mp4".to_string(),
+            ..Default::default()
+        });
+
+        let maps: Vec<&str> = tracker
+            .new_segments(&playlist)
+            .into_iter()
+            .map(|(_, _, map)| map.unwrap().uri.as_str())
+            .collect();
+        assert_eq!(maps, vec!["init_a.mp4", "init_a.mp4", "init_b.mp4"]);
+    }
+
+    #[test]
+    fn test_segment_format_detect() {
+        let map = Map::default();
+        assert_eq!(SegmentFormat::detect("media_1.ts", None), SegmentFormat::Ts);
+        assert_eq!(
+            SegmentFormat::detect("media_1.m4s?sig=x", None),
+            SegmentFormat::Fmp4
+        );
+        assert_eq!(
+            SegmentFormat::detect("media_1", Some(&map)),
+            SegmentFormat::Fmp4
+        );
+    }
+
+    #[test]
+    fn test_new_segments_rollover() {
+        let mut tracker = SegmentTracker::new().unwrap();
+
+        let playlist = media_playlist(5000, &["a.ts", "b.ts", "c.ts"]);
+        assert_eq!(record_all(&mut tracker, &playlist), vec![5000, 5001, 5002]);
+
+        // A stale response one segment behind is not a restart
+        let playlist = media_playlist(4999, &["z.ts", "a.ts", "b.ts"]);
+        assert!(record_all(&mut tracker, &playlist).is_empty());
+
+        // Encoder restarted; keep recording from the new numbering
+        let playlist = media_playlist(1, &["x.ts", "y.ts"]);
+        assert_eq!(record_all(&mut tracker, &playlist), vec![1, 2]);
+        assert_eq!(tracker.last_sequence(), 2);
+    }
+
+    #[test]
+    fn test_new_segments_without_numeric_suffix() {
+        let mut tracker = SegmentTracker::new().unwrap();
+
+        // No media sequence and no number in the URIs: position only
+        let playlist = media_playlist(0, &["seg-a.ts", "seg-b.ts"]);
+        assert_eq!(record_all(&mut tracker, &playlist), vec![0, 1]);
+
+        // No media sequence, numbered URIs
+        let mut tracker = SegmentTracker::new().unwrap();
+        let playlist = media_playlist(0, &["media_41.ts", "media_42.ts"]);
+        assert_eq!(record_all(&mut tracker, &playlist), vec![41, 42]);
+
+        // No media sequence, numbered URIs with signed query strings
+        let mut tracker = SegmentTracker::new().unwrap();
+        let playlist = media_playlist(0, &["media_41.ts?sig=x", "media_42.ts?sig=y"]);
+        assert_eq!(record_all(&mut tracker, &playlist), vec![41, 42]);
+    }
 }
@@ -0,0 +1,61 @@
+//! Helpers shared by the stream module's tests
+
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::stream::StreamInfo;
+
+/// `cbr-<name>-<pid>` in the temp directory, with anything a previous run
+/// left there removed. Tests create it as needed and remove it at the end.
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cbr-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// A 720p30 stream of room `alice` playing the media playlist at `url`
+pub fn stream_info(url: &str) -> StreamInfo {
+    StreamInfo {
+        hls_source: url.to_string(),
+        room: "alice".to_string(),
+        resolution: 720,
+        framerate: 30,
+        bandwidth: 0,
+        title: None,
+        tags: Vec::new(),
+        source: Default::default(),
+    }
+}
+
+/// Local HTTP server answering every request with a 200 whose body is
+/// `respond(path)`. Returns the base URL, without a trailing slash.
+pub async fn serve(respond: impl Fn(&str) -> Vec<u8> + Send + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.ends_with(b"\r\n\r\n") {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let body = respond(path);
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = socket.write_all(head.as_bytes()).await;
+            let _ = socket.write_all(&body).await;
+        }
+    });
+
+    base
+}
@@ -0,0 +1,979 @@
+use bytes::Bytes;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, BufWriter};
+
+use crate::config::{RecordingConfig, Timezone};
+use crate::error::Result;
+use crate::fs::{generate_output_path, mark_active, resolve_existing, ActiveFileGuard};
+use crate::output::console;
+use crate::stream::playlist::{build_vod_playlist, PlaylistEntry};
+use crate::stream::segment::SegmentFormat;
+
+/// Name of the fMP4 init segment inside a playlist-mode segment folder
+const INIT_SEGMENT_NAME: &str = "init.mp4";
+
+/// MPEG-TS packet size; appended files are trimmed to a multiple of it
+const TS_PACKET_SIZE: u64 = 188;
+
+/// All output files of one recording. Parts are opened when their first
+/// segment arrives, so a split or a container change never leaves an empty
+/// file behind.
+pub struct RecordingOutput {
+    room: String,
+    config: RecordingConfig,
+    format: SegmentFormat,
+    /// fMP4 init segment that starts every part
+    init: Option<Bytes>,
+    part: Option<OutputPart>,
+    files_created: u32,
+    /// Finished parts, in order
+    finished: Vec<PathBuf>,
+}
+
+impl RecordingOutput {
+    pub fn new(room: &str, config: &RecordingConfig) -> Self {
+        Self {
+            room: room.to_string(),
+            config: config.clone(),
+            format: SegmentFormat::default(),
+            init: None,
+            part: None,
+            files_created: 0,
+            finished: Vec::new(),
+        }
+    }
+
+    pub fn files_created(&self) -> u32 {
+        self.files_created
+    }
+
+    /// The part currently being written, if a segment has arrived since the
+    /// last split
+    pub fn part(&self) -> Option<&OutputPart> {
+        self.part.as_ref()
+    }
+
+    /// Switch container. Different segment types (or init segments) can't
+    /// share a file, so any change starts a new part.
+    pub async fn set_format(&mut self, format: SegmentFormat, init: Option<Bytes>) -> Result<()> {
+        if format != self.format || init != self.init {
+            if self.part.is_some() {
+                tracing::info!(
+                    "Segment format for {} changed to {:?}, starting a new file",
+                    self.room,
+                    format
+                );
+            }
+            self.split().await?;
+            self.format = format;
+            self.init = init;
+        }
+        Ok(())
+    }
+
+    pub async fn write_chunk(&mut self, data: &[u8]) -> Result<()> {
+        let part = match self.part.take() {
+            Some(part) => part,
+            None => self.open_part().await?,
+        };
+        self.part.insert(part).write_chunk(data).await
+    }
+
+    pub async fn abort_segment(&mut self) -> Result<()> {
+        if let Some(part) = self.part.as_mut() {
+            part.abort_segment().await?;
+        }
+        Ok(())
+    }
+
+    /// Commit the segment currently arriving; returns its size in bytes
+    pub async fn complete_segment(&mut self, duration: f64) -> Result<u64> {
+        match self.part.as_mut() {
+            Some(part) => part.complete_segment(duration).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Close the current part; the next segment starts a new file
+    pub async fn split(&mut self) -> Result<()> {
+        if let Some(part) = self.part.take() {
+            match part.finish().await? {
+                Some(path) => self.finished.push(path),
+                // Removed as empty; its name is free for the next part
+                None => self.files_created -= 1,
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the current part when the recording failed; what was written
+    /// stays as a `.part` file, and a part without a segment is removed
+    pub async fn abandon(mut self) {
+        if let Some(part) = self.part.as_mut() {
+            if let Err(e) = part.flush().await {
+                tracing::warn!("Failed to flush {}: {}", part.path().display(), e);
+            }
+        }
+    }
+
+    /// Close the last part and return every file written, in order
+    pub async fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.split().await?;
+
+        let single_files = !self.config.write_playlist && !self.config.keep_segments;
+        if self.config.write_concat_list && single_files && self.finished.len() > 1 {
+            let list_path = self.finished[0].with_extension("concat.txt");
+            let list_dir = list_path.parent().unwrap_or(Path::new(""));
+            tokio::fs::write(&list_path, build_concat_list(&self.finished, list_dir)).await?;
+            tracing::info!("Wrote concat list {}", list_path.display());
+        }
+
+        Ok(std::mem::take(&mut self.finished))
+    }
+
+    async fn open_part(&mut self) -> Result<OutputPart> {
+        let part = OutputPart::create(
+            &self.room,
+            &self.config,
+            self.files_created,
+            self.format,
+            self.init.as_ref(),
+        )
+        .await?;
+
+        if self.files_created == 0 {
+            tracing::info!("Recording {} to {}", self.room, part.path().display());
+        } else {
+            tracing::info!("Split recording, new file: {}", part.path().display());
+        }
+        self.files_created += 1;
+
+        Ok(part)
+    }
+}
+
+impl Drop for RecordingOutput {
+    fn drop(&mut self) {
+        // Dropped without finish(): the recording failed. Don't leave an
+        // empty file behind; partial data stays as a `.part` file.
+        if let Some(part) = self.part.take() {
+            part.remove_if_empty();
+            part.write_buffered();
+        }
+    }
+}
+
+/// One output part of a recording. A new part is started on every split.
+///
+/// By default a part is a single `.ts` (or `.mp4` for fMP4 streams) file that
+/// segments are appended to, after the init segment if there is one. It is
+/// written as `<name>.ts.part` and renamed when the part is finished, so only
+/// complete files carry the final name. With `append`, an MPEG-TS part whose
+/// `.part` or final file already exists continues that file instead of
+/// starting it over. With
+/// `write_playlist`, each segment is kept as its own file in a `<basename>/`
+/// folder and a `<basename>.m3u8` VOD playlist listing them is written when
+/// the part is finished. `keep_segments` keeps the folder alone, with
+/// segments named `<room>_<nnnnnn>.ts`.
+pub struct OutputPart {
+    /// The finished media file, the `.m3u8` playlist in playlist mode, or the
+    /// segment folder when only segments are kept
+    path: PathBuf,
+    mode: PartMode,
+    /// Bytes of complete segments in this part
+    size: u64,
+    /// Media duration of complete segments in this part
+    duration: f64,
+    /// Number of complete segments in this part
+    segments: u64,
+    /// Bytes of the segment currently arriving
+    pending: u64,
+    /// Continues an existing file, which must survive even if this part
+    /// never completes a segment
+    appended: bool,
+    _active: ActiveFileGuard,
+}
+
+enum PartMode {
+    Concat {
+        file: BufWriter<File>,
+        /// Where the file lives until the part is finished
+        part_path: PathBuf,
+    },
+    Segments {
+        segment_dir: PathBuf,
+        /// Name of `segment_dir`, which prefixes playlist URIs
+        stem: String,
+        /// Segment file names are `<prefix>_<index>.<extension>`
+        prefix: String,
+        /// Zero-padded width of the segment index
+        index_width: usize,
+        extension: &'static str,
+        /// Write a VOD playlist of the segments when the part is finished
+        write_playlist: bool,
+        /// Playlist URI of the fMP4 init segment
+        init_uri: Option<String>,
+        /// File and playlist URI of the segment currently arriving
+        current: Option<(BufWriter<File>, PathBuf, String)>,
+        entries: Vec<PlaylistEntry>,
+        buffer_size: usize,
+    },
+}
+
+impl OutputPart {
+    pub async fn create(
+        room: &str,
+        config: &RecordingConfig,
+        sequence: u32,
+        format: SegmentFormat,
+        init: Option<&Bytes>,
+    ) -> Result<Self> {
+        let media_path = generate_output_path(
+            &config.output_directory,
+            &config.filename_pattern,
+            room,
+            sequence,
+            filename_timezone(config),
+            match format {
+                SegmentFormat::Ts => config.ts_extension(),
+                SegmentFormat::Fmp4 => format.file_extension(),
+            },
+        )?;
+
+        // Appends continue the existing file under its own name
+        let appending = config.append
+            && format == SegmentFormat::Ts
+            && !config.write_playlist
+            && !config.keep_segments;
+        let media_path = if appending {
+            media_path
+        } else {
+            let stamp = filename_timezone(config).format(chrono::Utc::now(), "%Y%m%d-%H%M%S");
+            let segmented = config.write_playlist || config.keep_segments;
+            resolve_existing(&media_path, config.on_existing_file, &stamp, |path| {
+                if segmented {
+                    path.with_extension("").exists() || path.with_extension("m3u8").exists()
+                } else {
+                    path.exists() || in_progress_path(path).exists()
+                }
+            })?
+        };
+
+        // Create parent directories if needed
+        if let Some(parent) = media_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let buffer_size = config.write_buffer_kb as usize * 1024;
+
+        if config.write_playlist || config.keep_segments {
+            let segment_dir = media_path.with_extension("");
+            tokio::fs::create_dir_all(&segment_dir).await?;
+
+            let stem = segment_dir
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let init_uri = match init {
+                Some(data) => {
+                    tokio::fs::write(segment_dir.join(INIT_SEGMENT_NAME), data).await?;
+                    Some(format!("{}/{}", stem, INIT_SEGMENT_NAME))
+                }
+                None => None,
+            };
+
+            // Kept segments are named after the room and never split, so
+            // their index gets more room
+            let (path, prefix, index_width) = if config.write_playlist {
+                (media_path.with_extension("m3u8"), stem.clone(), 5)
+            } else {
+                (segment_dir.clone(), room.to_string(), 6)
+            };
+
+            Ok(Self {
+                path,
+                _active: mark_active(&segment_dir),
+                mode: PartMode::Segments {
+                    segment_dir,
+                    stem,
+                    prefix,
+                    index_width,
+                    extension: format.segment_extension(),
+                    write_playlist: config.write_playlist,
+                    init_uri,
+                    current: None,
+                    entries: Vec::new(),
+                    buffer_size,
+                },
+                size: 0,
+                duration: 0.0,
+                segments: 0,
+                pending: 0,
+                appended: false,
+            })
+        } else {
+            let part_path = in_progress_path(&media_path);
+
+            // fMP4 files start with their init segment, so only TS appends
+            let appended = appending && prepare_append(&media_path, &part_path).await?;
+            let file = if appended {
+                open_for_append(&part_path).await?
+            } else {
+                open_truncated(&part_path).await?
+            };
+            // Aborts truncate back to here: the end of the existing data
+            let mut size = file.metadata().await?.len();
+            let mut file = BufWriter::with_capacity(buffer_size, file);
+
+            // The init segment is the file header; aborts truncate back to it
+            if let Some(data) = init {
+                file.write_all(data).await?;
+                size = data.len() as u64;
+            }
+
+            if appended {
+                tracing::info!(
+                    "Appending to {} ({} bytes already written)",
+                    media_path.display(),
+                    size
+                );
+            }
+
+            Ok(Self {
+                _active: mark_active(&part_path),
+                path: media_path,
+                mode: PartMode::Concat { file, part_path },
+                size,
+                duration: 0.0,
+                segments: 0,
+                pending: 0,
+                appended,
+            })
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    /// The file being written while a single one holds the whole part;
+    /// kept segments are separate files
+    pub fn disk_file(&self) -> Option<&Path> {
+        match &self.mode {
+            PartMode::Concat { part_path, .. } => Some(part_path),
+            PartMode::Segments { .. } => None,
+        }
+    }
+
+    /// Append a chunk of the segment currently arriving
+    pub async fn write_chunk(&mut self, data: &[u8]) -> Result<()> {
+        match &mut self.mode {
+            PartMode::Concat { file, .. } => file.write_all(data).await?,
+            PartMode::Segments {
+                segment_dir,
+                stem,
+                prefix,
+                index_width,
+                extension,
+                current,
+                entries,
+                buffer_size,
+                ..
+            } => {
+                if current.is_none() {
+                    let index = entries.len();
+                    let name = format!(
+                        "{}_{:0width$}.{}",
+                        prefix,
+                        index,
+                        extension,
+                        width = *index_width
+                    );
+                    let path = segment_dir.join(&name);
+                    let file = BufWriter::with_capacity(*buffer_size, open_truncated(&path).await?);
+                    *current = Some((file, path, format!("{}/{}", stem, name)));
+                }
+                if let Some((file, _, _)) = current {
+                    file.write_all(data).await?;
+                }
+            }
+        }
+
+        self.pending += data.len() as u64;
+        Ok(())
+    }
+
+    /// Throw away whatever was written for the segment currently arriving
+    pub async fn abort_segment(&mut self) -> Result<()> {
+        match &mut self.mode {
+            PartMode::Concat { file, .. } => truncate_to(file, self.size).await?,
+            PartMode::Segments { current, .. } => {
+                if let Some((file, path, _)) = current.take() {
+                    drop(file);
+                    tokio::fs::remove_file(path).await?;
+                }
+            }
+        }
+
+        self.pending = 0;
+        Ok(())
+    }
+
+    /// Commit the segment currently arriving; returns its size in bytes
+    pub async fn complete_segment(&mut self, duration: f64) -> Result<u64> {
+        if let PartMode::Segments {
+            current, entries, ..
+        } = &mut self.mode
+        {
+            if let Some((mut file, _, uri)) = current.take() {
+                file.flush().await?;
+                entries.push(PlaylistEntry { uri, duration });
+            }
+        }
+
+        let bytes = self.pending;
+        self.size += bytes;
+        self.duration += duration;
+        self.segments += 1;
+        self.pending = 0;
+        Ok(bytes)
+    }
+
+    /// Write out buffered data without closing the part
+    async fn flush(&mut self) -> Result<()> {
+        match &mut self.mode {
+            PartMode::Concat { file, .. } => file.flush().await?,
+            PartMode::Segments { current, .. } => {
+                if let Some((file, _, _)) = current {
+                    file.flush().await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Best-effort write of buffered data from `Drop`, straight through the
+    /// std file so nothing waits on the runtime. Skipped if tokio still has
+    /// a write of its own in flight.
+    fn write_buffered(self) {
+        let writer = match self.mode {
+            PartMode::Concat { file, .. } => file,
+            PartMode::Segments {
+                current: Some((file, _, _)),
+                ..
+            } => file,
+            PartMode::Segments { current: None, .. } => return,
+        };
+        if writer.buffer().is_empty() {
+            return;
+        }
+
+        let buffered = writer.buffer().to_vec();
+        let result = match writer.into_inner().try_into_std() {
+            Ok(mut file) => std::io::Write::write_all(&mut file, &buffered),
+            Err(_) => Err(std::io::Error::other("a write is still in progress")),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to flush {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Delete this part's files if it never completed a segment. Used when a
+    /// recording fails and the part can't be finished normally.
+    fn remove_if_empty(&self) {
+        if self.segments > 0 || self.appended {
+            return;
+        }
+
+        let result = match &self.mode {
+            PartMode::Concat { part_path, .. } => std::fs::remove_file(part_path),
+            PartMode::Segments { segment_dir, .. } => std::fs::remove_dir_all(segment_dir),
+        };
+        if let Err(e) = result {
+            tracing::warn!(
+                "Failed to remove empty output for {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    /// Discard any incomplete segment, flush, and move the file to its final
+    /// name (or write the playlist). A part that never completed a segment
+    /// holds no media, so its files are removed and `None` is returned.
+    pub async fn finish(mut self) -> Result<Option<PathBuf>> {
+        if self.pending > 0 {
+            self.abort_segment().await?;
+        }
+
+        let empty = self.segments == 0 && !self.appended;
+
+        match self.mode {
+            PartMode::Concat {
+                mut file,
+                part_path,
+            } => {
+                file.flush().await?;
+                // Close before renaming; Windows refuses to rename open files
+                drop(file);
+                if empty {
+                    tokio::fs::remove_file(&part_path).await?;
+                } else {
+                    tokio::fs::rename(&part_path, &self.path).await?;
+                }
+            }
+            PartMode::Segments {
+                segment_dir,
+                entries,
+                init_uri,
+                write_playlist,
+                ..
+            } => {
+                if empty {
+                    // Only ever holds init.mp4 at this point
+                    tokio::fs::remove_dir_all(&segment_dir).await?;
+                } else if write_playlist {
+                    let playlist = build_vod_playlist(&entries, init_uri.as_deref());
+                    tokio::fs::write(&self.path, playlist).await?;
+                }
+            }
+        }
+
+        if empty {
+            tracing::debug!("Removed empty output {}", self.path.display());
+            return Ok(None);
+        }
+
+        Ok(Some(self.path))
+    }
+}
+
+/// ffmpeg concat-demuxer list of `parts`. Parts in `list_dir` are listed by
+/// name; others (e.g. a date folder that changed mid-recording) by full path.
+fn build_concat_list(parts: &[PathBuf], list_dir: &Path) -> String {
+    let mut list = String::new();
+
+    for part in parts {
+        let entry = match part.strip_prefix(list_dir) {
+            Ok(relative) if part.parent() == Some(list_dir) => relative.to_path_buf(),
+            _ => std::path::absolute(part).unwrap_or_else(|_| part.clone()),
+        };
+        // Quote for ffmpeg: ' becomes '\''
+        let entry = entry.to_string_lossy().replace('\'', "'\\''");
+        list.push_str(&format!("file '{}'\n", entry));
+    }
+
+    list
+}
+
+/// Timezone for filename date/time variables. Fixed by the config, so every
+/// part of a recording session uses the same one.
+fn filename_timezone(config: &RecordingConfig) -> Timezone {
+    if config.use_utc_timestamps {
+        Timezone::Utc
+    } else {
+        console::timezone()
+    }
+}
+
+/// `<path>.part`, the name a media file has while it is being written
+fn in_progress_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Get an existing recording ready to be continued at `part_path`: a
+/// leftover `.part` file is used as is, otherwise a finished `media_path` is
+/// moved back to its in-progress name. Either way, a segment cut off at the
+/// end is trimmed to whole TS packets. Returns false if neither exists.
+async fn prepare_append(media_path: &Path, part_path: &Path) -> Result<bool> {
+    if !tokio::fs::try_exists(part_path).await? {
+        if !tokio::fs::try_exists(media_path).await? {
+            return Ok(false);
+        }
+        tokio::fs::rename(media_path, part_path).await?;
+    }
+
+    let file = OpenOptions::new().write(true).open(part_path).await?;
+    let len = file.metadata().await?.len();
+    let whole_packets = len - len % TS_PACKET_SIZE;
+    if whole_packets != len {
+        file.set_len(whole_packets).await?;
+    }
+
+    Ok(true)
+}
+
+async fn open_for_append(path: &Path) -> Result<File> {
+    let file = OpenOptions::new().append(true).open(path).await?;
+    Ok(file)
+}
+
+async fn open_truncated(path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .await?;
+
+    Ok(file)
+}
+
+/// Discard everything past `len` and continue writing from there. Buffered
+/// data is flushed first, since it may include earlier, complete segments.
+async fn truncate_to(file: &mut BufWriter<File>, len: u64) -> Result<()> {
+    file.flush().await?;
+    let file = file.get_mut();
+    file.set_len(len).await?;
+    file.seek(SeekFrom::Start(len)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OnExistingFile;
+    use crate::stream::test_util::test_dir;
+
+    fn test_config(dir: &Path) -> RecordingConfig {
+        RecordingConfig {
+            output_directory: dir.to_string_lossy().into_owned(),
+            filename_pattern: "{{.Username}}_test".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_concat_list() {
+        let dir = Path::new("recordings");
+        let parts = vec![dir.join("room_test.ts"), dir.join("it's_1.ts")];
+
+        assert_eq!(
+            build_concat_list(&parts, dir),
+            "file 'room_test.ts'\nfile 'it'\\''s_1.ts'\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concat_list_for_two_part_split() {
+        let dir = test_dir("concat");
+
+        let config = RecordingConfig {
+            write_concat_list: true,
+            ..test_config(&dir)
+        };
+
+        let mut output = RecordingOutput::new("room", &config);
+        output.write_chunk(b"first").await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+        output.split().await.unwrap();
+        output.write_chunk(b"second").await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+
+        let files = output.finish().await.unwrap();
+        assert_eq!(
+            files,
+            vec![dir.join("room_test.ts"), dir.join("room_test_1.ts")]
+        );
+
+        let list = std::fs::read_to_string(dir.join("room_test.concat.txt")).unwrap();
+        assert_eq!(list, "file 'room_test.ts'\nfile 'room_test_1.ts'\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keep_segments_writes_numbered_files() {
+        let dir = test_dir("keep");
+
+        let config = RecordingConfig {
+            keep_segments: true,
+            ..test_config(&dir)
+        };
+
+        let mut output = RecordingOutput::new("room", &config);
+        for data in [&b"first"[..], b"second"] {
+            output.write_chunk(data).await.unwrap();
+            output.complete_segment(2.0).await.unwrap();
+        }
+
+        let files = output.finish().await.unwrap();
+        let segment_dir = dir.join("room_test");
+        assert_eq!(files, vec![segment_dir.clone()]);
+        assert_eq!(
+            std::fs::read(segment_dir.join("room_000000.ts")).unwrap(),
+            b"first"
+        );
+        assert_eq!(
+            std::fs::read(segment_dir.join("room_000001.ts")).unwrap(),
+            b"second"
+        );
+        assert!(!dir.join("room_test.m3u8").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dropped_output_keeps_buffered_data() {
+        let dir = test_dir("drop");
+
+        let mut output = RecordingOutput::new("room", &test_config(&dir));
+        output.write_chunk(b"segment").await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+        // Still in the write buffer
+        drop(output);
+
+        assert_eq!(
+            std::fs::read(dir.join("room_test.ts.part")).unwrap(),
+            b"segment"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_output_keeps_written_data() {
+        let dir = test_dir("abandon");
+
+        let mut output = RecordingOutput::new("room", &test_config(&dir));
+        output.write_chunk(b"segment").await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+        output.write_chunk(b"partial").await.unwrap();
+        output.abandon().await;
+
+        assert_eq!(
+            std::fs::read(dir.join("room_test.ts.part")).unwrap(),
+            b"segmentpartial"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    async fn record_once(dir: &Path, append: bool, data: &[u8]) -> PathBuf {
+        let config = RecordingConfig {
+            append,
+            ..test_config(dir)
+        };
+        record_with(&config, data).await.unwrap()
+    }
+
+    async fn record_with(config: &RecordingConfig, data: &[u8]) -> Result<PathBuf> {
+        let mut output = RecordingOutput::new("room", config);
+        output.write_chunk(data).await?;
+        output.complete_segment(2.0).await?;
+        Ok(output.finish().await?.remove(0))
+    }
+
+    /// Record `data` with `strategy` after a recording of `first`
+    async fn record_over(dir: &Path, strategy: OnExistingFile, data: &[u8]) -> Result<PathBuf> {
+        let _ = std::fs::remove_dir_all(dir);
+        record_once(dir, false, b"first").await;
+
+        let config = RecordingConfig {
+            on_existing_file: strategy,
+            ..test_config(dir)
+        };
+        record_with(&config, data).await
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_suffix() {
+        let dir = test_dir("suffix");
+
+        let path = record_over(&dir, OnExistingFile::Suffix, b"second")
+            .await
+            .unwrap();
+        assert_eq!(path, dir.join("room_test.1.ts"));
+        assert_eq!(std::fs::read(dir.join("room_test.ts")).unwrap(), b"first");
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        // The next free number, skipping taken ones
+        let config = test_config(&dir);
+        assert_eq!(
+            record_with(&config, b"third").await.unwrap(),
+            dir.join("room_test.2.ts")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_overwrite() {
+        let dir = test_dir("overwrite");
+
+        let path = record_over(&dir, OnExistingFile::Overwrite, b"second")
+            .await
+            .unwrap();
+        assert_eq!(path, dir.join("room_test.ts"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_skip() {
+        let dir = test_dir("skip");
+
+        let result = record_over(&dir, OnExistingFile::Skip, b"second").await;
+        assert!(matches!(result, Err(crate::error::Error::OutputExists(_))));
+        assert_eq!(std::fs::read(dir.join("room_test.ts")).unwrap(), b"first");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_timestamp() {
+        let dir = test_dir("stamp");
+
+        let path = record_over(&dir, OnExistingFile::Timestamp, b"second")
+            .await
+            .unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let stamp = name
+            .strip_prefix("room_test_")
+            .and_then(|rest| rest.strip_suffix(".ts"))
+            .unwrap_or_else(|| panic!("unexpected name {}", name));
+        assert!(
+            chrono::NaiveDateTime::parse_from_str(stamp, "%Y%m%d-%H%M%S").is_ok(),
+            "{}",
+            name
+        );
+        assert_eq!(std::fs::read(dir.join("room_test.ts")).unwrap(), b"first");
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_append_to_existing_file() {
+        let dir = test_dir("append");
+
+        let first = [0x47u8; 188];
+        let second = [0x48u8; 188];
+        let path = record_once(&dir, true, &first).await;
+        assert_eq!(record_once(&dir, true, &second).await, path);
+        assert_eq!(std::fs::read(&path).unwrap(), [first, second].concat());
+
+        // A killed run's .part is continued, minus its cut-off packet
+        std::fs::rename(&path, dir.join("room_test.ts.part")).unwrap();
+        let mut part = std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join("room_test.ts.part"))
+            .unwrap();
+        std::io::Write::write_all(&mut part, &[0x47; 50]).unwrap();
+        drop(part);
+        record_once(&dir, true, &first).await;
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            [first, second, first].concat()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_part_file_renamed_on_finish() {
+        let dir = test_dir("part");
+
+        let config = test_config(&dir);
+
+        let mut output = RecordingOutput::new("room", &config);
+        output.write_chunk(b"first").await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+        assert!(dir.join("room_test.ts.part").exists());
+        assert!(!dir.join("room_test.ts").exists());
+
+        // Every split part is renamed, not just the last
+        output.split().await.unwrap();
+        output.write_chunk(b"second").await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+        assert!(dir.join("room_test.ts").exists());
+        assert!(dir.join("room_test_1.ts.part").exists());
+
+        output.finish().await.unwrap();
+        assert_eq!(
+            std::fs::read(dir.join("room_test_1.ts")).unwrap(),
+            b"second"
+        );
+        assert!(!dir.join("room_test_1.ts.part").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_buffered_output_preserves_data() {
+        let dir = test_dir("buffer");
+
+        // A 1 KB buffer so segments straddle buffer boundaries
+        let config = RecordingConfig {
+            write_buffer_kb: 1,
+            ..test_config(&dir)
+        };
+
+        let segment = |n: u8, len: usize| vec![n; len];
+        let mut output = RecordingOutput::new("room", &config);
+
+        output.write_chunk(&segment(1, 700)).await.unwrap();
+        output.write_chunk(&segment(1, 700)).await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+        // Aborted while part of the previous segment may still be buffered
+        output.write_chunk(&segment(9, 300)).await.unwrap();
+        output.abort_segment().await.unwrap();
+        output.write_chunk(&segment(2, 3000)).await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+        output.split().await.unwrap();
+        output.write_chunk(&segment(3, 500)).await.unwrap();
+        output.complete_segment(2.0).await.unwrap();
+
+        let files = output.finish().await.unwrap();
+
+        let mut expected = segment(1, 1400);
+        expected.extend(segment(2, 3000));
+        assert_eq!(std::fs::read(&files[0]).unwrap(), expected);
+        assert_eq!(std::fs::read(&files[1]).unwrap(), segment(3, 500));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_first_segment_leaves_no_file() {
+        let dir = test_dir("empty");
+
+        let config = test_config(&dir);
+
+        // The first segment starts arriving, then the download fails for good
+        let mut output = RecordingOutput::new("room", &config);
+        output
+            .set_format(SegmentFormat::Fmp4, Some(Bytes::from_static(b"init")))
+            .await
+            .unwrap();
+        output.write_chunk(b"partial").await.unwrap();
+        output.abort_segment().await.unwrap();
+
+        let files = output.finish().await.unwrap();
+        assert!(files.is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        // Same, but the recording errors out and never finishes
+        let mut output = RecordingOutput::new("room", &config);
+        output.write_chunk(b"partial").await.unwrap();
+        drop(output);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,37 @@
+use std::process::Command;
+
+/// With `--format json`, stdout must parse line by line; banner, console
+/// messages and log lines belong on stderr
+#[test]
+fn test_json_format_keeps_stdout_parseable() {
+    let dir = std::env::temp_dir().join(format!("cbr-json-output-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    // Nothing listens on port 1, so the recording fails fast
+    let url_file = dir.join("urls.txt");
+    std::fs::write(&url_file, "http://127.0.0.1:1/playlist.m3u8\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chaturbate-recorder"))
+        .arg("--url-file")
+        .arg(&url_file)
+        .args(["--format", "json", "--config"])
+        .arg(dir.join("missing.toml"))
+        .arg("-o")
+        .arg(dir.join("out"))
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.trim().is_empty());
+    for line in stdout.lines() {
+        assert!(
+            serde_json::from_str::<serde_json::Value>(line).is_ok(),
+            "not JSON: {}",
+            line
+        );
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Checking stream1"), "{}", stderr);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}